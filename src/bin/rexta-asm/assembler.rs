@@ -1,13 +1,617 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as _};
 
-use rexta::u24::U24;
+use rexta::executable::{Executable, Section, SectionKind, Symbol};
+use rexta::u24::{U24, parse_number};
 
 use crate::ast::Address;
+use crate::ast::FRegister;
 use crate::ast::Instruction;
 use crate::ast::Register;
+use crate::ast::Statement;
+
+/// A problem with one line of the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// Line number, from 1
+    pub line: usize,
+    /// Column the offending text starts at, from 1
+    pub column: usize,
+    /// The offending text
+    pub text: String,
+    pub reason: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {} '{}'", self.line, self.column, self.reason, self.text)
+    }
+}
+
+/// A successfully assembled program.
+pub struct Assembly {
+    /// The program image
+    pub bytes: Vec<u8>,
+    /// Where `bytes` is meant to be loaded
+    pub origin: U24,
+    /// Addresses and bytes beside the source, then the symbol table
+    pub listing: String,
+    /// Every label and constant, one per line; see `symbol_map`
+    pub map: String,
+    /// The image with its load address, entry point and labels
+    pub executable: Executable,
+}
+
+/// A problem found while parsing a line; `text` is a slice of the line,
+/// so the column can be worked out from where it points.
+struct LineError<'a> {
+    text: &'a str,
+    reason: String,
+}
+
+impl<'a> LineError<'a> {
+    fn new(text: &'a str, reason: impl Into<String>) -> Self {
+        LineError { text, reason: reason.into() }
+    }
+}
+
+/// A line of source, or a line produced by expanding a macro or `.rept`.
+struct SourceLine<'a> {
+    number: usize,
+    raw: Cow<'a, str>,
+    /// The expansion the line came from, if any
+    expansion: Option<Expansion>,
+}
+
+/// Where a macro or `.rept` was expanded, for reporting errors in its
+/// lines.
+#[derive(Clone)]
+struct Expansion {
+    column: usize,
+    /// What was expanded, such as `macro save`
+    context: String,
+}
+
+impl<'a> SourceLine<'a> {
+    fn new(number: usize, raw: &'a str) -> Self {
+        SourceLine { number, raw: Cow::Borrowed(raw), expansion: None }
+    }
+
+    /// The statement on the line, without its comment.
+    fn text(&self) -> &str {
+        strip_comment(&self.raw).trim()
+    }
+
+    /// The column `text`, a slice of the line, starts at.
+    fn column(&self, text: &str) -> usize {
+        let offset = (text.as_ptr() as usize)
+            .checked_sub(self.raw.as_ptr() as usize)
+            .filter(|offset| *offset <= self.raw.len())
+            .unwrap_or(0);
+        self.raw[..offset].chars().count() + 1
+    }
+
+    /// An error in an expansion is reported where it was expanded.
+    fn error(&self, err: LineError) -> AsmError {
+        match &self.expansion {
+            Some(expansion) => AsmError {
+                line: self.number,
+                column: expansion.column,
+                text: err.text.to_string(),
+                reason: format!("in {}: {}", expansion.context, err.reason),
+            },
+            None => AsmError {
+                line: self.number,
+                column: self.column(err.text),
+                text: err.text.to_string(),
+                reason: err.reason,
+            },
+        }
+    }
+
+    /// The label the line starts with, if any, and the statement after it,
+    /// as in `start: LOADI.1 R0, 10`.
+    fn split_label(&self) -> (Option<&str>, &str) {
+        let text = self.text();
+        if let Some(label) = text.strip_suffix(':') {
+            return (Some(label), "");
+        }
+        match text.split_once(':') {
+            Some((label, rest)) if is_symbol(label.trim_end()) => (Some(label.trim_end()), rest.trim_start()),
+            _ => (None, text),
+        }
+    }
+
+    /// A line holding just the label at the start of this one.
+    fn label_line(&self, label: &str) -> SourceLine<'a> {
+        let start = label.as_ptr() as usize - self.raw.as_ptr() as usize;
+        let end = start + self.raw[start..].find(':').map_or(label.len(), |i| i + 1);
+        let raw = match &self.raw {
+            Cow::Borrowed(raw) => Cow::Borrowed(&raw[..end]),
+            Cow::Owned(raw) => Cow::Owned(raw[..end].to_string()),
+        };
+        SourceLine { number: self.number, raw, expansion: self.expansion.clone() }
+    }
+
+    /// The first occurrence of `name` in the line, or the whole statement.
+    fn locate(&self, name: &str) -> &str {
+        let text = self.text();
+        match text.find(name) {
+            Some(pos) => &text[pos..pos + name.len()],
+            None => text,
+        }
+    }
+
+    /// The line's expansion, or a new one starting at `text` on this line.
+    fn expansion(&self, text: &str, context: String) -> Expansion {
+        self.expansion.clone().unwrap_or_else(|| Expansion { column: self.column(text), context })
+    }
+}
+
+/// A macro defined with `.macro`: its parameter names, and the statements
+/// of its body.
+struct Macro {
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+/// Expands macros and `.rept` blocks, turning the source into the lines to
+/// assemble.
+struct Expander<'a> {
+    macros: HashMap<String, Macro>,
+    /// Constants defined so far, for `.rept` counts
+    symbols: Symbols,
+    /// Number of expansions so far, for `\@`
+    expansions: usize,
+    lines: Vec<SourceLine<'a>>,
+    errors: Vec<AsmError>,
+}
+
+impl<'a> Expander<'a> {
+    /// How deeply expansions may nest, which stops a macro that invokes
+    /// itself from expanding forever.
+    const MAX_DEPTH: usize = 16;
+
+    /// Expand every line from `lines`.
+    fn expand(&mut self, lines: &mut dyn Iterator<Item = SourceLine<'a>>, depth: usize) {
+        while let Some(line) = lines.next() {
+            let (label, text) = line.split_label();
+            let directive = first_word(text).to_lowercase();
+            // A label before something that expands stays where it is
+            let expands = matches!(directive.as_str(), ".macro" | ".rept") || self.macros.contains_key(first_word(text));
+            if let Some(label) = label.filter(|_| expands) {
+                self.lines.push(line.label_line(label));
+            }
+            match directive.as_str() {
+                ".macro" => self.define(&line, lines),
+                ".rept" => self.repeat(&line, lines, depth),
+                ".endm" => self.errors.push(line.error(LineError::new(text, "no .macro to end"))),
+                ".endr" => self.errors.push(line.error(LineError::new(text, "no .rept to end"))),
+                _ if text.is_empty() && label.is_none() => {}
+                _ => self.statement(line, depth),
+            }
+        }
+    }
+
+    /// Take the statements of a block from `lines`, up to the `end`
+    /// directive that closes it; blocks opened by `start` inside it nest.
+    /// None if the block is never closed.
+    fn block(lines: &mut dyn Iterator<Item = SourceLine<'a>>, start: &str, end: &str) -> Option<Vec<String>> {
+        let mut body = Vec::new();
+        let mut nested = 0;
+        for line in lines {
+            let text = line.text();
+            let directive = first_word(line.split_label().1).to_lowercase();
+            if directive == end {
+                if nested == 0 {
+                    return Some(body);
+                }
+                nested -= 1;
+            } else if directive == start {
+                nested += 1;
+            }
+            if !text.is_empty() {
+                body.push(text.to_string());
+            }
+        }
+        None
+    }
+
+    /// Define a macro from its `.macro` line, taking its body from `lines`.
+    fn define(&mut self, line: &SourceLine<'a>, lines: &mut dyn Iterator<Item = SourceLine<'a>>) {
+        let text = line.split_label().1;
+        let (directive, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let args = args.trim();
+        let (name, params) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let params: Vec<&str> = params.split(',').map(str::trim).filter(|s| !s.is_empty()).collect();
+
+        let body = Self::block(lines, ".macro", ".endm");
+        let err = if body.is_none() {
+            LineError::new(directive, "missing .endm")
+        } else if !is_symbol(name) {
+            LineError::new(if name.is_empty() { directive } else { name }, "invalid macro name")
+        } else if let Some(param) = params.iter().find(|param| !is_symbol(param)) {
+            LineError::new(param, "invalid parameter name")
+        } else if self.macros.contains_key(name) {
+            LineError::new(name, "already defined")
+        } else {
+            let params = params.into_iter().map(str::to_string).collect();
+            self.macros.insert(name.to_string(), Macro { params, body: body.unwrap_or_default() });
+            return;
+        };
+        self.errors.push(line.error(err));
+    }
+
+    /// Expand a `.rept` block, taking its body from `lines`.
+    fn repeat<'l>(&mut self, line: &'l SourceLine<'a>, lines: &mut dyn Iterator<Item = SourceLine<'a>>, depth: usize) {
+        let text = line.split_label().1;
+        let (directive, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let (count_expr, counter) = match args.split_once(',') {
+            Some((count, counter)) => (count, Some(counter.trim())),
+            None => (args, None),
+        };
+        let at = |arg: &'l str| if arg.trim().is_empty() { directive } else { arg.trim() };
+
+        let body = Self::block(lines, ".rept", ".endr");
+        let count = self
+            .symbols
+            .eval_defined(count_expr)
+            .and_then(|value| u32::try_from(value).ok().filter(|value| *value <= 0xFF_FFFF));
+        let err = match (body, count) {
+            (None, _) => LineError::new(directive, "missing .endr"),
+            _ if depth == Self::MAX_DEPTH => LineError::new(directive, "expansion too deep"),
+            (_, None) => LineError::new(at(count_expr), "expected a count"),
+            _ if counter.is_some_and(|counter| !is_symbol(counter)) => {
+                LineError::new(at(counter.unwrap_or_default()), "invalid counter name")
+            }
+            (Some(body), Some(count)) => {
+                let expansion = line.expansion(directive, ".rept".to_string());
+                for i in 0..count {
+                    self.expansions += 1;
+                    let mut iteration = Vec::new();
+                    if let Some(counter) = counter {
+                        iteration.push(format!("{} .set {}", counter, i));
+                    }
+                    iteration.extend(body.iter().map(|text| substitute(text, &[], &[], self.expansions)));
+                    let mut iteration = iteration.into_iter().map(|text| SourceLine {
+                        number: line.number,
+                        raw: Cow::Owned(text),
+                        expansion: Some(expansion.clone()),
+                    });
+                    self.expand(&mut iteration, depth + 1);
+                }
+                return;
+            }
+        };
+        self.errors.push(line.error(err));
+    }
+
+    /// Add a line to assemble, expanding it if it invokes a macro.
+    fn statement(&mut self, line: SourceLine<'a>, depth: usize) {
+        let text = line.split_label().1;
+        if let Some((name, kind, expr)) = parse_constant(text) {
+            // Only for `.rept` counts; the passes report any problems
+            if let Some(value) = self.symbols.eval_defined(expr) {
+                self.symbols.define(name, value, kind);
+            }
+        }
+
+        let (name, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let Some(mac) = self.macros.get(name) else {
+            self.lines.push(line);
+            return;
+        };
+        let args: Vec<&str> = args.split(',').map(str::trim).filter(|s| !s.is_empty()).collect();
+        if depth == Self::MAX_DEPTH {
+            self.errors.push(line.error(LineError::new(name, "expansion too deep")));
+            return;
+        }
+        if args.len() != mac.params.len() {
+            let reason = format!("expected {} argument(s)", mac.params.len());
+            self.errors.push(line.error(LineError::new(name, reason)));
+            return;
+        }
+
+        self.expansions += 1;
+        let params: Vec<&str> = mac.params.iter().map(String::as_str).collect();
+        let body: Vec<String> = mac.body.iter().map(|text| substitute(text, &params, &args, self.expansions)).collect();
+        let expansion = line.expansion(name, format!("macro {}", name));
+        let mut body = body.into_iter().map(|text| SourceLine {
+            number: line.number,
+            raw: Cow::Owned(text),
+            expansion: Some(expansion.clone()),
+        });
+        self.expand(&mut body, depth + 1);
+    }
+}
+
+/// Replace each `\param` in a macro body line with its argument, and `\@`
+/// with the number of the expansion, so that a label such as `loop\@` is
+/// different in every expansion.
+fn substitute(text: &str, params: &[&str], args: &[&str], expansion: usize) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let len = after.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(after.len());
+        if let Some(arg) = params.iter().position(|param| *param == &after[..len]).map(|i| args[i]) {
+            out.push_str(arg);
+            rest = &after[len..];
+        } else if let Some(after) = after.strip_prefix('@') {
+            out.push_str(&expansion.to_string());
+            rest = after;
+        } else {
+            // Not ours, such as a string escape; `\\` is kept whole
+            let len = if after.starts_with('\\') { 1 } else { 0 };
+            out.push('\\');
+            out.push_str(&after[..len]);
+            rest = &after[len..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The first word of a statement, such as its mnemonic or directive.
+fn first_word(text: &str) -> &str {
+    text.split(char::is_whitespace).next().unwrap_or("")
+}
+
+/// Split `text` into lines and expand the macros and `.rept` blocks in it.
+fn expand_macros<'a>(text: &'a str, errors: &mut Vec<AsmError>) -> Vec<SourceLine<'a>> {
+    let mut expander = Expander {
+        macros: HashMap::new(),
+        symbols: Symbols::new(),
+        expansions: 0,
+        lines: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut lines = text.lines().enumerate().map(|(i, raw)| SourceLine::new(i + 1, raw));
+    expander.expand(&mut lines, 0);
+    errors.extend(expander.errors);
+    expander.lines
+}
+
+/// Labels and constants, for evaluating operands.
+struct Symbols {
+    values: HashMap<String, i64>,
+    /// Names defined with `.equ` or `.set` rather than as labels
+    constants: HashSet<String>,
+    /// Names defined with `.set`, which may be redefined
+    variables: HashSet<String>,
+    /// In the first pass, names not defined yet stand for 0: that pass
+    /// only sizes each statement, and sizes don't depend on values
+    lenient: bool,
+    /// The first undefined name met since `take_unknown`
+    unknown: RefCell<Option<String>>,
+    /// Whether the value being evaluated stood 0 in for an undefined name
+    guessed: Cell<bool>,
+    /// The last global label, which names starting with `.` belong to
+    scope: String,
+    /// Register names defined with `.def` or `.alias`, and the registers
+    /// they stand for
+    aliases: HashMap<String, String>,
+}
+
+impl Symbols {
+    fn new() -> Self {
+        Symbols {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            variables: HashSet::new(),
+            lenient: true,
+            unknown: RefCell::new(None),
+            guessed: Cell::new(false),
+            scope: String::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Get ready for the second pass: keep the labels, and forget the
+    /// constants so they are defined again in order.
+    fn second_pass(&mut self) {
+        for name in self.constants.drain() {
+            self.values.remove(&name);
+        }
+        self.variables.clear();
+        self.aliases.clear();
+        self.lenient = false;
+    }
+
+    /// Define a label or constant. Fails if the name is taken, unless it is
+    /// a `.set` variable being set again.
+    fn define(&mut self, name: &str, value: i64, kind: SymbolKind) -> bool {
+        let name = &*self.qualify(name);
+        let redefining = kind == SymbolKind::Variable && self.variables.contains(name);
+        if self.values.contains_key(name) && !redefining {
+            return false;
+        }
+        self.values.insert(name.to_string(), value);
+        if kind != SymbolKind::Label {
+            self.constants.insert(name.to_string());
+        }
+        if kind == SymbolKind::Variable {
+            self.variables.insert(name.to_string());
+        }
+        true
+    }
+
+    /// Evaluate `expr`: numbers, names and function calls such as
+    /// `lo(table)` joined by `+` and `-`.
+    fn eval(&self, expr: &str) -> Option<i64> {
+        self.eval_with(expr, self.lenient)
+    }
+
+    /// Evaluate `expr` using only names defined earlier in the source, for
+    /// values such as `.org` that decide where later statements go.
+    fn eval_defined(&self, expr: &str) -> Option<i64> {
+        self.eval_with(expr, false)
+    }
+
+    fn eval_with(&self, expr: &str, lenient: bool) -> Option<i64> {
+        let mut total = 0i64;
+        let mut negate = false;
+        let mut rest = expr.trim();
+        loop {
+            let end = next_operator(rest);
+            let term = rest[..end].trim();
+            // An empty first term allows a leading sign
+            let value = if term.is_empty() && end < rest.len() && total == 0 && !negate {
+                0
+            } else {
+                self.term(term, lenient)?
+            };
+            total = if negate { total.checked_sub(value)? } else { total.checked_add(value)? };
+            if end == rest.len() {
+                return Some(total);
+            }
+            negate = rest[end..].starts_with('-');
+            rest = &rest[end + 1..];
+        }
+    }
+
+    fn term(&self, term: &str, lenient: bool) -> Option<i64> {
+        if let Some((name, arg)) = term.strip_suffix(')').and_then(|call| call.split_once('(')) {
+            let value = self.eval_with(arg, lenient)?;
+            return match name.trim().to_ascii_lowercase().as_str() {
+                "lo" => Some(value & 0xFF),
+                "hi" => Some(value >> 8 & 0xFF),
+                "bank" => Some(value >> 16 & 0xFF),
+                "lo16" => Some(value & 0xFFFF),
+                "hi16" => Some(value >> 8 & 0xFFFF),
+                _ => None,
+            };
+        }
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            return parse_number(term).ok().map(i64::from);
+        }
+        if !is_symbol(term) {
+            return None;
+        }
+        match self.values.get(&*self.qualify(term)) {
+            Some(value) => Some(*value),
+            None if lenient => {
+                self.guessed.set(true);
+                Some(0)
+            }
+            None => {
+                self.unknown.borrow_mut().get_or_insert_with(|| term.to_string());
+                None
+            }
+        }
+    }
+
+    /// The full name of a local name such as `.loop`, which is local to the
+    /// last global label: `main.loop` after `main:`. Other names are
+    /// global.
+    fn qualify<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        if name.starts_with('.') {
+            Cow::Owned(format!("{}{}", self.scope, name))
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+
+    /// The register `s` names, directly or through an alias.
+    fn register(&self, s: &str) -> Option<Register> {
+        parse_register(self.aliases.get(s).map_or(s, String::as_str))
+    }
+
+    /// The floating-point register `s` names, directly or through an alias.
+    fn fregister(&self, s: &str) -> Option<FRegister> {
+        parse_fregister(self.aliases.get(s).map_or(s, String::as_str))
+    }
+
+    /// Name `target`, a register or an alias of one, `name`. An alias may
+    /// be pointed at another register later.
+    fn alias<'t>(&mut self, name: &'t str, target: &'t str) -> Result<(), LineError<'t>> {
+        let register = self.aliases.get(target).map_or(target, String::as_str).to_string();
+        if !is_symbol(name) || parse_register(name).is_some() || parse_fregister(name).is_some() {
+            Err(LineError::new(name, "invalid alias name"))
+        } else if parse_register(&register).is_none() && parse_fregister(&register).is_none() {
+            Err(LineError::new(target, "expected a register"))
+        } else {
+            self.aliases.insert(name.to_string(), register);
+            Ok(())
+        }
+    }
+
+    /// Take the first undefined name evaluation has met, if any.
+    fn take_unknown(&self) -> Option<String> {
+        self.unknown.borrow_mut().take()
+    }
+
+    /// Evaluate `expr` as a `T`, failing if it is out of range. A value
+    /// that stood 0 in for an undefined name may be wrong, so in the first
+    /// pass it only has to evaluate: the statement's size is all that
+    /// matters there.
+    fn value<T: TryFrom<i64> + Default>(&self, expr: &str) -> Option<T> {
+        self.guessed.set(false);
+        let value = self.eval(expr)?;
+        T::try_from(value).ok().or_else(|| self.guessed.get().then(T::default))
+    }
+
+    /// Evaluate a `bits`-bit immediate. Negative values down to
+    /// -2^(bits-1) are encoded in two's complement, so `-1` and `0xFF` are
+    /// the same 8-bit immediate.
+    fn immediate(&self, expr: &str, bits: u32) -> Option<u32> {
+        let value = self.value::<i64>(expr)?;
+        if (-(1 << (bits - 1))..1 << bits).contains(&value) {
+            Some((value & ((1 << bits) - 1)) as u32)
+        } else {
+            self.guessed.get().then_some(0)
+        }
+    }
+
+    /// Evaluate an address: a value from 0 to 0xFFFFFF.
+    fn address(&self, expr: &str) -> Option<Address> {
+        let value = self.value::<u32>(expr)?;
+        if value <= 0xFF_FFFF {
+            Some(U24::new(value))
+        } else {
+            self.guessed.get().then(|| U24::new(0))
+        }
+    }
+}
+
+/// The position of the first `+` or `-` in `expr` outside parentheses, or
+/// the end of `expr`.
+fn next_operator(expr: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in expr.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' | '-' if depth == 0 => return i,
+            _ => {}
+        }
+    }
+    expr.len()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymbolKind {
+    Label,
+    /// Defined with `.equ`
+    Constant,
+    /// Defined with `.set`
+    Variable,
+}
+
+/// True if `s` can name a label or constant: a letter, `_` or `.`, then
+/// letters, digits, `_` or `.`.
+fn is_symbol(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
 
 impl Instruction {
-    pub fn encode(&self) -> Vec<u8> {
+    /// Encode the instruction, given the address it will be loaded at.
+    /// Fails if a branch target is out of range.
+    pub fn encode(&self, pc: U24) -> Result<Vec<u8>, String> {
         let mut bytes = vec![];
         bytes.extend_from_slice(&self.opcode_bytes());
 
@@ -38,11 +642,21 @@ impl Instruction {
             | Instruction::ROR3 { rd }
             | Instruction::POP1 { rd }
             | Instruction::POP2 { rd }
-            | Instruction::POP3 { rd } => vec![rd.encode() << 4],
+            | Instruction::POP3 { rd }
+            | Instruction::SEX2 { rd }
+            | Instruction::SEX3 { rd }
+            | Instruction::BSWAP2 { rd }
+            | Instruction::BSWAP3 { rd }
+            | Instruction::MOVSPR { rd }
+            | Instruction::DAA { rd }
+            | Instruction::CPUID { rd }
+            | Instruction::MOVPC { rd } => vec![rd.encode() << 4],
 
-            Instruction::PUSH1 { rs } | Instruction::PUSH2 { rs } | Instruction::PUSH3 { rs } => {
-                vec![rs.encode()]
-            }
+            Instruction::PUSH1 { rs }
+            | Instruction::PUSH2 { rs }
+            | Instruction::PUSH3 { rs }
+            | Instruction::MOVRSP { rs }
+            | Instruction::JSRR { rs } => vec![rs.encode()],
 
             Instruction::ADD1 { rd, rs }
             | Instruction::SUB1 { rd, rs }
@@ -67,42 +681,149 @@ impl Instruction {
             | Instruction::CMP3 { rd, rs }
             | Instruction::TST1 { rd, rs }
             | Instruction::TST2 { rd, rs }
-            | Instruction::TST3 { rd, rs } => vec![rs.encode() | rd.encode() << 4],
+            | Instruction::TST3 { rd, rs }
+            | Instruction::MUL1 { rd, rs }
+            | Instruction::MUL2 { rd, rs }
+            | Instruction::MUL3 { rd, rs }
+            | Instruction::DIV1 { rd, rs }
+            | Instruction::DIV2 { rd, rs }
+            | Instruction::DIV3 { rd, rs }
+            | Instruction::MOD1 { rd, rs }
+            | Instruction::MOD2 { rd, rs }
+            | Instruction::MOD3 { rd, rs }
+            | Instruction::SHLR1 { rd, rs }
+            | Instruction::SHLR2 { rd, rs }
+            | Instruction::SHLR3 { rd, rs }
+            | Instruction::SHRR1 { rd, rs }
+            | Instruction::SHRR2 { rd, rs }
+            | Instruction::SHRR3 { rd, rs }
+            | Instruction::CLZ1 { rd, rs }
+            | Instruction::CLZ2 { rd, rs }
+            | Instruction::CLZ3 { rd, rs }
+            | Instruction::POPCNT1 { rd, rs }
+            | Instruction::POPCNT2 { rd, rs }
+            | Instruction::POPCNT3 { rd, rs }
+            | Instruction::XCHG1 { rd, rs }
+            | Instruction::XCHG2 { rd, rs }
+            | Instruction::XCHG3 { rd, rs }
+            | Instruction::CMOVZ1 { rd, rs }
+            | Instruction::CMOVZ2 { rd, rs }
+            | Instruction::CMOVZ3 { rd, rs }
+            | Instruction::CMOVNZ1 { rd, rs }
+            | Instruction::CMOVNZ2 { rd, rs }
+            | Instruction::CMOVNZ3 { rd, rs }
+            | Instruction::CMOVC1 { rd, rs }
+            | Instruction::CMOVC2 { rd, rs }
+            | Instruction::CMOVC3 { rd, rs }
+            | Instruction::LOADR1 { rd, rs }
+            | Instruction::LOADR2 { rd, rs }
+            | Instruction::LOADR3 { rd, rs }
+            | Instruction::STORER1 { rd, rs }
+            | Instruction::STORER2 { rd, rs }
+            | Instruction::STORER3 { rd, rs }
+            | Instruction::TAS { rd, rs }
+            | Instruction::LOADPI1 { rd, rs }
+            | Instruction::LOADPI2 { rd, rs }
+            | Instruction::LOADPI3 { rd, rs }
+            | Instruction::STOREPD1 { rd, rs }
+            | Instruction::STOREPD2 { rd, rs }
+            | Instruction::STOREPD3 { rd, rs } => vec![rs.encode() | rd.encode() << 4],
 
-            Instruction::LOADI1 { rd, imm } | Instruction::ADDI1 { rd, imm } => {
+            Instruction::LOADI1 { rd, imm }
+            | Instruction::ADDI1 { rd, imm }
+            | Instruction::CMPI1 { rd, imm }
+            | Instruction::SUBI1 { rd, imm }
+            | Instruction::ANDI1 { rd, imm }
+            | Instruction::ORI1 { rd, imm }
+            | Instruction::XORI1 { rd, imm }
+            | Instruction::SHLI1 { rd, imm }
+            | Instruction::SHLI2 { rd, imm }
+            | Instruction::SHLI3 { rd, imm }
+            | Instruction::SHRI1 { rd, imm }
+            | Instruction::SHRI2 { rd, imm }
+            | Instruction::SHRI3 { rd, imm }
+            | Instruction::ROLI1 { rd, imm }
+            | Instruction::ROLI2 { rd, imm }
+            | Instruction::ROLI3 { rd, imm }
+            | Instruction::RORI1 { rd, imm }
+            | Instruction::RORI2 { rd, imm }
+            | Instruction::RORI3 { rd, imm }
+            | Instruction::BSET1 { rd, imm }
+            | Instruction::BSET2 { rd, imm }
+            | Instruction::BSET3 { rd, imm }
+            | Instruction::BCLR1 { rd, imm }
+            | Instruction::BCLR2 { rd, imm }
+            | Instruction::BCLR3 { rd, imm }
+            | Instruction::BTST1 { rd, imm }
+            | Instruction::BTST2 { rd, imm }
+            | Instruction::BTST3 { rd, imm } => {
                 vec![rd.encode() << 4, *imm]
             }
 
-            Instruction::LOADI2 { rd, imm } | Instruction::ADDI2 { rd, imm } => {
+            Instruction::LOADI2 { rd, imm }
+            | Instruction::ADDI2 { rd, imm }
+            | Instruction::CMPI2 { rd, imm }
+            | Instruction::SUBI2 { rd, imm }
+            | Instruction::ANDI2 { rd, imm }
+            | Instruction::ORI2 { rd, imm }
+            | Instruction::XORI2 { rd, imm } => {
                 let [b1, b2] = imm.to_le_bytes();
                 vec![rd.encode() << 4, b1, b2]
             }
 
-            Instruction::LOADI3 { rd, imm } | Instruction::ADDI3 { rd, imm } => {
+            Instruction::LOADI3 { rd, imm }
+            | Instruction::ADDI3 { rd, imm }
+            | Instruction::CMPI3 { rd, imm }
+            | Instruction::SUBI3 { rd, imm }
+            | Instruction::ANDI3 { rd, imm }
+            | Instruction::ORI3 { rd, imm }
+            | Instruction::XORI3 { rd, imm } => {
                 let [b1, b2, b3] = imm.to_le_bytes();
                 vec![rd.encode() << 4, b1, b2, b3]
             }
 
+            Instruction::MEMCPY { rd, rs, rc } => vec![rs.encode() | rd.encode() << 4, rc.encode() << 4],
+            Instruction::MAC { rd, rs, rt } => vec![rs.encode() | rd.encode() << 4, rt.encode() << 4],
+
+            Instruction::FLOAD { fd, rs } | Instruction::FITOF { fd, rs } => vec![rs.encode() | fd.encode() << 4],
+            Instruction::FSTORE { rd, fs } | Instruction::FFTOI { rd, fs } => vec![fs.encode() | rd.encode() << 4],
+            Instruction::FADD { fd, fs }
+            | Instruction::FSUB { fd, fs }
+            | Instruction::FMUL { fd, fs }
+            | Instruction::FDIV { fd, fs } => vec![fs.encode() | fd.encode() << 4],
+
+            Instruction::LOADS1 { rd, offset }
+            | Instruction::LOADS2 { rd, offset }
+            | Instruction::LOADS3 { rd, offset } => vec![rd.encode() << 4, *offset],
+
+            Instruction::STORES1 { rs, offset }
+            | Instruction::STORES2 { rs, offset }
+            | Instruction::STORES3 { rs, offset } => vec![rs.encode(), *offset],
+
+            Instruction::LOADX1 { rd, rs, disp }
+            | Instruction::LOADX2 { rd, rs, disp }
+            | Instruction::LOADX3 { rd, rs, disp }
+            | Instruction::STOREX1 { rd, rs, disp }
+            | Instruction::STOREX2 { rd, rs, disp }
+            | Instruction::STOREX3 { rd, rs, disp } => {
+                let [b1, b2] = disp.to_le_bytes();
+                vec![rs.encode() | rd.encode() << 4, b1, b2]
+            }
+
             Instruction::LOAD1 { rd, addr }
             | Instruction::LOAD2 { rd, addr }
-            | Instruction::LOAD3 { rd, addr } => {
-                if let Address::Addr(a) = addr {
-                    let [b1, b2, b3] = a.to_le_bytes();
-                    vec![rd.encode() << 4, b1, b2, b3]
-                } else {
-                    panic!("Label not resolved")
-                }
+            | Instruction::LOAD3 { rd, addr }
+            | Instruction::LEA { rd, addr } => {
+                let [b1, b2, b3] = addr.to_le_bytes();
+                vec![rd.encode() << 4, b1, b2, b3]
             }
 
             Instruction::STORE1 { rs, addr }
             | Instruction::STORE2 { rs, addr }
-            | Instruction::STORE3 { rs, addr } => {
-                if let Address::Addr(a) = addr {
-                    let [b1, b2, b3] = a.to_le_bytes();
-                    vec![rs.encode(), b1, b2, b3]
-                } else {
-                    panic!("Label not resolved")
-                }
+            | Instruction::STORE3 { rs, addr }
+            | Instruction::JTAB { rs, addr } => {
+                let [b1, b2, b3] = addr.to_le_bytes();
+                vec![rs.encode(), b1, b2, b3]
             }
 
             Instruction::JMP { addr }
@@ -111,24 +832,95 @@ impl Instruction {
             | Instruction::JNZ { addr }
             | Instruction::JNC { addr }
             | Instruction::JSR { addr }
+            | Instruction::JLT { addr }
+            | Instruction::JGE { addr }
+            | Instruction::JGT { addr }
+            | Instruction::JLE { addr }
             | Instruction::JMPA { addr }
             | Instruction::JZA { addr }
             | Instruction::JCA { addr }
             | Instruction::JNZA { addr }
             | Instruction::JNCA { addr }
             | Instruction::JSRA { addr } => {
-                if let Address::Addr(a) = addr {
-                    let [b1, b2, b3] = a.to_le_bytes();
-                    vec![b1, b2, b3]
-                } else {
-                    panic!("Label not resolved")
-                }
+                let [b1, b2, b3] = addr.to_le_bytes();
+                vec![b1, b2, b3]
+            }
+
+            Instruction::BRA1 { addr }
+            | Instruction::BZ1 { addr }
+            | Instruction::BNZ1 { addr }
+            | Instruction::BC1 { addr }
+            | Instruction::BNC1 { addr } => {
+                let disp = self.displacement(addr, pc);
+                let disp = i8::try_from(disp).map_err(|_| "branch target out of range")?;
+                vec![disp as u8]
+            }
+
+            Instruction::BRA2 { addr }
+            | Instruction::BZ2 { addr }
+            | Instruction::BNZ2 { addr }
+            | Instruction::BC2 { addr }
+            | Instruction::BNC2 { addr } => {
+                let disp = self.displacement(addr, pc);
+                let disp = i16::try_from(disp).map_err(|_| "branch target out of range")?;
+                disp.to_le_bytes().to_vec()
             }
 
-            Instruction::RTS | Instruction::HLT => vec![],
+            Instruction::RTS
+            | Instruction::HLT
+            | Instruction::PUSHA
+            | Instruction::POPA
+            | Instruction::PUSHF
+            | Instruction::POPF
+            | Instruction::RTI
+            | Instruction::SEI
+            | Instruction::CLI
+            | Instruction::WAIT
+            | Instruction::LEAVE
+            | Instruction::SED
+            | Instruction::CLD => vec![],
+
+            Instruction::SWI { vector } => vec![*vector],
+
+            Instruction::ENTER { size } => size.to_le_bytes().to_vec(),
         });
 
-        bytes
+        Ok(bytes)
+    }
+
+    /// Distance from the end of this instruction (at `pc`) to `addr`.
+    fn displacement(&self, addr: &Address, pc: U24) -> i32 {
+        addr.as_u32() as i32 - (pc.as_u32() + self.length() as u32) as i32
+    }
+}
+
+impl Statement {
+    /// Encode the statement, given the address it will be loaded at.
+    pub fn encode(&self, pc: U24) -> Result<Vec<u8>, String> {
+        match self {
+            Statement::Instruction(instr) => instr.encode(pc),
+            Statement::Data8(values) => values
+                .iter()
+                .map(|value| u8::try_from(value.as_u32()).map_err(|_| "value too large for .db".to_string()))
+                .collect(),
+            Statement::Data16(values) => values.iter().try_fold(Vec::new(), |mut bytes, value| {
+                let value = u16::try_from(value.as_u32()).map_err(|_| "value too large for .dw")?;
+                bytes.extend(value.to_le_bytes());
+                Ok(bytes)
+            }),
+            Statement::Data24(values) => Ok(values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect()),
+            Statement::Text(bytes) => Ok(bytes.clone()),
+            Statement::Fill { count, value } => Ok(vec![*value; *count as usize]),
+            Statement::Org(addr) => Ok(vec![0; (*addr - pc).as_u32() as usize]),
+            Statement::Align { boundary, fill } => {
+                let pc = pc.as_u32();
+                Ok(vec![*fill; (pc.next_multiple_of(*boundary) - pc) as usize])
+            }
+            Statement::Entry(_) | Statement::Section(_) => Ok(Vec::new()),
+        }
     }
 }
 
@@ -147,23 +939,66 @@ fn parse_register(s: &str) -> Option<Register> {
     }
 }
 
-fn parse_address(addr: &str) -> Option<Address> {
-    if addr.starts_with("0x") || addr.starts_with(&['0','1','2','3','4','5','6','7','8','9']) {
-        let value: U24 = addr.parse().ok()?;
-        Some(Address::Addr(value))
-    } else {
-        Some(Address::Label(addr.to_string()))
+fn parse_fregister(s: &str) -> Option<FRegister> {
+    match s.to_uppercase().as_str() {
+        "F0" => Some(FRegister::F0),
+        "F1" => Some(FRegister::F1),
+        "F2" => Some(FRegister::F2),
+        "F3" => Some(FRegister::F3),
+        "F4" => Some(FRegister::F4),
+        "F5" => Some(FRegister::F5),
+        "F6" => Some(FRegister::F6),
+        "F7" => Some(FRegister::F7),
+        _ => None,
     }
 }
 
-fn parse_line(line: &str) -> Option<Instruction> {
-    let parts: Vec<&str> = line
-        .split(|c| c == ' ' || c == ',')
+/// Parse a register-indirect operand such as `(R3)`.
+fn parse_indirect(s: &str, symbols: &Symbols) -> Option<Register> {
+    symbols.register(s.strip_prefix('(')?.strip_suffix(')')?)
+}
+
+/// Parse a stack-relative operand such as `(SP+3)` or `(SP)`.
+fn parse_stack_relative(s: &str, symbols: &Symbols) -> Option<u8> {
+    let inner = s.strip_prefix('(')?.strip_suffix(')')?;
+    let (base, offset) = inner.split_once('+').unwrap_or((inner, "0"));
+    if !base.trim().eq_ignore_ascii_case("SP") {
+        return None;
+    }
+    symbols.value(offset)
+}
+
+/// Parse a post-increment operand such as `(R3)+`.
+fn parse_post_increment(s: &str, symbols: &Symbols) -> Option<Register> {
+    symbols.register(s.strip_prefix('(')?.strip_suffix(")+")?)
+}
+
+/// Parse a pre-decrement operand such as `-(R3)`.
+fn parse_pre_decrement(s: &str, symbols: &Symbols) -> Option<Register> {
+    symbols.register(s.strip_prefix("-(")?.strip_suffix(')')?)
+}
+
+/// Parse an indexed operand such as `(R3+5)` or `(R3 - 2)`.
+fn parse_indexed(s: &str, symbols: &Symbols) -> Option<(Register, i16)> {
+    let inner = s.strip_prefix('(')?.strip_suffix(')')?;
+    let pos = inner.find(['+', '-'])?;
+    let reg = symbols.register(inner[..pos].trim())?;
+    // The sign is part of the displacement, e.g. `- 2` or `+ OFFSET`
+    let disp = symbols.value(&inner[pos..])?;
+    Some((reg, disp))
+}
+
+fn parse_line<'a>(line: &'a str, symbols: &Symbols) -> Result<Instruction, LineError<'a>> {
+    // The mnemonic is separated from its operands by whitespace; operands are
+    // separated by commas so that bracketed operands may contain spaces.
+    let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let parts: Vec<&str> = std::iter::once(mnemonic)
+        .chain(operands.split(',').map(str::trim))
         .filter(|s| !s.is_empty())
         .collect();
 
     if parts.is_empty() {
-        return None;
+        return Err(LineError::new(line, "missing instruction"));
     }
 
     let base = parts[0].to_uppercase();
@@ -177,260 +1012,1270 @@ fn parse_line(line: &str) -> Option<Instruction> {
         if parts.len() < 2 {
             return None;
         }
-        Some(parse_register(parts[1])?)
+        symbols.register(parts[1])
     };
 
     let parse_rs = || -> Option<Register> {
         if parts.len() < 2 {
             return None;
         }
-        Some(parse_register(parts[1])?)
+        symbols.register(parts[1])
     };
 
     let parse_rd_rs = || -> Option<(Register, Register)> {
         if parts.len() < 3 {
             return None;
         }
-        Some((parse_register(parts[1])?, parse_register(parts[2])?))
+        Some((symbols.register(parts[1])?, symbols.register(parts[2])?))
+    };
+
+    let parse_fd_ind = || -> Option<(FRegister, Register)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.fregister(parts[1])?, parse_indirect(parts[2], symbols)?))
+    };
+
+    let parse_ind_fs = || -> Option<(Register, FRegister)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((parse_indirect(parts[1], symbols)?, symbols.fregister(parts[2])?))
+    };
+
+    let parse_fd_rs = || -> Option<(FRegister, Register)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.fregister(parts[1])?, symbols.register(parts[2])?))
+    };
+
+    let parse_rd_fs = || -> Option<(Register, FRegister)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.register(parts[1])?, symbols.fregister(parts[2])?))
+    };
+
+    let parse_fd_fs = || -> Option<(FRegister, FRegister)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.fregister(parts[1])?, symbols.fregister(parts[2])?))
+    };
+
+    let parse_rd_rs_rc = || -> Option<(Register, Register, Register)> {
+        if parts.len() < 4 {
+            return None;
+        }
+        Some((symbols.register(parts[1])?, symbols.register(parts[2])?, symbols.register(parts[3])?))
     };
 
     let parse_addr = || -> Option<Address> {
         if parts.len() < 2 {
             return None;
         }
-        Some(parse_address(parts[1])?)
+        symbols.address(parts[1])
     };
 
     let parse_rd_addr = || -> Option<(Register, Address)> {
         if parts.len() < 3 {
             return None;
         }
-        Some((parse_register(parts[1])?, parse_address(parts[2])?))
+        Some((symbols.register(parts[1])?, symbols.address(parts[2])?))
+    };
+
+    let parse_rd_ind = || -> Option<(Register, Register)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.register(parts[1])?, parse_indirect(parts[2], symbols)?))
+    };
+
+    let parse_ind_rs = || -> Option<(Register, Register)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((parse_indirect(parts[1], symbols)?, symbols.register(parts[2])?))
+    };
+
+    let parse_rd_idx = || -> Option<(Register, Register, i16)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        let (rs, disp) = parse_indexed(parts[2], symbols)?;
+        Some((symbols.register(parts[1])?, rs, disp))
+    };
+
+    let parse_idx_rs = || -> Option<(Register, Register, i16)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        let (rd, disp) = parse_indexed(parts[1], symbols)?;
+        Some((rd, symbols.register(parts[2])?, disp))
+    };
+
+    let parse_rd_postinc = || -> Option<(Register, Register)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.register(parts[1])?, parse_post_increment(parts[2], symbols)?))
+    };
+
+    let parse_predec_rs = || -> Option<(Register, Register)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((parse_pre_decrement(parts[1], symbols)?, symbols.register(parts[2])?))
+    };
+
+    let parse_rd_sp = || -> Option<(Register, u8)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.register(parts[1])?, parse_stack_relative(parts[2], symbols)?))
+    };
+
+    let parse_sp_rs = || -> Option<(Register, u8)> {
+        if parts.len() < 3 {
+            return None;
+        }
+        Some((symbols.register(parts[2])?, parse_stack_relative(parts[1], symbols)?))
     };
 
     let parse_rd_imm1 = || -> Option<(Register, u8)> {
         if parts.len() < 3 {
             return None;
         }
-        Some((parse_register(parts[1])?, parts[2].parse().ok()?))
+        Some((symbols.register(parts[1])?, symbols.immediate(parts[2], 8)? as u8))
     };
 
     let parse_rd_imm2 = || -> Option<(Register, u16)> {
         if parts.len() < 3 {
             return None;
         }
-        Some((parse_register(parts[1])?, parts[2].parse().ok()?))
+        Some((symbols.register(parts[1])?, symbols.immediate(parts[2], 16)? as u16))
     };
 
     let parse_rd_imm3 = || -> Option<(Register, U24)> {
         if parts.len() < 3 {
             return None;
         }
-        Some((parse_register(parts[1])?, parts[2].parse().ok()?))
+        Some((symbols.register(parts[1])?, U24::new(symbols.immediate(parts[2], 24)?)))
     };
 
-    match opcode {
+    let instruction = match opcode {
         "ADD" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::ADD1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::ADD2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::ADD3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::ADD1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::ADD2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::ADD3 { rd, rs }),
             _ => None,
         },
         "SUB" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::SUB1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::SUB2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::SUB3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::SUB1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::SUB2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::SUB3 { rd, rs }),
+            _ => None,
+        },
+        "MUL" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::MUL1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::MUL2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::MUL3 { rd, rs }),
+            _ => None,
+        },
+        "DIV" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::DIV1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::DIV2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::DIV3 { rd, rs }),
+            _ => None,
+        },
+        "MOD" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::MOD1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::MOD2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::MOD3 { rd, rs }),
             _ => None,
         },
         "AND" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::AND1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::AND2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::AND3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::AND1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::AND2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::AND3 { rd, rs }),
             _ => None,
         },
         "OR" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::OR1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::OR2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::OR3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::OR1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::OR2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::OR3 { rd, rs }),
             _ => None,
         },
         "XOR" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::XOR1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::XOR2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::XOR3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::XOR1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::XOR2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::XOR3 { rd, rs }),
             _ => None,
         },
         "MOV" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::MOV1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::MOV2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::MOV3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::MOV1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::MOV2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::MOV3 { rd, rs }),
+            _ => None,
+        },
+        "XCHG" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::XCHG1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::XCHG2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::XCHG3 { rd, rs }),
+            _ => None,
+        },
+        "CMOVZ" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVZ1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVZ2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVZ3 { rd, rs }),
+            _ => None,
+        },
+        "CMOVNZ" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVNZ1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVNZ2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVNZ3 { rd, rs }),
+            _ => None,
+        },
+        "CMOVC" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVC1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVC2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::CMOVC3 { rd, rs }),
             _ => None,
         },
         "CMP" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::CMP1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::CMP2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::CMP3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::CMP1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::CMP2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::CMP3 { rd, rs }),
             _ => None,
         },
         "TST" => match width {
-            "1" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::TST1 { rd, rs }))?,
-            "2" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::TST2 { rd, rs }))?,
-            "3" => parse_rd_rs().map(|(rd, rs)| Some(Instruction::TST3 { rd, rs }))?,
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::TST1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::TST2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::TST3 { rd, rs }),
             _ => None,
         },
         "NOT" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::NOT1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::NOT2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::NOT3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::NOT1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::NOT2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::NOT3 { rd }),
             _ => None,
         },
         "INC" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::INC1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::INC2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::INC3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::INC1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::INC2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::INC3 { rd }),
             _ => None,
         },
         "DEC" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::DEC1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::DEC2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::DEC3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::DEC1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::DEC2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::DEC3 { rd }),
             _ => None,
         },
         "NEG" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::NEG1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::NEG2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::NEG3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::NEG1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::NEG2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::NEG3 { rd }),
+            _ => None,
+        },
+        "SHL" if parts.len() > 2 => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::SHLR1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::SHLR2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::SHLR3 { rd, rs }),
+            _ => None,
+        },
+        "SHR" if parts.len() > 2 => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::SHRR1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::SHRR2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::SHRR3 { rd, rs }),
             _ => None,
         },
         "SHL" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::SHL1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::SHL2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::SHL3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::SHL1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::SHL2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::SHL3 { rd }),
             _ => None,
         },
         "SHR" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::SHR1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::SHR2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::SHR3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::SHR1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::SHR2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::SHR3 { rd }),
             _ => None,
         },
         "ROL" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::ROL1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::ROL2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::ROL3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::ROL1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::ROL2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::ROL3 { rd }),
             _ => None,
         },
         "ROR" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::ROR1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::ROR2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::ROR3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::ROR1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::ROR2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::ROR3 { rd }),
+            _ => None,
+        },
+        "CLZ" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::CLZ1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::CLZ2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::CLZ3 { rd, rs }),
+            _ => None,
+        },
+        "POPCNT" => match width {
+            "1" => parse_rd_rs().map(|(rd, rs)| Instruction::POPCNT1 { rd, rs }),
+            "2" => parse_rd_rs().map(|(rd, rs)| Instruction::POPCNT2 { rd, rs }),
+            "3" => parse_rd_rs().map(|(rd, rs)| Instruction::POPCNT3 { rd, rs }),
+            _ => None,
+        },
+        "SEX" => match width {
+            "2" => parse_rd().map(|rd| Instruction::SEX2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::SEX3 { rd }),
+            _ => None,
+        },
+        "BSWAP" => match width {
+            "2" => parse_rd().map(|rd| Instruction::BSWAP2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::BSWAP3 { rd }),
             _ => None,
         },
+        "MOVSP" if parts.len() > 2 && parts[2].eq_ignore_ascii_case("SP") => {
+            symbols.register(parts[1]).map(|rd| Instruction::MOVSPR { rd })
+        }
+        "MOVSP" if parts.len() > 2 && parts[1].eq_ignore_ascii_case("SP") => {
+            symbols.register(parts[2]).map(|rs| Instruction::MOVRSP { rs })
+        }
+        "MOVPC" => parse_rd().map(|rd| Instruction::MOVPC { rd }),
         "POP" => match width {
-            "1" => parse_rd().map(|rd| Some(Instruction::POP1 { rd }))?,
-            "2" => parse_rd().map(|rd| Some(Instruction::POP2 { rd }))?,
-            "3" => parse_rd().map(|rd| Some(Instruction::POP3 { rd }))?,
+            "1" => parse_rd().map(|rd| Instruction::POP1 { rd }),
+            "2" => parse_rd().map(|rd| Instruction::POP2 { rd }),
+            "3" => parse_rd().map(|rd| Instruction::POP3 { rd }),
             _ => None,
         },
         "PUSH" => match width {
-            "1" => parse_rs().map(|rs| Some(Instruction::PUSH1 { rs }))?,
-            "2" => parse_rs().map(|rs| Some(Instruction::PUSH2 { rs }))?,
-            "3" => parse_rs().map(|rs| Some(Instruction::PUSH3 { rs }))?,
+            "1" => parse_rs().map(|rs| Instruction::PUSH1 { rs }),
+            "2" => parse_rs().map(|rs| Instruction::PUSH2 { rs }),
+            "3" => parse_rs().map(|rs| Instruction::PUSH3 { rs }),
+            _ => None,
+        },
+        "LOAD" if parts.len() > 2 && parse_stack_relative(parts[2], symbols).is_some() => match width {
+            "1" => parse_rd_sp().map(|(rd, offset)| Instruction::LOADS1 { rd, offset }),
+            "2" => parse_rd_sp().map(|(rd, offset)| Instruction::LOADS2 { rd, offset }),
+            "3" => parse_rd_sp().map(|(rd, offset)| Instruction::LOADS3 { rd, offset }),
+            _ => None,
+        },
+        "STORE" if parts.len() > 1 && parse_stack_relative(parts[1], symbols).is_some() => match width {
+            "1" => parse_sp_rs().map(|(rs, offset)| Instruction::STORES1 { rs, offset }),
+            "2" => parse_sp_rs().map(|(rs, offset)| Instruction::STORES2 { rs, offset }),
+            "3" => parse_sp_rs().map(|(rs, offset)| Instruction::STORES3 { rs, offset }),
+            _ => None,
+        },
+        "LOAD" if parts.len() > 2 && parts[2].ends_with(")+") => match width {
+            "1" => parse_rd_postinc().map(|(rd, rs)| Instruction::LOADPI1 { rd, rs }),
+            "2" => parse_rd_postinc().map(|(rd, rs)| Instruction::LOADPI2 { rd, rs }),
+            "3" => parse_rd_postinc().map(|(rd, rs)| Instruction::LOADPI3 { rd, rs }),
+            _ => None,
+        },
+        "STORE" if parts.len() > 1 && parts[1].starts_with("-(") => match width {
+            "1" => parse_predec_rs().map(|(rd, rs)| Instruction::STOREPD1 { rd, rs }),
+            "2" => parse_predec_rs().map(|(rd, rs)| Instruction::STOREPD2 { rd, rs }),
+            "3" => parse_predec_rs().map(|(rd, rs)| Instruction::STOREPD3 { rd, rs }),
+            _ => None,
+        },
+        "LOAD" if parts.len() > 2 && parse_indexed(parts[2], symbols).is_some() => match width {
+            "1" => parse_rd_idx().map(|(rd, rs, disp)| Instruction::LOADX1 { rd, rs, disp }),
+            "2" => parse_rd_idx().map(|(rd, rs, disp)| Instruction::LOADX2 { rd, rs, disp }),
+            "3" => parse_rd_idx().map(|(rd, rs, disp)| Instruction::LOADX3 { rd, rs, disp }),
+            _ => None,
+        },
+        "STORE" if parts.len() > 1 && parse_indexed(parts[1], symbols).is_some() => match width {
+            "1" => parse_idx_rs().map(|(rd, rs, disp)| Instruction::STOREX1 { rd, rs, disp }),
+            "2" => parse_idx_rs().map(|(rd, rs, disp)| Instruction::STOREX2 { rd, rs, disp }),
+            "3" => parse_idx_rs().map(|(rd, rs, disp)| Instruction::STOREX3 { rd, rs, disp }),
+            _ => None,
+        },
+        "LOAD" if parts.len() > 2 && parts[2].starts_with('(') => match width {
+            "1" => parse_rd_ind().map(|(rd, rs)| Instruction::LOADR1 { rd, rs }),
+            "2" => parse_rd_ind().map(|(rd, rs)| Instruction::LOADR2 { rd, rs }),
+            "3" => parse_rd_ind().map(|(rd, rs)| Instruction::LOADR3 { rd, rs }),
+            _ => None,
+        },
+        "STORE" if parts.len() > 1 && parts[1].starts_with('(') => match width {
+            "1" => parse_ind_rs().map(|(rd, rs)| Instruction::STORER1 { rd, rs }),
+            "2" => parse_ind_rs().map(|(rd, rs)| Instruction::STORER2 { rd, rs }),
+            "3" => parse_ind_rs().map(|(rd, rs)| Instruction::STORER3 { rd, rs }),
             _ => None,
         },
         "LOAD" => match width {
-            "1" => parse_rd_addr().map(|(rd, addr)| Some(Instruction::LOAD1 { rd, addr }))?,
-            "2" => parse_rd_addr().map(|(rd, addr)| Some(Instruction::LOAD2 { rd, addr }))?,
-            "3" => parse_rd_addr().map(|(rd, addr)| Some(Instruction::LOAD3 { rd, addr }))?,
+            "1" => parse_rd_addr().map(|(rd, addr)| Instruction::LOAD1 { rd, addr }),
+            "2" => parse_rd_addr().map(|(rd, addr)| Instruction::LOAD2 { rd, addr }),
+            "3" => parse_rd_addr().map(|(rd, addr)| Instruction::LOAD3 { rd, addr }),
             _ => None,
         },
         "STORE" => match width {
-            "1" => parse_rd_addr().map(|(rs, addr)| Some(Instruction::STORE1 { rs, addr }))?,
-            "2" => parse_rd_addr().map(|(rs, addr)| Some(Instruction::STORE2 { rs, addr }))?,
-            "3" => parse_rd_addr().map(|(rs, addr)| Some(Instruction::STORE3 { rs, addr }))?,
+            "1" => parse_rd_addr().map(|(rs, addr)| Instruction::STORE1 { rs, addr }),
+            "2" => parse_rd_addr().map(|(rs, addr)| Instruction::STORE2 { rs, addr }),
+            "3" => parse_rd_addr().map(|(rs, addr)| Instruction::STORE3 { rs, addr }),
             _ => None,
         },
+        "LEA" => parse_rd_addr().map(|(rd, addr)| Instruction::LEA { rd, addr }),
         "LOADI" => match width {
-            "1" => parse_rd_imm1().map(|(rd, imm)| Some(Instruction::LOADI1 { rd, imm }))?,
-            "2" => parse_rd_imm2().map(|(rd, imm)| Some(Instruction::LOADI2 { rd, imm }))?,
-            "3" => parse_rd_imm3().map(|(rd, imm)| Some(Instruction::LOADI3 { rd, imm }))?,
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::LOADI1 { rd, imm }),
+            "2" => parse_rd_imm2().map(|(rd, imm)| Instruction::LOADI2 { rd, imm }),
+            "3" => parse_rd_imm3().map(|(rd, imm)| Instruction::LOADI3 { rd, imm }),
             _ => None,
         },
         "ADDI" => match width {
-            "1" => parse_rd_imm1().map(|(rd, imm)| Some(Instruction::ADDI1 { rd, imm }))?,
-            "2" => parse_rd_imm2().map(|(rd, imm)| Some(Instruction::ADDI2 { rd, imm }))?,
-            "3" => parse_rd_imm3().map(|(rd, imm)| Some(Instruction::ADDI3 { rd, imm }))?,
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::ADDI1 { rd, imm }),
+            "2" => parse_rd_imm2().map(|(rd, imm)| Instruction::ADDI2 { rd, imm }),
+            "3" => parse_rd_imm3().map(|(rd, imm)| Instruction::ADDI3 { rd, imm }),
+            _ => None,
+        },
+        "SUBI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::SUBI1 { rd, imm }),
+            "2" => parse_rd_imm2().map(|(rd, imm)| Instruction::SUBI2 { rd, imm }),
+            "3" => parse_rd_imm3().map(|(rd, imm)| Instruction::SUBI3 { rd, imm }),
+            _ => None,
+        },
+        "CMPI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::CMPI1 { rd, imm }),
+            "2" => parse_rd_imm2().map(|(rd, imm)| Instruction::CMPI2 { rd, imm }),
+            "3" => parse_rd_imm3().map(|(rd, imm)| Instruction::CMPI3 { rd, imm }),
+            _ => None,
+        },
+        "ANDI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::ANDI1 { rd, imm }),
+            "2" => parse_rd_imm2().map(|(rd, imm)| Instruction::ANDI2 { rd, imm }),
+            "3" => parse_rd_imm3().map(|(rd, imm)| Instruction::ANDI3 { rd, imm }),
+            _ => None,
+        },
+        "ORI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::ORI1 { rd, imm }),
+            "2" => parse_rd_imm2().map(|(rd, imm)| Instruction::ORI2 { rd, imm }),
+            "3" => parse_rd_imm3().map(|(rd, imm)| Instruction::ORI3 { rd, imm }),
+            _ => None,
+        },
+        "XORI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::XORI1 { rd, imm }),
+            "2" => parse_rd_imm2().map(|(rd, imm)| Instruction::XORI2 { rd, imm }),
+            "3" => parse_rd_imm3().map(|(rd, imm)| Instruction::XORI3 { rd, imm }),
+            _ => None,
+        },
+        "SHLI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::SHLI1 { rd, imm }),
+            "2" => parse_rd_imm1().map(|(rd, imm)| Instruction::SHLI2 { rd, imm }),
+            "3" => parse_rd_imm1().map(|(rd, imm)| Instruction::SHLI3 { rd, imm }),
+            _ => None,
+        },
+        "SHRI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::SHRI1 { rd, imm }),
+            "2" => parse_rd_imm1().map(|(rd, imm)| Instruction::SHRI2 { rd, imm }),
+            "3" => parse_rd_imm1().map(|(rd, imm)| Instruction::SHRI3 { rd, imm }),
+            _ => None,
+        },
+        "ROLI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::ROLI1 { rd, imm }),
+            "2" => parse_rd_imm1().map(|(rd, imm)| Instruction::ROLI2 { rd, imm }),
+            "3" => parse_rd_imm1().map(|(rd, imm)| Instruction::ROLI3 { rd, imm }),
+            _ => None,
+        },
+        "RORI" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::RORI1 { rd, imm }),
+            "2" => parse_rd_imm1().map(|(rd, imm)| Instruction::RORI2 { rd, imm }),
+            "3" => parse_rd_imm1().map(|(rd, imm)| Instruction::RORI3 { rd, imm }),
+            _ => None,
+        },
+        "BSET" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::BSET1 { rd, imm }),
+            "2" => parse_rd_imm1().map(|(rd, imm)| Instruction::BSET2 { rd, imm }),
+            "3" => parse_rd_imm1().map(|(rd, imm)| Instruction::BSET3 { rd, imm }),
+            _ => None,
+        },
+        "BCLR" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::BCLR1 { rd, imm }),
+            "2" => parse_rd_imm1().map(|(rd, imm)| Instruction::BCLR2 { rd, imm }),
+            "3" => parse_rd_imm1().map(|(rd, imm)| Instruction::BCLR3 { rd, imm }),
+            _ => None,
+        },
+        "BTST" => match width {
+            "1" => parse_rd_imm1().map(|(rd, imm)| Instruction::BTST1 { rd, imm }),
+            "2" => parse_rd_imm1().map(|(rd, imm)| Instruction::BTST2 { rd, imm }),
+            "3" => parse_rd_imm1().map(|(rd, imm)| Instruction::BTST3 { rd, imm }),
+            _ => None,
+        },
+        "MAC" => parse_rd_rs_rc().map(|(rd, rs, rt)| Instruction::MAC { rd, rs, rt }),
+        "MEMCPY" => parse_rd_rs_rc().map(|(rd, rs, rc)| Instruction::MEMCPY { rd, rs, rc }),
+        "JMP" => parse_addr().map(|addr| Instruction::JMP { addr }),
+        "JZ" => parse_addr().map(|addr| Instruction::JZ { addr }),
+        "JC" => parse_addr().map(|addr| Instruction::JC { addr }),
+        "JSR" => parse_addr().map(|addr| Instruction::JSR { addr }),
+        "TAS" => parse_rd_ind().map(|(rd, rs)| Instruction::TAS { rd, rs }),
+        "JSRR" => parse_rs().map(|rs| Instruction::JSRR { rs }),
+        "JNZ" => parse_addr().map(|addr| Instruction::JNZ { addr }),
+        "JNC" => parse_addr().map(|addr| Instruction::JNC { addr }),
+        "JLT" => parse_addr().map(|addr| Instruction::JLT { addr }),
+        "JGE" => parse_addr().map(|addr| Instruction::JGE { addr }),
+        "JGT" => parse_addr().map(|addr| Instruction::JGT { addr }),
+        "JLE" => parse_addr().map(|addr| Instruction::JLE { addr }),
+
+        "BRA" => match width {
+            "1" => parse_addr().map(|addr| Instruction::BRA1 { addr }),
+            "2" => parse_addr().map(|addr| Instruction::BRA2 { addr }),
+            _ => None,
+        },
+        "BZ" => match width {
+            "1" => parse_addr().map(|addr| Instruction::BZ1 { addr }),
+            "2" => parse_addr().map(|addr| Instruction::BZ2 { addr }),
+            _ => None,
+        },
+        "BNZ" => match width {
+            "1" => parse_addr().map(|addr| Instruction::BNZ1 { addr }),
+            "2" => parse_addr().map(|addr| Instruction::BNZ2 { addr }),
+            _ => None,
+        },
+        "BC" => match width {
+            "1" => parse_addr().map(|addr| Instruction::BC1 { addr }),
+            "2" => parse_addr().map(|addr| Instruction::BC2 { addr }),
+            _ => None,
+        },
+        "BNC" => match width {
+            "1" => parse_addr().map(|addr| Instruction::BNC1 { addr }),
+            "2" => parse_addr().map(|addr| Instruction::BNC2 { addr }),
             _ => None,
         },
-        "JMP" => parse_addr().map(|addr| Some(Instruction::JMP { addr }))?,
-        "JZ" => parse_addr().map(|addr| Some(Instruction::JZ { addr }))?,
-        "JC" => parse_addr().map(|addr| Some(Instruction::JC { addr }))?,
-        "JSR" => parse_addr().map(|addr| Some(Instruction::JSR { addr }))?,
-        "JNZ" => parse_addr().map(|addr| Some(Instruction::JNZ { addr }))?,
-        "JNC" => parse_addr().map(|addr| Some(Instruction::JNC { addr }))?,
 
-        "JMPA" => parse_addr().map(|addr| Some(Instruction::JMPA { addr }))?,
-        "JZA" => parse_addr().map(|addr| Some(Instruction::JZA { addr }))?,
-        "JCA" => parse_addr().map(|addr| Some(Instruction::JCA { addr }))?,
-        "JSRA" => parse_addr().map(|addr| Some(Instruction::JSRA { addr }))?,
-        "JNZA" => parse_addr().map(|addr| Some(Instruction::JNZA { addr }))?,
-        "JNCA" => parse_addr().map(|addr| Some(Instruction::JNCA { addr }))?,
+        "JMPA" => parse_addr().map(|addr| Instruction::JMPA { addr }),
+        "JZA" => parse_addr().map(|addr| Instruction::JZA { addr }),
+        "JCA" => parse_addr().map(|addr| Instruction::JCA { addr }),
+        "JSRA" => parse_addr().map(|addr| Instruction::JSRA { addr }),
+        "JNZA" => parse_addr().map(|addr| Instruction::JNZA { addr }),
+        "JNCA" => parse_addr().map(|addr| Instruction::JNCA { addr }),
 
         "RTS" => Some(Instruction::RTS),
         "HLT" => Some(Instruction::HLT),
-        _ => None,
+        "PUSHA" => Some(Instruction::PUSHA),
+        "POPA" => Some(Instruction::POPA),
+        "PUSHF" => Some(Instruction::PUSHF),
+        "POPF" => Some(Instruction::POPF),
+        "RTI" => Some(Instruction::RTI),
+        "SEI" => Some(Instruction::SEI),
+        "CLI" => Some(Instruction::CLI),
+        "WAIT" => Some(Instruction::WAIT),
+        "ENTER" => parts
+            .get(1)
+            .and_then(|s| symbols.value(s))
+            .map(|size| Instruction::ENTER { size }),
+        "LEAVE" => Some(Instruction::LEAVE),
+        "SED" => Some(Instruction::SED),
+        "CLD" => Some(Instruction::CLD),
+        "DAA" => parse_rd().map(|rd| Instruction::DAA { rd }),
+        "CPUID" => parse_rd().map(|rd| Instruction::CPUID { rd }),
+        "FLOAD" => parse_fd_ind().map(|(fd, rs)| Instruction::FLOAD { fd, rs }),
+        "FSTORE" => parse_ind_fs().map(|(rd, fs)| Instruction::FSTORE { rd, fs }),
+        "FITOF" => parse_fd_rs().map(|(fd, rs)| Instruction::FITOF { fd, rs }),
+        "FFTOI" => parse_rd_fs().map(|(rd, fs)| Instruction::FFTOI { rd, fs }),
+        "FADD" => parse_fd_fs().map(|(fd, fs)| Instruction::FADD { fd, fs }),
+        "FSUB" => parse_fd_fs().map(|(fd, fs)| Instruction::FSUB { fd, fs }),
+        "FMUL" => parse_fd_fs().map(|(fd, fs)| Instruction::FMUL { fd, fs }),
+        "FDIV" => parse_fd_fs().map(|(fd, fs)| Instruction::FDIV { fd, fs }),
+        "SWI" => parts
+            .get(1)
+            .and_then(|v| symbols.value(v))
+            .map(|vector| Instruction::SWI { vector }),
+        "JTAB" => parse_rd_addr().map(|(rs, addr)| Instruction::JTAB { rs, addr }),
+        _ => return Err(LineError::new(mnemonic, "unknown instruction")),
+    };
+
+    // Point at the operands, or at the mnemonic if they are missing
+    let text = if operands.trim().is_empty() { mnemonic } else { operands.trim() };
+    instruction.ok_or_else(|| LineError::new(text, format!("invalid operands for {}", base)))
+}
+
+/// Parse comma-separated data values, which may use labels and constants.
+fn parse_values<'a>(args: &'a str, symbols: &Symbols) -> Result<Vec<Address>, LineError<'a>> {
+    args.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|value| symbols.address(value).ok_or_else(|| LineError::new(value, "invalid value")))
+        .collect()
+}
+
+/// Parse a quoted string such as `"Hello\n"` into bytes. The escapes are
+/// `\n`, `\r`, `\t`, `\0`, `\\`, `\"` and `\xNN`.
+fn parse_string(s: &str) -> Result<Vec<u8>, LineError<'_>> {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| LineError::new(s, "expected a quoted string"))?;
+
+    let mut bytes = Vec::new();
+    let mut chars = inner.char_indices();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some((_, 'n')) => b'\n',
+            Some((_, 'r')) => b'\r',
+            Some((_, 't')) => b'\t',
+            Some((_, '0')) => 0,
+            Some((_, '\\')) => b'\\',
+            Some((_, '"')) => b'"',
+            Some((_, 'x')) => {
+                let digits = inner.get(start + 2..start + 4).filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()));
+                let Some(digits) = digits else {
+                    return Err(LineError::new(&inner[start..start + 2], "expected two hex digits"));
+                };
+                chars.nth(1);
+                u8::from_str_radix(digits, 16).unwrap()
+            }
+            Some((pos, c)) => return Err(LineError::new(&inner[start..pos + c.len_utf8()], "unknown escape")),
+            None => return Err(LineError::new(&inner[start..], "unknown escape")),
+        };
+        bytes.push(byte);
     }
+    Ok(bytes)
 }
 
-fn create_label_map(lines: &Vec<&str>) -> HashMap<String, U24> {
-    let mut labels = HashMap::new();
-    let mut pc = U24::new(0);
+/// The line without its comment, which starts with `;`, `#` or `//`.
+/// These inside a string don't start a comment.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' | '#' if !quoted => return &line[..i],
+            '/' if !quoted && line[i + 1..].starts_with('/') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
 
-    for line in lines {
-        let line = line.trim();
-        if line.ends_with(':') {
-            let label = line.trim_end_matches(':').to_string();
-            labels.insert(label, pc);
-        } else if let Some(instr) = parse_line(line) {
-            pc += instr.length() as u32;
+/// Parse a directive such as `.d24 1, 2, label` or `.org 49152`.
+fn parse_directive<'a>(line: &'a str, symbols: &Symbols) -> Result<Statement, LineError<'a>> {
+    let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    // Point at an argument, or at the directive if it is missing
+    let at = |arg: &'a str| if arg.trim().is_empty() { name } else { arg.trim() };
+
+    match name.to_lowercase().as_str() {
+        ".db" => parse_values(args, symbols).map(Statement::Data8),
+        ".dw" => parse_values(args, symbols).map(Statement::Data16),
+        ".d24" => parse_values(args, symbols).map(Statement::Data24),
+        ".ascii" => parse_string(args.trim()).map(Statement::Text),
+        ".asciz" => parse_string(args.trim()).map(|mut bytes| {
+            bytes.push(0);
+            Statement::Text(bytes)
+        }),
+        ".space" | ".fill" => {
+            let (count, value) = args.split_once(',').unwrap_or((args, "0"));
+            if name.eq_ignore_ascii_case(".fill") && !args.contains(',') {
+                return Err(LineError::new(at(args), "expected a count and a value"));
+            }
+            let count = symbols
+                .eval_defined(count)
+                .and_then(|value| u32::try_from(value).ok().filter(|value| *value <= 0xFF_FFFF))
+                .ok_or_else(|| LineError::new(at(count), "expected a count"))?;
+            let value = symbols.immediate(value, 8).ok_or_else(|| LineError::new(at(value), "invalid fill byte"))?;
+            Ok(Statement::Fill { count, value: value as u8 })
         }
+        ".org" => symbols
+            .eval_defined(args)
+            .and_then(|value| u32::try_from(value).ok().filter(|value| *value <= 0xFF_FFFF))
+            .map(|value| Statement::Org(U24::new(value)))
+            .ok_or_else(|| LineError::new(at(args), "expected an address")),
+        ".align" => {
+            let (boundary, fill) = args.split_once(',').unwrap_or((args, "0"));
+            let boundary = symbols
+                .eval_defined(boundary)
+                .and_then(|value| u32::try_from(value).ok().filter(|value| (1..=0xFF_FFFF).contains(value)))
+                .ok_or_else(|| LineError::new(at(boundary), "expected an alignment"))?;
+            let fill = symbols.immediate(fill, 8).ok_or_else(|| LineError::new(at(fill), "invalid fill byte"))?;
+            Ok(Statement::Align { boundary, fill: fill as u8 })
+        }
+        ".entry" => symbols
+            .address(args)
+            .map(Statement::Entry)
+            .ok_or_else(|| LineError::new(at(args), "expected an address")),
+        ".text" | ".data" | ".bss" if !args.trim().is_empty() => Err(LineError::new(args.trim(), "unexpected operand")),
+        ".text" => Ok(Statement::Section(SectionKind::Text)),
+        ".data" => Ok(Statement::Section(SectionKind::Data)),
+        ".bss" => Ok(Statement::Section(SectionKind::Bss)),
+        _ => Err(LineError::new(name, "unknown directive")),
     }
+}
 
-    labels
+fn parse_statement<'a>(line: &'a str, symbols: &Symbols) -> Result<Statement, LineError<'a>> {
+    if line.starts_with('.') {
+        parse_directive(line, symbols)
+    } else {
+        parse_line(line, symbols).map(Statement::Instruction)
+    }
 }
 
-pub fn assemble(text: &str) -> Vec<u8> {
-    let lines: Vec<&str> = text
-        .lines()
-        .map(|line| line.split(';').next().unwrap().trim()) // strip comments
-        .filter(|line| !line.is_empty())
-        .collect();
+/// Split a constant definition such as `SIZE .equ 16` into the name, the
+/// kind of constant and the expression.
+fn parse_constant(line: &str) -> Option<(&str, SymbolKind, &str)> {
+    let (name, rest) = line.split_once(char::is_whitespace)?;
+    let rest = rest.trim_start();
+    let (directive, expr) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let kind = match directive.to_lowercase().as_str() {
+        ".equ" => SymbolKind::Constant,
+        ".set" => SymbolKind::Variable,
+        _ => return None,
+    };
+    Some((name, kind, expr.trim()))
+}
 
-    let labels = create_label_map(&lines);
+/// Split a register alias such as `.def counter, R2` into the name and
+/// the register.
+fn parse_alias(line: &str) -> Option<(&str, &str)> {
+    let (directive, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if !matches!(directive.to_lowercase().as_str(), ".def" | ".alias") {
+        return None;
+    }
+    let (name, target) = args.split_once(',').unwrap_or((args, ""));
+    Some((name.trim(), target.trim()))
+}
 
-    let program: Vec<Instruction> = lines
-        .iter()
-        .filter(|line| !line.ends_with(':'))
-        .filter_map(|line| parse_line(line))
-        .map(|instr| {
-            println!("  {:?} => {:?}", instr, instr.encode());
-            instr
-        })
-        .map(|mut instr| match &mut instr {
-            Instruction::JMP { addr }
-            | Instruction::JZ { addr }
-            | Instruction::JC { addr }
-            | Instruction::JSR { addr } => {
-                if let Address::Label(name) = addr {
-                    *addr = Address::Addr(
-                        *labels.get(name).expect(&format!("unknown label: {}", name)),
-                    );
+/// The sections, in the order they are laid out.
+const SECTIONS: [SectionKind; 3] = [SectionKind::Text, SectionKind::Data, SectionKind::Bss];
+
+/// Where each section starts and ends, indexed by `SectionKind`. An empty
+/// section starts and ends where it would have started.
+type Layout = [(U24, U24); 3];
+
+/// Run one pass over the source, starting each section at `starts` unless
+/// it has a `.org` first. The first pass sizes every statement to place
+/// the labels; values aren't known yet, so it only reports duplicate
+/// labels and names used too early. The second parses each statement with
+/// every label known and reports everything else.
+fn run_pass<'a>(
+    lines: &'a [SourceLine<'a>],
+    symbols: &mut Symbols,
+    errors: &mut Vec<AsmError>,
+    starts: [U24; 3],
+) -> (Vec<(&'a SourceLine<'a>, U24, Statement)>, Layout) {
+    let first = symbols.lenient;
+    let mut program = Vec::new();
+    let mut section = SectionKind::Text;
+    // Where each section has got to, and its first output
+    let mut pcs = starts;
+    let mut opened: [Option<&SourceLine>; 3] = [None; 3];
+    let mut layout: Layout = starts.map(|start| (start, start));
+    let mut pc = starts[0];
+    let mut entry = false;
+    symbols.scope.clear();
+
+    // An error caused by an undefined name is reported as that
+    let report = |line: &SourceLine, symbols: &Symbols, err: LineError| match symbols.take_unknown() {
+        Some(name) if first => Some(line.error(LineError::new(line.locate(&name), "not defined before this line"))),
+        Some(name) => Some(line.error(LineError::new(line.locate(&name), "unknown symbol"))),
+        None if !first => Some(line.error(err)),
+        None => None,
+    };
+
+    for line in lines {
+        symbols.take_unknown();
+        let (label, text) = line.split_label();
+        if let Some(label) = label {
+            if first && !is_symbol(label) {
+                errors.push(line.error(LineError::new(label, "invalid label name")));
+            } else if first && !symbols.define(label, pc.as_u32() as i64, SymbolKind::Label) {
+                errors.push(line.error(LineError::new(label, "already defined")));
+            }
+            // A macro's own labels, such as `loop\@`, don't start a scope
+            // for the local labels of the code around it
+            if is_symbol(label) && !label.starts_with('.') && line.expansion.is_none() {
+                symbols.scope = label.to_string();
+            }
+            if text.is_empty() {
+                continue;
+            }
+        }
+
+        if let Some((name, target)) = parse_alias(text) {
+            let defined = match name {
+                "" => Err(LineError::new(text, "expected a name and a register")),
+                _ => symbols.alias(name, target),
+            };
+            if let Err(err) = defined {
+                errors.extend(report(line, symbols, err));
+            }
+            continue;
+        }
+
+        if let Some((name, kind, expr)) = parse_constant(text) {
+            let defined = match symbols.eval(expr) {
+                _ if !is_symbol(name) => Err(LineError::new(name, "invalid name")),
+                Some(value) if symbols.define(name, value, kind) => Ok(()),
+                Some(_) => Err(LineError::new(name, "already defined")),
+                None => Err(LineError::new(expr, "invalid value")),
+            };
+            if let Err(err) = defined {
+                errors.extend(report(line, symbols, err));
+            }
+            continue;
+        }
+
+        match parse_statement(text, symbols) {
+            Ok(Statement::Entry(_)) if entry => {
+                errors.extend(report(line, symbols, LineError::new(text, "entry point set twice")));
+            }
+            // The entry point isn't output, so it doesn't fix the origin
+            Ok(stmt @ Statement::Entry(_)) => {
+                entry = true;
+                program.push((line, pc, stmt));
+            }
+            // Before any output, `.org` just moves where the image starts
+            // Each section carries on from where it was left
+            Ok(Statement::Section(kind)) => {
+                pcs[section as usize] = pc;
+                section = kind;
+                pc = pcs[kind as usize];
+                program.push((line, pc, Statement::Section(kind)));
+            }
+            Ok(Statement::Org(addr)) if opened[section as usize].is_none() => pc = addr,
+            Ok(Statement::Org(addr)) if addr < pc => {
+                errors.extend(report(line, symbols, LineError::new(text, "origin is behind the current address")));
+            }
+            Ok(stmt) if section == SectionKind::Bss && !stmt.is_zero() => {
+                errors.extend(report(line, symbols, LineError::new(text, "only .space and .align can be used in .bss")));
+            }
+            Ok(stmt) => {
+                let next = match stmt {
+                    Statement::Org(addr) => addr,
+                    Statement::Align { boundary, .. } => U24::new(pc.as_u32().next_multiple_of(boundary)),
+                    _ => pc + stmt.length(),
+                };
+                if opened[section as usize].is_none() {
+                    opened[section as usize] = Some(line);
+                    layout[section as usize].0 = pc;
                 }
-                instr
+                program.push((line, pc, stmt));
+                pc = next;
+            }
+            Err(err) => errors.extend(report(line, symbols, err)),
+        }
+    }
+    pcs[section as usize] = pc;
+    for kind in SECTIONS {
+        if opened[kind as usize].is_some() {
+            layout[kind as usize].1 = pcs[kind as usize];
+        }
+    }
+
+    // Sections placed with `.org` may run into each other; the one that
+    // starts later is at fault
+    for (i, a) in SECTIONS.into_iter().enumerate() {
+        for b in SECTIONS[i + 1..].iter().copied() {
+            let ((a_start, a_end), (b_start, b_end)) = (layout[a as usize], layout[b as usize]);
+            let (a_start, a_end, b_start, b_end) = (a_start.as_u32(), a_end.as_u32(), b_start.as_u32(), b_end.as_u32());
+            if first || a_start == a_end || b_start == b_end || a_end <= b_start || b_end <= a_start {
+                continue;
             }
-            _ => instr,
+            let (later, other) = if b_start >= a_start { (b, a) } else { (a, b) };
+            if let Some(line) = opened[later as usize] {
+                let reason = format!("{} overlaps {}", later, other);
+                errors.push(line.error(LineError::new(line.split_label().1, reason)));
+            }
+        }
+    }
+    (program, layout)
+}
+
+/// The first `.space`, `.align` or `.org` whose size changed between two
+/// first passes, and so keeps the sections from settling.
+fn unsettled<'a>(
+    before: &[(&'a SourceLine<'a>, U24, Statement)],
+    after: &[(&'a SourceLine<'a>, U24, Statement)],
+) -> Option<&'a SourceLine<'a>> {
+    before.iter().zip(after).find_map(|((line, _, a), (_, _, b))| match (a, b) {
+        (Statement::Fill { count: a, .. }, Statement::Fill { count: b, .. }) if a != b => Some(*line),
+        (Statement::Align { boundary: a, .. }, Statement::Align { boundary: b, .. }) if a != b => Some(*line),
+        (Statement::Org(a), Statement::Org(b)) if a != b => Some(*line),
+        _ => None,
+    })
+}
+
+/// Assemble `text` into a program image. The image runs from the start of
+/// `.text` or `.data`, whichever is lower, to the end of the other, and is
+/// meant to be loaded there; `.text` starts at the address of a `.org`
+/// before any output, or at 0. Every error in the source is reported, not
+/// just the first.
+pub fn assemble(text: &str) -> Result<Assembly, Vec<AsmError>> {
+    let mut errors = Vec::new();
+    let lines = expand_macros(text, &mut errors);
+
+    // A section without a `.org` follows the one before, so where it
+    // starts depends on how long that one is. Each first pass places one
+    // more section, until the starts stop moving. They can keep moving
+    // for ever if a size depends on a label in a later section.
+    let mut starts = [U24::new(0); 3];
+    let mut previous = Vec::new();
+    let mut passes = 0;
+    let mut symbols = loop {
+        let mut symbols = Symbols::new();
+        let mut pass_errors = Vec::new();
+        let (program, layout) = run_pass(&lines, &mut symbols, &mut pass_errors, starts);
+        let next = [U24::new(0), layout[SectionKind::Text as usize].1, layout[SectionKind::Data as usize].1];
+        if next == starts {
+            errors.extend(pass_errors);
+            break symbols;
+        }
+        passes += 1;
+        if passes > SECTIONS.len() {
+            let line = unsettled(&previous, &program).or(lines.first()).expect("an empty source settles at once");
+            errors.push(line.error(LineError::new(line.split_label().1, "section layout does not converge")));
+            errors.sort_by_key(|err| (err.line, err.column));
+            return Err(errors);
+        }
+        starts = next;
+        previous = program;
+    };
+    symbols.second_pass();
+    let (program, layout) = run_pass(&lines, &mut symbols, &mut errors, starts);
+    if !errors.is_empty() {
+        // Both passes may fail at the same place; the first says why
+        errors.sort_by_key(|err| (err.line, err.column));
+        let mut seen = HashSet::new();
+        errors.retain(|err| seen.insert((err.line, err.column, err.text.clone())));
+        return Err(errors);
+    }
+
+    let mut sections: [Vec<u8>; 3] = Default::default();
+    let mut section = SectionKind::Text;
+    let mut encoded = Vec::new();
+    for (line, pc, stmt) in &program {
+        if let Statement::Section(kind) = stmt {
+            section = *kind;
+        }
+        match stmt.encode(*pc) {
+            Ok(stmt_bytes) => {
+                sections[section as usize].extend(&stmt_bytes);
+                encoded.push((*line, *pc, stmt_bytes));
+            }
+            Err(reason) => errors.push(line.error(LineError::new(line.split_label().1, reason))),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let listing = listing(text, &encoded, &symbols);
+    let map = symbol_map(&symbols);
+    let (origin, bytes) = image(&layout, &sections);
+    let executable = executable(&program, &layout, sections, &symbols);
+    Ok(Assembly { bytes, origin, listing, map, executable })
+}
+
+/// Lay out `.text` and `.data` as one image, with zeros between them if
+/// there is a gap, and return it with the address it starts at. `.bss` is
+/// left out: memory starts zeroed.
+fn image(layout: &Layout, sections: &[Vec<u8>; 3]) -> (U24, Vec<u8>) {
+    let mut filled: Vec<_> = [SectionKind::Text, SectionKind::Data]
+        .into_iter()
+        .filter(|kind| !sections[*kind as usize].is_empty())
+        .map(|kind| (layout[kind as usize].0.as_u32(), &sections[kind as usize]))
+        .collect();
+    filled.sort_by_key(|(start, _)| *start);
+    let Some(&(origin, _)) = filled.first() else { return (U24::new(0), Vec::new()) };
+    let mut bytes = Vec::new();
+    for (start, section) in filled {
+        bytes.resize((start - origin) as usize, 0);
+        bytes.extend(section);
+    }
+    (U24::new(origin), bytes)
+}
+
+/// Package the program as an executable, with each section that isn't
+/// empty at its own address. It starts at the `.entry` address, or at the
+/// start of `.text` if there is none.
+fn executable(
+    program: &[(&SourceLine, U24, Statement)],
+    layout: &Layout,
+    sections: [Vec<u8>; 3],
+    symbols: &Symbols,
+) -> Executable {
+    let entry = program
+        .iter()
+        .find_map(|(.., stmt)| match stmt {
+            Statement::Entry(addr) => Some(*addr),
+            _ => None,
         })
+        .unwrap_or(layout[SectionKind::Text as usize].0);
+    let mut labels: Vec<_> = symbols
+        .values
+        .iter()
+        .filter(|(name, _)| !symbols.constants.contains(*name))
+        .map(|(name, value)| Symbol { name: name.clone(), value: U24::new(*value as u32) })
+        .collect();
+    labels.sort_by(|a, b| (a.value.value(), &a.name).cmp(&(b.value.value(), &b.name)));
+    let sections = SECTIONS
+        .into_iter()
+        .zip(sections)
+        .filter(|(_, bytes)| !bytes.is_empty())
+        .map(|(kind, bytes)| Section { kind, address: layout[kind as usize].0, bytes })
         .collect();
+    Executable { entry, sections, symbols: labels }
+}
+
+/// List every label and constant, sorted by value, as lines of
+/// `VALUE KIND NAME`: the value is hex with a `0x` prefix if it is an
+/// address, or signed decimal otherwise, and the kind is `label` or
+/// `const`. For example, `0x000104 label main.loop`.
+fn symbol_map(symbols: &Symbols) -> String {
+    let mut entries: Vec<_> = symbols.values.iter().map(|(name, value)| (*value, name)).collect();
+    entries.sort();
+    let mut out = String::new();
+    for (value, name) in entries {
+        let kind = if symbols.constants.contains(name) { "const" } else { "label" };
+        let _ = match u32::try_from(value) {
+            Ok(value) if value <= 0xFF_FFFF => writeln!(out, "0x{:06X} {} {}", value, kind, name),
+            _ => writeln!(out, "{} {} {}", value, kind, name),
+        };
+    }
+    out
+}
+
+/// Bytes shown on each row of a listing.
+const LISTING_WIDTH: usize = 8;
+
+/// Lay out a listing: each source line beside the address and bytes it
+/// assembled to, with the statements macros and `.rept` expanded to after
+/// the line that expanded them, then the value of every symbol.
+fn listing(text: &str, encoded: &[(&SourceLine, U24, Vec<u8>)], symbols: &Symbols) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "ADDR    BYTES                     LINE  SOURCE");
+    let mut entries = encoded.iter().peekable();
+    for (i, raw) in text.lines().enumerate() {
+        let number = i + 1;
+        let mut source = Some(raw.trim_end());
+        while let Some((line, pc, bytes)) = entries.next_if(|(line, ..)| line.number == number) {
+            if line.expansion.is_none() {
+                list_row(&mut out, Some(*pc), bytes, Some(number), source.take().unwrap_or_default());
+                continue;
+            }
+            if let Some(source) = source.take() {
+                list_row(&mut out, None, &[], Some(number), source);
+            }
+            list_row(&mut out, Some(*pc), bytes, None, &format!("+ {}", line.text()));
+        }
+        if let Some(source) = source {
+            list_row(&mut out, None, &[], Some(number), source);
+        }
+    }
+
+    let mut names: Vec<_> = symbols.values.iter().collect();
+    names.sort();
+    let _ = writeln!(out, "\nSYMBOLS");
+    for (name, value) in names {
+        let _ = match u32::try_from(*value) {
+            Ok(value) if value <= 0xFF_FFFF => writeln!(out, "{:06X}  {}", value, name),
+            _ => writeln!(out, "{:>6}  {}", value, name),
+        };
+    }
+    out
+}
+
+/// Add a row to a listing, with more rows for bytes that don't fit on one.
+fn list_row(out: &mut String, pc: Option<U24>, bytes: &[u8], number: Option<usize>, source: &str) {
+    let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ");
+    let addr = pc.map_or(String::new(), |pc| format!("{:06X}", pc.as_u32()));
+    let number = number.map_or(String::new(), |number| number.to_string());
+    let mut chunks = bytes.chunks(LISTING_WIDTH);
+    let first = hex(chunks.next().unwrap_or_default());
+    let row = format!("{:6}  {:23}  {:>5}  {}", addr, first, number, source);
+    let _ = writeln!(out, "{}", row.trim_end());
+    for (i, chunk) in chunks.enumerate() {
+        let pc = pc.unwrap_or(U24::new(0)) + ((i + 1) * LISTING_WIDTH) as u32;
+        let _ = writeln!(out, "{:06X}  {}", pc.as_u32(), hex(chunk));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rexta::op::OpCode;
+
+    /// Assemble `insn`, which refers to `target` before it is defined,
+    /// followed by 0x100 bytes of padding and then `target`. Returns the
+    /// instruction's bytes and the address of `target`.
+    fn forward_reference(insn: &str) -> (Vec<u8>, u32) {
+        let text = format!("    {}\n    .space 0x100\ntarget:\n    HLT\n", insn);
+        let bytes = assemble(&text).unwrap().bytes;
+        let len = bytes.len() - 0x100 - 2;
+        (bytes[..len].to_vec(), (len + 0x100) as u32)
+    }
+
+    fn encoded(code: OpCode, operands: &[u8]) -> Vec<u8> {
+        let mut bytes = (code as u16).to_le_bytes().to_vec();
+        bytes.extend(operands);
+        bytes
+    }
+
+    #[test]
+    fn jumps_resolve_forward_labels() {
+        let jumps = [
+            ("JMP", OpCode::JMP),
+            ("JZ", OpCode::JZ),
+            ("JC", OpCode::JC),
+            ("JNZ", OpCode::JNZ),
+            ("JNC", OpCode::JNC),
+            ("JSR", OpCode::JSR),
+            ("JLT", OpCode::JLT),
+            ("JGE", OpCode::JGE),
+            ("JGT", OpCode::JGT),
+            ("JLE", OpCode::JLE),
+            ("JMPA", OpCode::JMPA),
+            ("JZA", OpCode::JZA),
+            ("JCA", OpCode::JCA),
+            ("JNZA", OpCode::JNZA),
+            ("JNCA", OpCode::JNCA),
+            ("JSRA", OpCode::JSRA),
+        ];
+        for (mnemonic, code) in jumps {
+            let (bytes, target) = forward_reference(&format!("{} target", mnemonic));
+            assert_eq!(bytes, encoded(code, &U24::new(target).to_le_bytes()), "{}", mnemonic);
+        }
+    }
+
+    #[test]
+    fn branches_resolve_forward_labels() {
+        let branches = [
+            ("BRA", OpCode::BRA1, OpCode::BRA2),
+            ("BZ", OpCode::BZ1, OpCode::BZ2),
+            ("BNZ", OpCode::BNZ1, OpCode::BNZ2),
+            ("BC", OpCode::BC1, OpCode::BC2),
+            ("BNC", OpCode::BNC1, OpCode::BNC2),
+        ];
+        for (mnemonic, short, long) in branches {
+            // 0x100 bytes of padding is too far for a 1-byte displacement
+            let text = format!("    {}.1 target\n    .space 0x10\ntarget:\n", mnemonic);
+            assert_eq!(assemble(&text).unwrap().bytes[..3], encoded(short, &[0x10]), "{}.1", mnemonic);
+
+            let (bytes, _) = forward_reference(&format!("{}.2 target", mnemonic));
+            assert_eq!(bytes, encoded(long, &0x100u16.to_le_bytes()), "{}.2", mnemonic);
+        }
+    }
 
-    program.iter().flat_map(|i| i.encode()).collect()
+    #[test]
+    fn loads_and_stores_resolve_forward_labels() {
+        let accesses = [
+            ("LOAD.1 R1, target", OpCode::LOAD1, 0x10),
+            ("LOAD.2 R1, target", OpCode::LOAD2, 0x10),
+            ("LOAD.3 R1, target", OpCode::LOAD3, 0x10),
+            ("LEA R1, target", OpCode::LEA, 0x10),
+            ("STORE.1 R1, target", OpCode::STORE1, 0x01),
+            ("STORE.2 R1, target", OpCode::STORE2, 0x01),
+            ("STORE.3 R1, target", OpCode::STORE3, 0x01),
+            ("JTAB R1, target", OpCode::JTAB, 0x01),
+        ];
+        for (insn, code, reg) in accesses {
+            let (bytes, target) = forward_reference(insn);
+            let mut operands = vec![reg];
+            operands.extend(U24::new(target).to_le_bytes());
+            assert_eq!(bytes, encoded(code, &operands), "{}", insn);
+        }
+    }
+
+    /// The reasons `text` fails to assemble.
+    fn errors(text: &str) -> Vec<String> {
+        match assemble(text) {
+            Ok(_) => panic!("assembled without errors"),
+            Err(errors) => errors.into_iter().map(|err| err.reason).collect(),
+        }
+    }
+
+    #[test]
+    fn macro_arguments_replace_parameters() {
+        let text = ".macro put reg, value\n    LOADI.1 \\reg, \\value\n.endm\n    put R1, 42\n";
+        assert_eq!(assemble(text).unwrap().bytes, encoded(OpCode::LOADI1, &[0x10, 42]));
+    }
+
+    #[test]
+    fn each_macro_expansion_gets_its_own_labels() {
+        let text = ".macro spin\nhere\\@:\n    JMP here\\@\n.endm\n    spin\n    spin\n";
+        let mut expected = encoded(OpCode::JMP, &[0, 0, 0]);
+        expected.extend(encoded(OpCode::JMP, &[5, 0, 0]));
+        assert_eq!(assemble(text).unwrap().bytes, expected);
+    }
+
+    #[test]
+    fn macro_with_the_wrong_number_of_arguments_is_an_error() {
+        let text = ".macro put reg, value\n    LOADI.1 \\reg, \\value\n.endm\n    put R1\n";
+        assert_eq!(errors(text), ["expected 2 argument(s)"]);
+    }
+
+    #[test]
+    fn macro_that_invokes_itself_is_an_error() {
+        let text = ".macro again\n    again\n.endm\n    again\n";
+        let errors = errors(text);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].ends_with("expansion too deep"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn rept_counter_counts_the_repetitions() {
+        assert_eq!(assemble("    .rept 3, i\n    .db i\n    .endr\n").unwrap().bytes, [0, 1, 2]);
+    }
+
+    #[test]
+    fn rept_blocks_nest() {
+        let text = "    .rept 2, i\n    .rept 3, j\n    .db i, j\n    .endr\n    .endr\n";
+        assert_eq!(assemble(text).unwrap().bytes, [0, 0, 0, 1, 0, 2, 1, 0, 1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn rept_errors() {
+        assert_eq!(errors("    .rept 2\n    NOP\n"), ["missing .endr"]);
+        assert_eq!(errors("    .rept later\n    NOP\n    .endr\nlater .equ 2\n"), ["expected a count"]);
+    }
+
+    #[test]
+    fn macro_labels_leave_the_local_label_scope_alone() {
+        let text = ".macro spin\nloop\\@:\n    JMP loop\\@\n.endm\nmain:\n    spin\n.loop:\n    JMP .loop\n";
+        let map = assemble(text).unwrap().map;
+        assert!(map.contains(" label main.loop\n"), "{}", map);
+    }
+
+    /// A small program with a macro expansion, a label and a constant,
+    /// for pinning the listing and map formats
+    const LISTED: &str = ".macro two\n    .db 1, 2\n.endm\nstart:\n    two\nVALUE .equ 5\n    JMP start\n";
+
+    #[test]
+    fn listing_format() {
+        let expected = "\
+ADDR    BYTES                     LINE  SOURCE
+                                     1  .macro two
+                                     2      .db 1, 2
+                                     3  .endm
+                                     4  start:
+                                     5      two
+000000  01 02                           + .db 1, 2
+                                     6  VALUE .equ 5
+000002  00 06 00 00 00               7      JMP start
+
+SYMBOLS
+000005  VALUE
+000000  start
+";
+        assert_eq!(assemble(LISTED).unwrap().listing, expected);
+    }
+
+    #[test]
+    fn symbol_map_format() {
+        assert_eq!(assemble(LISTED).unwrap().map, "0x000000 label start\n0x000005 const VALUE\n");
+    }
+
+    #[test]
+    fn sizes_that_chase_a_later_section_are_rejected() {
+        let Err(errors) = assemble(".data\nd: .db 1\n.text\n    .space d\n    HLT\n") else {
+            panic!("assembled without errors");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].reason.as_str()), (4, "section layout does not converge"));
+    }
 }
@@ -1,4 +1,4 @@
-use rexta::{op::OpCode, u24::U24};
+use rexta::{executable::SectionKind, op::OpCode, u24::U24};
 
 #[derive(Debug)]
 pub enum Register {
@@ -29,13 +29,40 @@ impl Register {
     }
 }
 
+/// Floating-point register (FPU extension).
 #[derive(Debug)]
-pub enum Address {
-    Addr(U24),
-    Label(String),
+pub enum FRegister {
+    F0,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
 }
 
+impl FRegister {
+    pub fn encode(&self) -> u8 {
+        match self {
+            FRegister::F0 => 0,
+            FRegister::F1 => 1,
+            FRegister::F2 => 2,
+            FRegister::F3 => 3,
+            FRegister::F4 => 4,
+            FRegister::F5 => 5,
+            FRegister::F6 => 6,
+            FRegister::F7 => 7,
+        }
+    }
+}
+
+/// An address operand. Labels and constants are replaced by their values
+/// as the source is parsed.
+pub type Address = U24;
+
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Instruction {
     ADD1 { rd: Register, rs: Register },
     ADD2 { rd: Register, rs: Register },
@@ -45,6 +72,18 @@ pub enum Instruction {
     SUB2 { rd: Register, rs: Register },
     SUB3 { rd: Register, rs: Register },
 
+    MUL1 { rd: Register, rs: Register },
+    MUL2 { rd: Register, rs: Register },
+    MUL3 { rd: Register, rs: Register },
+
+    DIV1 { rd: Register, rs: Register },
+    DIV2 { rd: Register, rs: Register },
+    DIV3 { rd: Register, rs: Register },
+
+    MOD1 { rd: Register, rs: Register },
+    MOD2 { rd: Register, rs: Register },
+    MOD3 { rd: Register, rs: Register },
+
     AND1 { rd: Register, rs: Register },
     AND2 { rd: Register, rs: Register },
     AND3 { rd: Register, rs: Register },
@@ -69,10 +108,74 @@ pub enum Instruction {
     ADDI2 { rd: Register, imm: u16 },
     ADDI3 { rd: Register, imm: U24 },
 
+    SUBI1 { rd: Register, imm: u8 },
+    SUBI2 { rd: Register, imm: u16 },
+    SUBI3 { rd: Register, imm: U24 },
+
+    CMPI1 { rd: Register, imm: u8 },
+    CMPI2 { rd: Register, imm: u16 },
+    CMPI3 { rd: Register, imm: U24 },
+
+    ANDI1 { rd: Register, imm: u8 },
+    ANDI2 { rd: Register, imm: u16 },
+    ANDI3 { rd: Register, imm: U24 },
+
+    ORI1 { rd: Register, imm: u8 },
+    ORI2 { rd: Register, imm: u16 },
+    ORI3 { rd: Register, imm: U24 },
+
+    XORI1 { rd: Register, imm: u8 },
+    XORI2 { rd: Register, imm: u16 },
+    XORI3 { rd: Register, imm: U24 },
+
+    SHLI1 { rd: Register, imm: u8 },
+    SHLI2 { rd: Register, imm: u8 },
+    SHLI3 { rd: Register, imm: u8 },
+
+    SHRI1 { rd: Register, imm: u8 },
+    SHRI2 { rd: Register, imm: u8 },
+    SHRI3 { rd: Register, imm: u8 },
+
+    ROLI1 { rd: Register, imm: u8 },
+    ROLI2 { rd: Register, imm: u8 },
+    ROLI3 { rd: Register, imm: u8 },
+
+    RORI1 { rd: Register, imm: u8 },
+    RORI2 { rd: Register, imm: u8 },
+    RORI3 { rd: Register, imm: u8 },
+
+    BSET1 { rd: Register, imm: u8 },
+    BSET2 { rd: Register, imm: u8 },
+    BSET3 { rd: Register, imm: u8 },
+
+    BCLR1 { rd: Register, imm: u8 },
+    BCLR2 { rd: Register, imm: u8 },
+    BCLR3 { rd: Register, imm: u8 },
+
+    BTST1 { rd: Register, imm: u8 },
+    BTST2 { rd: Register, imm: u8 },
+    BTST3 { rd: Register, imm: u8 },
+
     MOV1 { rd: Register, rs: Register },
     MOV2 { rd: Register, rs: Register },
     MOV3 { rd: Register, rs: Register },
 
+    XCHG1 { rd: Register, rs: Register },
+    XCHG2 { rd: Register, rs: Register },
+    XCHG3 { rd: Register, rs: Register },
+
+    CMOVZ1 { rd: Register, rs: Register },
+    CMOVZ2 { rd: Register, rs: Register },
+    CMOVZ3 { rd: Register, rs: Register },
+
+    CMOVNZ1 { rd: Register, rs: Register },
+    CMOVNZ2 { rd: Register, rs: Register },
+    CMOVNZ3 { rd: Register, rs: Register },
+
+    CMOVC1 { rd: Register, rs: Register },
+    CMOVC2 { rd: Register, rs: Register },
+    CMOVC3 { rd: Register, rs: Register },
+
     INC1 { rd: Register },
     INC2 { rd: Register },
     INC3 { rd: Register },
@@ -93,6 +196,45 @@ pub enum Instruction {
     SHR2 { rd: Register },
     SHR3 { rd: Register },
 
+    SHLR1 { rd: Register, rs: Register },
+    SHLR2 { rd: Register, rs: Register },
+    SHLR3 { rd: Register, rs: Register },
+
+    SHRR1 { rd: Register, rs: Register },
+    SHRR2 { rd: Register, rs: Register },
+    SHRR3 { rd: Register, rs: Register },
+
+    CLZ1 { rd: Register, rs: Register },
+    CLZ2 { rd: Register, rs: Register },
+    CLZ3 { rd: Register, rs: Register },
+
+    POPCNT1 { rd: Register, rs: Register },
+    POPCNT2 { rd: Register, rs: Register },
+    POPCNT3 { rd: Register, rs: Register },
+
+    SEX2 { rd: Register },
+    SEX3 { rd: Register },
+
+    BSWAP2 { rd: Register },
+    BSWAP3 { rd: Register },
+
+    DAA { rd: Register },
+
+    CPUID { rd: Register },
+
+    FLOAD { fd: FRegister, rs: Register },
+    FSTORE { rd: Register, fs: FRegister },
+    FITOF { fd: FRegister, rs: Register },
+    FFTOI { rd: Register, fs: FRegister },
+    FADD { fd: FRegister, fs: FRegister },
+    FSUB { fd: FRegister, fs: FRegister },
+    FMUL { fd: FRegister, fs: FRegister },
+    FDIV { fd: FRegister, fs: FRegister },
+
+    MOVSPR { rd: Register },
+    MOVRSP { rs: Register },
+    MOVPC { rd: Register },
+
     ROL1 { rd: Register },
     ROL2 { rd: Register },
     ROL3 { rd: Register },
@@ -125,12 +267,73 @@ pub enum Instruction {
     STORE2 { rs: Register, addr: Address },
     STORE3 { rs: Register, addr: Address },
 
+    LEA { rd: Register, addr: Address },
+
+    JTAB { rs: Register, addr: Address },
+
+    LOADR1 { rd: Register, rs: Register },
+    LOADR2 { rd: Register, rs: Register },
+    LOADR3 { rd: Register, rs: Register },
+
+    STORER1 { rd: Register, rs: Register },
+    STORER2 { rd: Register, rs: Register },
+    STORER3 { rd: Register, rs: Register },
+
+    TAS { rd: Register, rs: Register },
+
+    LOADX1 { rd: Register, rs: Register, disp: i16 },
+    LOADX2 { rd: Register, rs: Register, disp: i16 },
+    LOADX3 { rd: Register, rs: Register, disp: i16 },
+
+    STOREX1 { rd: Register, rs: Register, disp: i16 },
+    STOREX2 { rd: Register, rs: Register, disp: i16 },
+    STOREX3 { rd: Register, rs: Register, disp: i16 },
+
+    LOADPI1 { rd: Register, rs: Register },
+    LOADPI2 { rd: Register, rs: Register },
+    LOADPI3 { rd: Register, rs: Register },
+
+    STOREPD1 { rd: Register, rs: Register },
+    STOREPD2 { rd: Register, rs: Register },
+    STOREPD3 { rd: Register, rs: Register },
+
+    LOADS1 { rd: Register, offset: u8 },
+    LOADS2 { rd: Register, offset: u8 },
+    LOADS3 { rd: Register, offset: u8 },
+
+    STORES1 { rs: Register, offset: u8 },
+    STORES2 { rs: Register, offset: u8 },
+    STORES3 { rs: Register, offset: u8 },
+
+    MEMCPY { rd: Register, rs: Register, rc: Register },
+    ENTER { size: u16 },
+    MAC { rd: Register, rs: Register, rt: Register },
+
     JMP { addr: Address },
     JZ { addr: Address },
     JC { addr: Address },
     JNZ { addr: Address },
     JNC { addr: Address },
     JSR { addr: Address },
+    JLT { addr: Address },
+    JGE { addr: Address },
+    JGT { addr: Address },
+    JLE { addr: Address },
+    JSRR { rs: Register },
+
+    BRA1 { addr: Address },
+    BZ1 { addr: Address },
+    BNZ1 { addr: Address },
+    BC1 { addr: Address },
+    BNC1 { addr: Address },
+
+    SWI { vector: u8 },
+
+    BRA2 { addr: Address },
+    BZ2 { addr: Address },
+    BNZ2 { addr: Address },
+    BC2 { addr: Address },
+    BNC2 { addr: Address },
 
     JMPA { addr: Address },
     JZA { addr: Address },
@@ -141,6 +344,17 @@ pub enum Instruction {
 
     RTS,
     HLT,
+    PUSHA,
+    POPA,
+    PUSHF,
+    POPF,
+    RTI,
+    SEI,
+    CLI,
+    WAIT,
+    LEAVE,
+    SED,
+    CLD,
 }
 
 impl Instruction {
@@ -154,6 +368,17 @@ impl Instruction {
         match self {
             Instruction::HLT => OpCode::HLT,
             Instruction::RTS => OpCode::RTS,
+            Instruction::PUSHA => OpCode::PUSHA,
+            Instruction::POPA => OpCode::POPA,
+            Instruction::PUSHF => OpCode::PUSHF,
+            Instruction::POPF => OpCode::POPF,
+            Instruction::RTI => OpCode::RTI,
+            Instruction::SEI => OpCode::SEI,
+            Instruction::CLI => OpCode::CLI,
+            Instruction::WAIT => OpCode::WAIT,
+            Instruction::LEAVE => OpCode::LEAVE,
+            Instruction::SED => OpCode::SED,
+            Instruction::CLD => OpCode::CLD,
             Instruction::ADD1 { .. } => OpCode::ADD1,
             Instruction::SUB1 { .. } => OpCode::SUB1,
             Instruction::AND1 { .. } => OpCode::AND1,
@@ -172,6 +397,25 @@ impl Instruction {
             Instruction::TST1 { .. } => OpCode::TST1,
             Instruction::PUSH1 { .. } => OpCode::PUSH1,
             Instruction::POP1 { .. } => OpCode::POP1,
+            Instruction::MUL1 { .. } => OpCode::MUL1,
+            Instruction::DIV1 { .. } => OpCode::DIV1,
+            Instruction::MOD1 { .. } => OpCode::MOD1,
+            Instruction::SHLR1 { .. } => OpCode::SHLR1,
+            Instruction::SHRR1 { .. } => OpCode::SHRR1,
+            Instruction::CLZ1 { .. } => OpCode::CLZ1,
+            Instruction::POPCNT1 { .. } => OpCode::POPCNT1,
+            Instruction::XCHG1 { .. } => OpCode::XCHG1,
+            Instruction::CMOVZ1 { .. } => OpCode::CMOVZ1,
+            Instruction::CMOVNZ1 { .. } => OpCode::CMOVNZ1,
+            Instruction::CMOVC1 { .. } => OpCode::CMOVC1,
+            Instruction::LOADR1 { .. } => OpCode::LOADR1,
+            Instruction::STORER1 { .. } => OpCode::STORER1,
+            Instruction::LOADX1 { .. } => OpCode::LOADX1,
+            Instruction::STOREX1 { .. } => OpCode::STOREX1,
+            Instruction::LOADPI1 { .. } => OpCode::LOADPI1,
+            Instruction::STOREPD1 { .. } => OpCode::STOREPD1,
+            Instruction::LOADS1 { .. } => OpCode::LOADS1,
+            Instruction::STORES1 { .. } => OpCode::STORES1,
             Instruction::ADD2 { .. } => OpCode::ADD2,
             Instruction::SUB2 { .. } => OpCode::SUB2,
             Instruction::AND2 { .. } => OpCode::AND2,
@@ -190,6 +434,27 @@ impl Instruction {
             Instruction::TST2 { .. } => OpCode::TST2,
             Instruction::PUSH2 { .. } => OpCode::PUSH2,
             Instruction::POP2 { .. } => OpCode::POP2,
+            Instruction::MUL2 { .. } => OpCode::MUL2,
+            Instruction::DIV2 { .. } => OpCode::DIV2,
+            Instruction::MOD2 { .. } => OpCode::MOD2,
+            Instruction::SHLR2 { .. } => OpCode::SHLR2,
+            Instruction::SHRR2 { .. } => OpCode::SHRR2,
+            Instruction::CLZ2 { .. } => OpCode::CLZ2,
+            Instruction::POPCNT2 { .. } => OpCode::POPCNT2,
+            Instruction::XCHG2 { .. } => OpCode::XCHG2,
+            Instruction::CMOVZ2 { .. } => OpCode::CMOVZ2,
+            Instruction::CMOVNZ2 { .. } => OpCode::CMOVNZ2,
+            Instruction::CMOVC2 { .. } => OpCode::CMOVC2,
+            Instruction::LOADR2 { .. } => OpCode::LOADR2,
+            Instruction::STORER2 { .. } => OpCode::STORER2,
+            Instruction::LOADX2 { .. } => OpCode::LOADX2,
+            Instruction::STOREX2 { .. } => OpCode::STOREX2,
+            Instruction::LOADPI2 { .. } => OpCode::LOADPI2,
+            Instruction::STOREPD2 { .. } => OpCode::STOREPD2,
+            Instruction::LOADS2 { .. } => OpCode::LOADS2,
+            Instruction::STORES2 { .. } => OpCode::STORES2,
+            Instruction::SEX2 { .. } => OpCode::SEX2,
+            Instruction::BSWAP2 { .. } => OpCode::BSWAP2,
             Instruction::ADD3 { .. } => OpCode::ADD3,
             Instruction::SUB3 { .. } => OpCode::SUB3,
             Instruction::AND3 { .. } => OpCode::AND3,
@@ -208,16 +473,94 @@ impl Instruction {
             Instruction::TST3 { .. } => OpCode::TST3,
             Instruction::PUSH3 { .. } => OpCode::PUSH3,
             Instruction::POP3 { .. } => OpCode::POP3,
+            Instruction::MUL3 { .. } => OpCode::MUL3,
+            Instruction::DIV3 { .. } => OpCode::DIV3,
+            Instruction::MOD3 { .. } => OpCode::MOD3,
+            Instruction::SHLR3 { .. } => OpCode::SHLR3,
+            Instruction::SHRR3 { .. } => OpCode::SHRR3,
+            Instruction::CLZ3 { .. } => OpCode::CLZ3,
+            Instruction::POPCNT3 { .. } => OpCode::POPCNT3,
+            Instruction::XCHG3 { .. } => OpCode::XCHG3,
+            Instruction::CMOVZ3 { .. } => OpCode::CMOVZ3,
+            Instruction::CMOVNZ3 { .. } => OpCode::CMOVNZ3,
+            Instruction::CMOVC3 { .. } => OpCode::CMOVC3,
+            Instruction::LOADR3 { .. } => OpCode::LOADR3,
+            Instruction::STORER3 { .. } => OpCode::STORER3,
+            Instruction::TAS { .. } => OpCode::TAS,
+            Instruction::LOADX3 { .. } => OpCode::LOADX3,
+            Instruction::STOREX3 { .. } => OpCode::STOREX3,
+            Instruction::LOADPI3 { .. } => OpCode::LOADPI3,
+            Instruction::STOREPD3 { .. } => OpCode::STOREPD3,
+            Instruction::MEMCPY { .. } => OpCode::MEMCPY,
+            Instruction::ENTER { .. } => OpCode::ENTER,
+            Instruction::MAC { .. } => OpCode::MAC,
+            Instruction::LOADS3 { .. } => OpCode::LOADS3,
+            Instruction::STORES3 { .. } => OpCode::STORES3,
+            Instruction::SEX3 { .. } => OpCode::SEX3,
+            Instruction::BSWAP3 { .. } => OpCode::BSWAP3,
+            Instruction::DAA { .. } => OpCode::DAA,
+            Instruction::CPUID { .. } => OpCode::CPUID,
+            Instruction::FLOAD { .. } => OpCode::FLOAD,
+            Instruction::FSTORE { .. } => OpCode::FSTORE,
+            Instruction::FITOF { .. } => OpCode::FITOF,
+            Instruction::FFTOI { .. } => OpCode::FFTOI,
+            Instruction::FADD { .. } => OpCode::FADD,
+            Instruction::FSUB { .. } => OpCode::FSUB,
+            Instruction::FMUL { .. } => OpCode::FMUL,
+            Instruction::FDIV { .. } => OpCode::FDIV,
+            Instruction::MOVSPR { .. } => OpCode::MOVSPR,
+            Instruction::MOVRSP { .. } => OpCode::MOVRSP,
+            Instruction::MOVPC { .. } => OpCode::MOVPC,
             Instruction::LOADI1 { .. } => OpCode::LOADI1,
             Instruction::ADDI1 { .. } => OpCode::ADDI1,
+            Instruction::CMPI1 { .. } => OpCode::CMPI1,
+            Instruction::SUBI1 { .. } => OpCode::SUBI1,
+            Instruction::ANDI1 { .. } => OpCode::ANDI1,
+            Instruction::ORI1 { .. } => OpCode::ORI1,
+            Instruction::XORI1 { .. } => OpCode::XORI1,
+            Instruction::SHLI1 { .. } => OpCode::SHLI1,
+            Instruction::SHRI1 { .. } => OpCode::SHRI1,
+            Instruction::ROLI1 { .. } => OpCode::ROLI1,
+            Instruction::RORI1 { .. } => OpCode::RORI1,
+            Instruction::BSET1 { .. } => OpCode::BSET1,
+            Instruction::BCLR1 { .. } => OpCode::BCLR1,
+            Instruction::BTST1 { .. } => OpCode::BTST1,
             Instruction::JMP { .. } => OpCode::JMP,
             Instruction::JZ { .. } => OpCode::JZ,
             Instruction::JNZ { .. } => OpCode::JNZ,
             Instruction::JC { .. } => OpCode::JC,
             Instruction::JNC { .. } => OpCode::JNC,
             Instruction::JSR { .. } => OpCode::JSR,
+            Instruction::JLT { .. } => OpCode::JLT,
+            Instruction::JGE { .. } => OpCode::JGE,
+            Instruction::JGT { .. } => OpCode::JGT,
+            Instruction::JLE { .. } => OpCode::JLE,
+            Instruction::JSRR { .. } => OpCode::JSRR,
+            Instruction::BRA1 { .. } => OpCode::BRA1,
+            Instruction::BRA2 { .. } => OpCode::BRA2,
+            Instruction::BZ1 { .. } => OpCode::BZ1,
+            Instruction::BZ2 { .. } => OpCode::BZ2,
+            Instruction::BNZ1 { .. } => OpCode::BNZ1,
+            Instruction::BNZ2 { .. } => OpCode::BNZ2,
+            Instruction::BC1 { .. } => OpCode::BC1,
+            Instruction::BC2 { .. } => OpCode::BC2,
+            Instruction::BNC1 { .. } => OpCode::BNC1,
+            Instruction::BNC2 { .. } => OpCode::BNC2,
+            Instruction::SWI { .. } => OpCode::SWI,
             Instruction::LOADI2 { .. } => OpCode::LOADI2,
             Instruction::ADDI2 { .. } => OpCode::ADDI2,
+            Instruction::CMPI2 { .. } => OpCode::CMPI2,
+            Instruction::SUBI2 { .. } => OpCode::SUBI2,
+            Instruction::ANDI2 { .. } => OpCode::ANDI2,
+            Instruction::ORI2 { .. } => OpCode::ORI2,
+            Instruction::XORI2 { .. } => OpCode::XORI2,
+            Instruction::SHLI2 { .. } => OpCode::SHLI2,
+            Instruction::SHRI2 { .. } => OpCode::SHRI2,
+            Instruction::ROLI2 { .. } => OpCode::ROLI2,
+            Instruction::RORI2 { .. } => OpCode::RORI2,
+            Instruction::BSET2 { .. } => OpCode::BSET2,
+            Instruction::BCLR2 { .. } => OpCode::BCLR2,
+            Instruction::BTST2 { .. } => OpCode::BTST2,
             Instruction::JMPA { .. } => OpCode::JMPA,
             Instruction::JZA { .. } => OpCode::JZA,
             Instruction::JNZA { .. } => OpCode::JNZA,
@@ -231,7 +574,21 @@ impl Instruction {
             Instruction::LOADI3 { .. } => OpCode::LOADI3,
             Instruction::LOAD3 { .. } => OpCode::LOAD3,
             Instruction::STORE3 { .. } => OpCode::STORE3,
+            Instruction::LEA { .. } => OpCode::LEA,
+            Instruction::JTAB { .. } => OpCode::JTAB,
             Instruction::ADDI3 { .. } => OpCode::ADDI3,
+            Instruction::CMPI3 { .. } => OpCode::CMPI3,
+            Instruction::SUBI3 { .. } => OpCode::SUBI3,
+            Instruction::ANDI3 { .. } => OpCode::ANDI3,
+            Instruction::ORI3 { .. } => OpCode::ORI3,
+            Instruction::XORI3 { .. } => OpCode::XORI3,
+            Instruction::SHLI3 { .. } => OpCode::SHLI3,
+            Instruction::SHRI3 { .. } => OpCode::SHRI3,
+            Instruction::ROLI3 { .. } => OpCode::ROLI3,
+            Instruction::RORI3 { .. } => OpCode::RORI3,
+            Instruction::BSET3 { .. } => OpCode::BSET3,
+            Instruction::BCLR3 { .. } => OpCode::BCLR3,
+            Instruction::BTST3 { .. } => OpCode::BTST3,
         }
     }
 
@@ -239,3 +596,69 @@ impl Instruction {
         ((self.opcode() as u16 & 0xE00) >> 9) as u8 + 2
     }
 }
+
+/// A line of source that produces output: an instruction, or data
+/// emitted by a directive.
+#[derive(Debug)]
+pub enum Statement {
+    Instruction(Instruction),
+
+    /// `.db` - bytes.
+    Data8(Vec<Address>),
+
+    /// `.dw` - 16-bit little-endian values.
+    Data16(Vec<Address>),
+
+    /// `.d24` - 24-bit little-endian values, such as label addresses.
+    Data24(Vec<Address>),
+
+    /// `.ascii` / `.asciz` - the bytes of a string, with escapes already
+    /// replaced (and the terminating zero, for `.asciz`).
+    Text(Vec<u8>),
+
+    /// `.space` / `.fill` - `count` copies of the byte `value`.
+    Fill { count: u32, value: u8 },
+
+    /// `.org` - continue at this address, padding the gap with zeros.
+    Org(U24),
+
+    /// `.align` - pad with `fill` up to the next multiple of `boundary`.
+    Align { boundary: u32, fill: u8 },
+
+    /// `.entry` - start executing here rather than at the start of the
+    /// image. Only an executable records it.
+    Entry(U24),
+
+    /// `.text` / `.data` / `.bss` - continue in that section.
+    Section(SectionKind),
+}
+
+impl Statement {
+    /// Bytes of output. The padding `.org` and `.align` add depends on
+    /// where they are, so it is not counted; `.entry` and section
+    /// directives have none.
+    pub fn length(&self) -> u32 {
+        match self {
+            Statement::Instruction(instr) => instr.length() as u32,
+            Statement::Data8(values) => values.len() as u32,
+            Statement::Data16(values) => values.len() as u32 * 2,
+            Statement::Data24(values) => values.len() as u32 * 3,
+            Statement::Text(bytes) => bytes.len() as u32,
+            Statement::Fill { count, .. } => *count,
+            Statement::Org(_) | Statement::Align { .. } | Statement::Entry(_) | Statement::Section(_) => 0,
+        }
+    }
+
+    /// Whether the statement outputs only zeros, as everything in `.bss`
+    /// must.
+    pub fn is_zero(&self) -> bool {
+        matches!(
+            self,
+            Statement::Fill { value: 0, .. }
+                | Statement::Align { fill: 0, .. }
+                | Statement::Org(_)
+                | Statement::Entry(_)
+                | Statement::Section(_)
+        )
+    }
+}
@@ -1,28 +1,69 @@
 mod ast;
 mod assembler;
 
-use std::{env, fs::{self, File}, io::Write, path::Path};
+use std::{env, fs::{self, File}, io::Write, path::Path, process};
 use crate::assembler::assemble;
 
 fn main() {
 
     let args: Vec<String> = env::args().collect();
+    let listing = args.iter().any(|arg| arg == "--listing");
+    let map = args.iter().any(|arg| arg == "--map");
+    let exe = args.iter().any(|arg| arg == "--exe");
 
-    if args.len() < 2 {
-        println!("use: rexta-asm <file>");
+    let Some(source) = args.iter().skip(1).find(|arg| !arg.starts_with("--")) else {
+        println!("use: rexta-asm [--listing] [--map] [--exe] <file>");
         return;        
-    }
+    };
 
-    let source_path = Path::new(&args[1]);
+    let source_path = Path::new(source);
 
     let program = fs::read_to_string(source_path).expect("unable to read source file");
   
-    let bytes: Vec<u8> = assemble(program.as_str());
+    let assembly = match assemble(program.as_str()) {
+        Ok(assembly) => assembly,
+        Err(errors) => {
+            for err in &errors {
+                eprintln!("{}:{}", source_path.display(), err);
+            }
+            eprintln!("{} error(s); nothing written", errors.len());
+            process::exit(1);
+        }
+    };
     
     let dest_path = source_path.with_extension("b");
     let mut dest_file = File::create(&dest_path).expect("failed to create output file");
     
-    dest_file.write_all(&bytes).expect("failed to write binary data to file");
+    dest_file.write_all(&assembly.bytes).expect("failed to write binary data to file");
     
-    println!("Wrote {} bytes to {}", bytes.len(), dest_path.display());
+    println!("Wrote {} bytes to {}", assembly.bytes.len(), dest_path.display());
+    if assembly.origin != 0 {
+        // A raw image doesn't say where it goes
+        println!("Load it at 0x{:06X}, e.g. with rexta-sim --load {:X}", assembly.origin, assembly.origin.value());
+    }
+
+    if listing {
+        let listing_path = source_path.with_extension("lst");
+        fs::write(&listing_path, &assembly.listing).expect("failed to write listing file");
+        println!("Wrote listing to {}", listing_path.display());
+    }
+
+    if map {
+        let map_path = source_path.with_extension("map");
+        fs::write(&map_path, &assembly.map).expect("failed to write map file");
+        println!("Wrote symbols to {}", map_path.display());
+    }
+
+    if exe {
+        let exe_path = source_path.with_extension("rxe");
+        let bytes = match assembly.executable.to_bytes() {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                eprintln!("{}: {}", exe_path.display(), err);
+                process::exit(1);
+            }
+        };
+        fs::write(&exe_path, bytes).expect("failed to write executable file");
+        println!("Wrote executable to {}", exe_path.display());
+    }
 }
\ No newline at end of file
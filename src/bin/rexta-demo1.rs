@@ -1,6 +1,6 @@
 
-use rexta::cpu::Cpu;
-use rexta::cpu::CpuError;
+use rexta::bus::Bus;
+use rexta::cpu::{Cpu, StopReason};
 use rexta::u24::U24;
 
 fn main() {
@@ -13,19 +13,15 @@ fn main() {
     ];
 
     let mut cpu = Cpu::new();
-    cpu.mem[0..program.len()].copy_from_slice(&program);
+    cpu.bus.load(U24::new(0), &program);
     
     match cpu.run() {
-        Ok(()) => {
+        StopReason::Error(err) => {
+            println!("Error: {}", err);
+        }
+        _ => {
             println!("Run successful");
             println!("Value at 0x2000: {0}", cpu.mem_read(U24::new(0x2000)));
         }
-        Err(CpuError::InvalidInstruction) => {
-            println!("Invalid instruction: PC={0:4X}", cpu.pc);
-
-        }
-        Err(CpuError::InvalidOpCode(code)) => {
-            println!("Invalid opcode {0}: PC={1:4X}", code, cpu.pc);
-        }
     }
 }
@@ -1,46 +1,287 @@
-use std::{env, fs, path::Path};
-use rexta::cpu::{Cpu, CpuError};
+use std::{cell::RefCell, env, fs, path::{Path, PathBuf}, process, rc::Rc};
+use rexta::bus::{Bus, BusFault};
+use rexta::cpu::{ResetVector, StopReason};
+use rexta::executable::Executable;
+use rexta::machine::Machine;
+use rexta::power::PowerControl;
+use rexta::rng::Rng;
+use rexta::semihost::Semihost;
+use rexta::sound::Beeper;
+use rexta::trace::TraceBuffer;
 use rexta::u24::U24;
+use rexta::uart::{StdioPort, TcpPort, Uart};
+use rexta::video::TextVideo;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let guest_args = match args.iter().position(|arg| arg == "--") {
+        Some(pos) => args.split_off(pos).split_off(1),
+        None => Vec::new(),
+    };
+
+    let poison = take_option(&mut args, "--poison")
+        .map(|byte| u8::from_str_radix(byte.trim_start_matches("0x"), 16).expect("invalid poison byte"));
+    let trap_uninitialized = take_flag(&mut args, "--trap-uninitialized");
+
+    let mut machine = match build_machine(&mut args, poison, trap_uninitialized) {
+        Ok(machine) => machine,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+
+    let video = take_option(&mut args, "--video").map(|base| {
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid video address");
+        let video = Rc::new(RefCell::new(TextVideo::new()));
+        machine.map_device(base..base + TextVideo::SIZE, video.clone());
+        video
+    });
+
+    let seed = take_option(&mut args, "--seed").map(|seed| seed.parse::<u32>().expect("invalid seed"));
+    if let Some(base) = take_option(&mut args, "--rng") {
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid rng address");
+        machine.map_device(base..base + Rng::SIZE, Rng::new(seed.unwrap_or(0)));
+    }
+
+    let beeper = take_option(&mut args, "--beeper").map(|base| {
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid beeper address");
+        let beeper = Rc::new(RefCell::new(Beeper::new()));
+        #[cfg(feature = "audio")]
+        if let Some(output) = rexta::sound::audio_output() {
+            beeper.borrow_mut().set_output(output);
+        }
+        machine.map_device(base..base + Beeper::SIZE, beeper.clone());
+        beeper
+    });
+
+    let uart_port = take_option(&mut args, "--uart-port").map(|port| port.parse::<u16>().expect("invalid port"));
+    if let Some(base) = take_option(&mut args, "--uart") {
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid uart address");
+        let range = base..base + Uart::SIZE;
+        match uart_port {
+            Some(port) => match TcpPort::bind(("0.0.0.0", port)) {
+                Ok(tcp) => {
+                    println!("Serial console on port {}", port);
+                    machine.map_device(range, Uart::new(tcp, 0));
+                }
+                Err(err) => {
+                    println!("Error: port {}: {}", port, err);
+                    return;
+                }
+            },
+            None => machine.map_device(range, Uart::new(StdioPort::new(), 0)),
+        }
+    }
+
+    if let Some(base) = take_option(&mut args, "--power") {
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid power address");
+        machine.map_device(base..base + PowerControl::SIZE, PowerControl::new());
+    }
+
+    let rom = take_option(&mut args, "--rom");
+    let load = take_option(&mut args, "--load")
+        .map(|base| U24::new(u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid load address")));
+    if let Some(base) = take_option(&mut args, "--perf") {
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid perf address");
+        machine.cpu.perf_base = Some(U24::new(base));
+    }
+    let sandbox = take_option(&mut args, "--sandbox").map(PathBuf::from);
+    let semihost = take_option(&mut args, "--semihost");
+    let monitor = take_flag(&mut args, "--monitor");
 
     if args.len() < 2 {
-        println!("use: rexta-sim <file> [<addr>]");
+        println!("use: rexta-sim [--config <machine.toml>] [--video <addr>] [--rng <addr> [--seed <n>]] [--beeper <addr>] [--uart <addr> [--uart-port <port>]] [--power <addr>] [--semihost <addr> [--sandbox <dir>]] [--rom <addr>:<file>] [--load <addr>] [--monitor] [--perf <addr>] [--poison <byte>] [--trap-uninitialized] <file> [<addr>] [-- <args>...]");
         println!("simulate the file and output the value at <addr> if given");
+        println!("<file> is a raw image loaded at 0, or an executable (.rxe) loaded where it says");
+        println!("--load loads a raw image at <addr> instead and starts execution there");
+        println!("--video maps an 80x25 text screen at <addr> and prints it after the run");
+        println!("--rng maps a random number generator at <addr>, seeded with --seed");
+        println!("--beeper maps a tone generator at <addr> and lists the tones played");
+        println!("--uart maps a serial port at <addr> on stdin/stdout, or on TCP port --uart-port");
+        println!("--power maps a power register at <addr>; the value written becomes the exit status");
+        println!("--semihost maps the host services mailbox at <addr>, with file access under --sandbox");
+        println!("and <file> followed by <args> as the program's arguments");
+        println!("--rom loads a read-only boot ROM at <addr> and starts execution there");
+        println!("--monitor loads the monitor ROM at F000 with its mailbox at E000");
+        println!("--perf maps the performance counters at <addr> and prints them after the run");
+        println!("--poison fills RAM with <byte> instead of zeros; --trap-uninitialized stops on reads of unwritten RAM");
         return;
     }
 
+    // A boot ROM decides where to start, not the program
+    let has_rom = rom.is_some();
+    if let Some(rom) = rom {
+        let Some((base, path)) = rom.split_once(':') else {
+            println!("Error: --rom expects <addr>:<file>");
+            return;
+        };
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid rom address");
+        let bytes = fs::read(path).expect("unable to read rom");
+        machine.load_boot_rom(U24::new(base), &bytes);
+    }
+
+    let mut argv = vec![args[1].clone()];
+    argv.extend(guest_args);
+    if monitor {
+        machine.load_monitor(argv, sandbox);
+    } else if let Some(base) = semihost {
+        let base = u32::from_str_radix(base.trim_start_matches("0x"), 16).expect("invalid semihost address");
+        machine.map_device(base..base + Semihost::SIZE, Semihost::new(argv, sandbox));
+    }
+
     let source_path = Path::new(&args[1]);
     let addr =
         if args.len() < 3 {
             None
         } else {
-            Some(U24::new(u32::from_str_radix(&args[2].trim_start_matches("0x"), 16).unwrap()))
+            Some(U24::new(u32::from_str_radix(args[2].trim_start_matches("0x"), 16).unwrap()))
         };
 
     println!("Executing: {}", source_path.display());
 
     let program = fs::read(source_path).expect("unable to read program");
 
-    let mut cpu = Cpu::new();
-    cpu.mem[0..program.len()].copy_from_slice(&program);
+    machine.cpu.trace = Some(TraceBuffer::new(16));
+    if Executable::is_executable(&program) {
+        let exe = match Executable::from_bytes(&program) {
+            Ok(exe) => exe,
+            Err(err) => {
+                println!("Error: {}", err);
+                return;
+            }
+        };
+        if has_rom {
+            for section in &exe.sections {
+                machine.load(section.address, &section.bytes);
+            }
+        } else {
+            machine.load_executable(&exe);
+        }
+    } else if let Some(addr) = load {
+        machine.load(addr, &program);
+        if !has_rom {
+            machine.cpu.reset_vector = ResetVector::Address(addr);
+            machine.reset();
+        }
+    } else {
+        machine.load(U24::new(0), &program);
+    }
+    // Whatever doesn't fit in RAM is dropped, with a fault left behind
+    if let Some(BusFault::OutOfBounds(addr)) = machine.cpu.bus.take_fault() {
+        println!("Error: program does not fit in memory: 0x{:06X} is past the end", addr.value());
+        return;
+    }
 
-    match cpu.run() {
-        Ok(()) => {
+    let reason = machine.run();
+    // Taken before the value at <addr> is read, which would count
+    let perf = machine.cpu.perf;
+    match &reason {
+        StopReason::Error(err) => {
+            println!("Error: {}", err);
+            if let Some(trace) = &machine.cpu.trace {
+                println!("Last instructions:");
+                print!("{}", trace);
+            }
+        }
+        reason => {
+            if *reason == StopReason::Waiting {
+                println!("CPU waiting for interrupt: PC=0x{0:04X}", machine.cpu.pc);
+            }
+            if let StopReason::PowerOff(code) = reason {
+                println!("Powered off with exit code {}", code);
+            }
             println!("Run successful");
-            match addr {
-                Some(addr) => println!("Value at 0x{0:04X}: 0x{1:02X}", addr, cpu.mem_read(addr)),
-                None => {}
+            if let Some(addr) = addr {
+                println!("Value at 0x{0:04X}: 0x{1:02X}", addr, machine.cpu.mem_read(addr));
             }
-            println!("Executed {} tick(s)", cpu.ic);
+            println!("Executed {} tick(s)", machine.cpu.ic);
         }
-        Err(CpuError::InvalidInstruction) => {
-            println!("Invalid instruction: PC={0:04X}", cpu.pc);
+    }
+
+    if let Some(video) = video {
+        print!("{}", video.borrow().render_ansi());
+    }
 
+    if let Some(beeper) = beeper {
+        for tone in &beeper.borrow().tones {
+            println!("Tone: {} Hz for {} ms", tone.frequency, tone.duration_ms);
         }
-        Err(CpuError::InvalidOpCode(code)) => {
-            println!("Invalid opcode 0x{0:02X}: PC=0x{1:04X}", code, cpu.pc);
+    }
+
+    if machine.cpu.perf_base.is_some() {
+        println!("Instructions: {}", perf.instructions);
+        println!("Cycles: {}", perf.cycles);
+        println!("Memory reads: {}", perf.mem_reads);
+        println!("Memory writes: {}", perf.mem_writes);
+        println!("Branches taken: {}", perf.branches);
+    }
+
+    if let StopReason::PowerOff(code) = reason {
+        process::exit(code as i32);
+    }
+}
+
+/// Remove the flag `name` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(pos) => {
+            args.remove(pos);
+            true
         }
+        None => false,
+    }
+}
+
+/// Remove `name <value>` from `args` and return the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == name)?;
+    if pos + 1 >= args.len() {
+        return None;
+    }
+    let value = args.remove(pos + 1);
+    args.remove(pos);
+    Some(value)
+}
+
+/// Build the machine described by `--config <file>` (removing the option
+/// from `args`), or a plain 64 KiB machine if there is none. RAM is
+/// poisoned before any images are loaded.
+#[cfg(feature = "config")]
+fn build_machine(
+    args: &mut Vec<String>,
+    poison: Option<u8>,
+    trap_uninitialized: bool,
+) -> Result<Machine, rexta::config::ConfigError> {
+    use rexta::config::{ConfigError, MachineConfig};
+
+    let Some(path) = take_option(args, "--config") else {
+        return Ok(plain_machine(poison, trap_uninitialized));
+    };
+    let path = Path::new(&path);
+    let text = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+    let mut config = MachineConfig::from_toml(&text)?;
+    config.poison = poison.or(config.poison);
+    config.trap_uninitialized |= trap_uninitialized;
+    Machine::from_config(&config, path.parent().unwrap_or(Path::new(".")))
+}
+
+#[cfg(not(feature = "config"))]
+fn build_machine(
+    args: &mut Vec<String>,
+    poison: Option<u8>,
+    trap_uninitialized: bool,
+) -> Result<Machine, &'static str> {
+    if take_option(args, "--config").is_some() {
+        return Err("built without the `config` feature");
+    }
+    Ok(plain_machine(poison, trap_uninitialized))
+}
+
+fn plain_machine(poison: Option<u8>, trap_uninitialized: bool) -> Machine {
+    let mut machine = Machine::new(0x10000);
+    if poison.is_some() || trap_uninitialized {
+        machine.poison(poison.unwrap_or(0), trap_uninitialized);
     }
-}
\ No newline at end of file
+    machine
+}
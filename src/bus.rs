@@ -0,0 +1,593 @@
+use std::ops::Range;
+
+use crate::device::Device;
+use crate::u24::U24;
+
+/// An access the bus could not complete. Reads that fault return 0xFF and
+/// writes that fault are dropped; the fault is held until `Bus::take_fault`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusFault {
+    /// The address is outside the memory behind the bus.
+    OutOfBounds(U24),
+
+    /// A write to a read-only region set to `WriteProtect::Trap`.
+    WriteProtected(U24),
+
+    /// A read of a byte nothing has written, with poisoned RAM set to trap.
+    Uninitialized(U24),
+}
+
+/// The memory bus the CPU reads and writes through. Implementations
+/// decide what lives at each address (RAM, ROM, devices, ...).
+pub trait Bus {
+    /// Number of addressable bytes. The stack starts at the top.
+    fn size(&self) -> usize;
+
+    /// Read the byte at `addr`. Takes `&mut self` because reading a
+    /// device register may have side effects.
+    fn read8(&mut self, addr: U24) -> u8;
+
+    /// Write a byte to `addr`.
+    fn write8(&mut self, addr: U24, val: u8);
+
+    /// Read two bytes, little-endian.
+    fn read16(&mut self, addr: U24) -> u16 {
+        u16::from_le_bytes([self.read8(addr), self.read8(addr + 1)])
+    }
+
+    /// Read three bytes, little-endian.
+    fn read24(&mut self, addr: U24) -> U24 {
+        U24::from_le_bytes([self.read8(addr), self.read8(addr + 1), self.read8(addr + 2)])
+    }
+
+    /// Write two bytes, little-endian.
+    fn write16(&mut self, addr: U24, val: u16) {
+        let [low, high] = val.to_le_bytes();
+        self.write8(addr, low);
+        self.write8(addr + 1, high);
+    }
+
+    /// Write three bytes, little-endian.
+    fn write24(&mut self, addr: U24, val: U24) {
+        let [low, mid, high] = val.to_le_bytes();
+        self.write8(addr, low);
+        self.write8(addr + 1, mid);
+        self.write8(addr + 2, high);
+    }
+
+    /// Take the first access the bus could not complete since the last
+    /// call, if any. The CPU checks this after every instruction and stops
+    /// with the matching `ErrorKind`.
+    fn take_fault(&mut self) -> Option<BusFault> {
+        None
+    }
+
+    /// Advance any devices on the bus by `cycles` and return the interrupt
+    /// lines they assert, one bit per line. Called by the CPU after every
+    /// step.
+    fn tick(&mut self, _cycles: u64) -> u8 {
+        0
+    }
+
+    /// True if the bus has devices that may raise an interrupt by
+    /// themselves. A run then keeps ticking them while the CPU waits,
+    /// rather than returning `StopReason::Waiting`.
+    fn has_devices(&self) -> bool {
+        false
+    }
+
+    /// The exit code, if a device on the bus has asked for the machine to
+    /// power off.
+    fn power_off(&self) -> Option<u8> {
+        None
+    }
+
+    /// Copy out the state of the memory behind the bus, for snapshots.
+    /// Devices are not included. The default has no state to save.
+    fn dump(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Put back state taken with `dump`. Returns false if `data` does
+    /// not fit this bus.
+    fn restore(&mut self, data: &[u8]) -> bool {
+        data.is_empty()
+    }
+
+    /// Copy `bytes` into memory starting at `addr`, e.g. to load a program.
+    fn load(&mut self, addr: U24, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write8(addr + i as u32, *byte);
+        }
+    }
+}
+
+/// Flat RAM covering addresses `0..size`. This is the default bus.
+/// Accesses at or above `size` fault with `BusFault::OutOfBounds`.
+#[derive(Clone)]
+pub struct Ram {
+    data: Box<[u8]>,
+    /// Which bytes have been written, when trapping uninitialized reads
+    written: Option<Box<[bool]>>,
+    fault: Option<BusFault>,
+}
+
+impl Ram {
+    /// Create `size` bytes of zeroed RAM.
+    pub fn new(size: usize) -> Self {
+        Ram { data: vec![0; size].into_boxed_slice(), written: None, fault: None }
+    }
+
+    /// Fill RAM with `pattern` (e.g. 0xCD), so a program that uses memory
+    /// it never wrote sees a recognisable value rather than zero. With
+    /// `trap`, reading a byte that nothing has written since also faults
+    /// with `BusFault::Uninitialized`. Call this before loading anything.
+    pub fn poison(&mut self, pattern: u8, trap: bool) {
+        self.data.fill(pattern);
+        self.written = trap.then(|| vec![false; self.data.len()].into_boxed_slice());
+    }
+
+    fn mark_written(&mut self, range: Range<usize>) {
+        if let Some(written) = &mut self.written {
+            written[range].fill(true);
+        }
+    }
+}
+
+impl Bus for Ram {
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn read8(&mut self, addr: U24) -> u8 {
+        let pos = addr.value() as usize;
+        match self.data.get(pos) {
+            Some(byte) => {
+                if self.written.as_ref().is_some_and(|written| !written[pos]) {
+                    self.fault.get_or_insert(BusFault::Uninitialized(addr));
+                }
+                *byte
+            }
+            None => {
+                self.fault.get_or_insert(BusFault::OutOfBounds(addr));
+                0xFF
+            }
+        }
+    }
+
+    fn write8(&mut self, addr: U24, val: u8) {
+        let pos = addr.value() as usize;
+        match self.data.get_mut(pos) {
+            Some(byte) => {
+                *byte = val;
+                self.mark_written(pos..pos + 1);
+            }
+            None => {
+                self.fault.get_or_insert(BusFault::OutOfBounds(addr));
+            }
+        }
+    }
+
+    fn take_fault(&mut self) -> Option<BusFault> {
+        self.fault.take()
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    fn restore(&mut self, data: &[u8]) -> bool {
+        if data.len() != self.data.len() {
+            return false;
+        }
+        self.data.copy_from_slice(data);
+        // Restored memory is all known, whatever the snapshot held
+        self.mark_written(0..data.len());
+        true
+    }
+
+    /// Bytes past the end of RAM are dropped, and fault like a write
+    /// there would.
+    fn load(&mut self, addr: U24, bytes: &[u8]) {
+        let pos = (addr.value() as usize).min(self.data.len());
+        let len = bytes.len().min(self.data.len() - pos);
+        self.data[pos..pos + len].copy_from_slice(&bytes[..len]);
+        self.mark_written(pos..pos + len);
+        if len < bytes.len() {
+            self.fault.get_or_insert(BusFault::OutOfBounds(addr + len as u32));
+        }
+    }
+}
+
+/// What happens to a write into a read-only region of a `MemoryMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteProtect {
+    /// Drop the write and stop the CPU with `ErrorKind::WriteProtected`.
+    Trap,
+
+    /// Drop the write silently, like writing to a real ROM.
+    Ignore,
+}
+
+/// A device mapped into a `MemoryMap`.
+struct Mapping {
+    range: Range<u32>,
+    device: Box<dyn Device>,
+    /// The interrupt controller source its requests go to, or `None` if
+    /// they go straight to the CPU
+    source: Option<u8>,
+}
+
+/// RAM with devices mapped over parts of the address space. Addresses
+/// inside a device's range go to the device; other addresses below the
+/// RAM size go to RAM, and any other address faults like an access past
+/// the end of `Ram`. Ranges can also be made read-only with `protect`.
+pub struct MemoryMap {
+    ram: Ram,
+    devices: Vec<Mapping>,
+    read_only: Vec<(Range<u32>, WriteProtect)>,
+    fault: Option<BusFault>,
+}
+
+impl MemoryMap {
+    /// Create a map with `ram_size` bytes of RAM and no devices.
+    pub fn new(ram_size: usize) -> Self {
+        MemoryMap {
+            ram: Ram::new(ram_size),
+            devices: Vec::new(),
+            read_only: Vec::new(),
+            fault: None,
+        }
+    }
+
+    /// Poison RAM; see `Ram::poison`. Devices are not affected.
+    pub fn poison(&mut self, pattern: u8, trap: bool) {
+        self.ram.poison(pattern, trap);
+    }
+
+    /// Make `range` read-only. `load` still writes to it, so a ROM image
+    /// can be loaded after the range is protected.
+    pub fn protect(&mut self, range: Range<u32>, mode: WriteProtect) {
+        self.read_only.push((range, mode));
+    }
+
+    /// Route accesses to `range` to `device`.
+    ///
+    /// Panics if `range` overlaps a device that is already mapped.
+    pub fn map_device(&mut self, range: Range<u32>, device: impl Device + 'static) {
+        self.map(range, Box::new(device), None);
+    }
+
+    /// Map `device` as `map_device` does, but send its interrupt requests
+    /// to source `source` (0-7) of the interrupt controllers on the bus
+    /// (see `Device::request`) instead of to the CPU.
+    pub fn map_device_to_source(&mut self, range: Range<u32>, device: impl Device + 'static, source: u8) {
+        assert!(source < 8, "interrupt source {} is not 0-7", source);
+        self.map(range, Box::new(device), Some(source));
+    }
+
+    fn map(&mut self, range: Range<u32>, device: Box<dyn Device>, source: Option<u8>) {
+        assert!(
+            self.devices.iter().all(|m| range.end <= m.range.start || m.range.end <= range.start),
+            "device range {:#08x}..{:#08x} overlaps an existing mapping",
+            range.start,
+            range.end
+        );
+        self.devices.push(Mapping { range, device, source });
+    }
+
+    fn device_at(&mut self, addr: U24) -> Option<(&mut Box<dyn Device>, u32)> {
+        let addr = addr.value();
+        self.devices
+            .iter_mut()
+            .find(|m| m.range.contains(&addr))
+            .map(|m| (&mut m.device, addr - m.range.start))
+    }
+
+    fn write_unprotected(&mut self, addr: U24, val: u8) {
+        match self.device_at(addr) {
+            Some((device, offset)) => device.write(offset, val),
+            None => self.ram.write8(addr, val),
+        }
+    }
+}
+
+impl Bus for MemoryMap {
+    fn size(&self) -> usize {
+        self.ram.size()
+    }
+
+    fn read8(&mut self, addr: U24) -> u8 {
+        match self.device_at(addr) {
+            Some((device, offset)) => device.read(offset),
+            None => self.ram.read8(addr),
+        }
+    }
+
+    fn write8(&mut self, addr: U24, val: u8) {
+        let protection = self
+            .read_only
+            .iter()
+            .find(|(range, _)| range.contains(&addr.value()))
+            .map(|(_, mode)| *mode);
+        match protection {
+            Some(WriteProtect::Trap) => {
+                self.fault.get_or_insert(BusFault::WriteProtected(addr));
+            }
+            Some(WriteProtect::Ignore) => {}
+            None => self.write_unprotected(addr, val),
+        }
+    }
+
+    fn take_fault(&mut self) -> Option<BusFault> {
+        self.fault.take().or_else(|| self.ram.take_fault())
+    }
+
+    fn tick(&mut self, cycles: u64) -> u8 {
+        let mut sources = 0;
+        for m in &mut self.devices {
+            m.device.tick(cycles);
+            if let (Some(source), Some(_)) = (m.source, m.device.irq()) {
+                sources |= 1 << source;
+            }
+        }
+        // Controllers see this tick's requests before the CPU sees them
+        let mut lines = 0;
+        for m in &mut self.devices {
+            if sources != 0 {
+                m.device.request(sources);
+            }
+            if let (None, Some(line)) = (m.source, m.device.irq()) {
+                lines |= 1 << (line & 7);
+            }
+        }
+        lines
+    }
+
+    fn has_devices(&self) -> bool {
+        !self.devices.is_empty()
+    }
+
+    fn power_off(&self) -> Option<u8> {
+        self.devices.iter().find_map(|m| m.device.power_off())
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.ram.dump()
+    }
+
+    fn restore(&mut self, data: &[u8]) -> bool {
+        self.ram.restore(data)
+    }
+
+    fn load(&mut self, addr: U24, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write_unprotected(addr + i as u32, *byte);
+        }
+    }
+}
+
+/// One bank of a `BankedMemory`.
+#[derive(Clone)]
+struct Bank {
+    data: Box<[u8]>,
+    read_only: bool,
+}
+
+/// RAM with a bank-switched window, for cartridge-style systems. Accesses
+/// inside the window go to the selected bank; everything else goes to
+/// RAM. The bank is selected by writing its index to the select register,
+/// a single memory-mapped byte (reading it returns the current bank), or
+/// from the host with `select_bank`.
+#[derive(Clone)]
+pub struct BankedMemory {
+    ram: Ram,
+    window: Range<u32>,
+    select: U24,
+    banks: Vec<Bank>,
+    current: usize,
+}
+
+impl BankedMemory {
+    /// Create `ram_size` bytes of RAM with a bank window over `window` and
+    /// the select register at `select`. There are no banks until some are
+    /// added; until then the window falls through to RAM.
+    pub fn new(ram_size: usize, window: Range<u32>, select: U24) -> Self {
+        BankedMemory { ram: Ram::new(ram_size), window, select, banks: Vec::new(), current: 0 }
+    }
+
+    /// Add a zeroed RAM bank the size of the window and return its index.
+    pub fn add_ram_bank(&mut self) -> usize {
+        let size = self.window.len();
+        self.banks.push(Bank { data: vec![0; size].into_boxed_slice(), read_only: false });
+        self.banks.len() - 1
+    }
+
+    /// Add a ROM bank holding `bytes` (padded with zeros, or truncated, to
+    /// the size of the window) and return its index. Writes to it are ignored.
+    pub fn add_rom_bank(&mut self, bytes: &[u8]) -> usize {
+        let mut data = vec![0; self.window.len()];
+        let len = bytes.len().min(data.len());
+        data[..len].copy_from_slice(&bytes[..len]);
+        self.banks.push(Bank { data: data.into_boxed_slice(), read_only: true });
+        self.banks.len() - 1
+    }
+
+    /// Map bank `index` into the window. Out-of-range indexes are ignored.
+    pub fn select_bank(&mut self, index: usize) {
+        if index < self.banks.len() {
+            self.current = index;
+        }
+    }
+
+    /// Index of the bank currently mapped into the window.
+    pub fn current_bank(&self) -> usize {
+        self.current
+    }
+
+    fn bank_offset(&self, addr: U24) -> Option<usize> {
+        if self.banks.is_empty() || !self.window.contains(&addr.value()) {
+            return None;
+        }
+        Some((addr.value() - self.window.start) as usize)
+    }
+}
+
+impl Bus for BankedMemory {
+    fn size(&self) -> usize {
+        self.ram.size()
+    }
+
+    fn read8(&mut self, addr: U24) -> u8 {
+        if addr == self.select {
+            self.current as u8
+        } else if let Some(offset) = self.bank_offset(addr) {
+            self.banks[self.current].data[offset]
+        } else {
+            self.ram.read8(addr)
+        }
+    }
+
+    fn write8(&mut self, addr: U24, val: u8) {
+        if addr == self.select {
+            self.select_bank(val as usize);
+        } else if let Some(offset) = self.bank_offset(addr) {
+            let bank = &mut self.banks[self.current];
+            if !bank.read_only {
+                bank.data[offset] = val;
+            }
+        } else {
+            self.ram.write8(addr, val);
+        }
+    }
+
+    fn take_fault(&mut self) -> Option<BusFault> {
+        self.ram.take_fault()
+    }
+
+    /// RAM, then each bank in order, then the selected bank index.
+    fn dump(&self) -> Vec<u8> {
+        let mut data = self.ram.dump();
+        for bank in &self.banks {
+            data.extend_from_slice(&bank.data);
+        }
+        data.push(self.current as u8);
+        data
+    }
+
+    /// Fails if the saved bank index is not a bank this memory has.
+    fn restore(&mut self, data: &[u8]) -> bool {
+        let ram_size = self.ram.size();
+        let window = self.window.len();
+        if data.len() != ram_size + self.banks.len() * window + 1 {
+            return false;
+        }
+        // With no banks the index is always 0
+        let current = data[data.len() - 1] as usize;
+        if current >= self.banks.len().max(1) {
+            return false;
+        }
+        self.ram.restore(&data[..ram_size]);
+        for (bank, saved) in self.banks.iter_mut().zip(data[ram_size..].chunks(window)) {
+            bank.data.copy_from_slice(saved);
+        }
+        self.current = current;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ram_load_past_the_end_is_clipped_and_faults() {
+        let mut ram = Ram::new(16);
+        ram.load(U24::new(12), &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(ram.dump()[12..], [1, 2, 3, 4]);
+        assert_eq!(ram.take_fault(), Some(BusFault::OutOfBounds(U24::new(16))));
+
+        ram.load(U24::new(0x100), &[1]);
+        assert_eq!(ram.take_fault(), Some(BusFault::OutOfBounds(U24::new(0x100))));
+        ram.load(U24::new(0), &[9; 16]);
+        assert_eq!(ram.take_fault(), None);
+    }
+
+    /// 256 bytes of RAM with two banks at 0x80-0x8F, selected at 0xFF:
+    /// bank 0 is RAM and bank 1 a ROM starting 0xAA, 0xBB.
+    fn banked() -> BankedMemory {
+        let mut mem = BankedMemory::new(0x100, 0x80..0x90, U24::new(0xFF));
+        mem.add_ram_bank();
+        mem.add_rom_bank(&[0xAA, 0xBB]);
+        mem
+    }
+
+    #[test]
+    fn select_register_switches_the_window() {
+        let mut mem = banked();
+        mem.write8(U24::new(0x80), 0x11);
+        assert_eq!(mem.read8(U24::new(0x80)), 0x11);
+
+        mem.write8(U24::new(0xFF), 1);
+        assert_eq!(mem.read8(U24::new(0xFF)), 1);
+        assert_eq!(mem.read8(U24::new(0x80)), 0xAA);
+        assert_eq!(mem.read8(U24::new(0x81)), 0xBB);
+        mem.write8(U24::new(0x80), 0x22);
+        assert_eq!(mem.read8(U24::new(0x80)), 0xAA);
+
+        mem.write8(U24::new(0xFF), 7);
+        assert_eq!(mem.current_bank(), 1);
+        mem.write8(U24::new(0xFF), 0);
+        assert_eq!(mem.read8(U24::new(0x80)), 0x11);
+        // Outside the window is plain RAM
+        mem.write8(U24::new(0x90), 0x33);
+        assert_eq!(mem.read8(U24::new(0x90)), 0x33);
+    }
+
+    #[test]
+    fn program_switches_banks_mid_run() {
+        let mut mem = BankedMemory::new(0x10000, 0x8000..0x9000, U24::new(0xFF00));
+        mem.add_ram_bank();
+        mem.add_rom_bank(&[0x5A]);
+        mem.write8(U24::new(0x8000), 0x42);
+        let mut cpu = crate::cpu::Cpu::with_bus(mem);
+        cpu.bus.load(
+            U24::new(0),
+            &[
+                0x03, 0x08, 0x40, 0x00, 0x80, 0x00, // LOADI.3 R4, 0x8000
+                0x69, 0x02, 0x24, // LOAD.1 R2, (R4)
+                0x01, 0x04, 0x00, 0x01, // LOADI.1 R0, 1
+                0x09, 0x08, 0x00, 0x00, 0xFF, 0x00, // STORE.1 R0, 0xFF00
+                0x69, 0x02, 0x34, // LOAD.1 R3, (R4)
+                0x04, 0x00, // HLT
+            ],
+        );
+        assert_eq!(cpu.run(), crate::cpu::StopReason::Halted);
+        assert_eq!(cpu.reg_read(2), 0x42);
+        assert_eq!(cpu.reg_read(3), 0x5A);
+        assert_eq!(cpu.bus.current_bank(), 1);
+    }
+
+    #[test]
+    fn banked_dump_and_restore_round_trip() {
+        let mut mem = banked();
+        mem.write8(U24::new(0x10), 0x01);
+        mem.write8(U24::new(0x85), 0x02);
+        mem.select_bank(1);
+        let saved = mem.dump();
+
+        let mut other = banked();
+        assert!(other.restore(&saved));
+        assert_eq!(other.current_bank(), 1);
+        assert_eq!(other.read8(U24::new(0x10)), 0x01);
+        assert_eq!(other.read8(U24::new(0x80)), 0xAA);
+        other.select_bank(0);
+        assert_eq!(other.read8(U24::new(0x85)), 0x02);
+        assert_eq!(other.dump()[..saved.len() - 1], saved[..saved.len() - 1]);
+
+        let mut corrupt = saved.clone();
+        *corrupt.last_mut().unwrap() = 2;
+        assert!(!other.restore(&corrupt));
+        assert!(!other.restore(&saved[1..]));
+    }
+}
@@ -0,0 +1,484 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::bus::Bus;
+use crate::cpu::ResetVector;
+use crate::device::Device;
+use crate::executable::{Executable, ExecutableError};
+use crate::interrupt::InterruptController;
+use crate::machine::Machine;
+use crate::net::{NetDevice, UdpLink};
+use crate::power::PowerControl;
+use crate::rng::Rng;
+use crate::semihost::Semihost;
+use crate::timer::Timer;
+use crate::u24::U24;
+use crate::uart::{StdioPort, TcpPort, Uart};
+
+/// Declarative description of a `Machine`, usually read from TOML:
+///
+/// ```toml
+/// ram_size = 0x10000
+/// vector_base = 0x0300
+///
+/// [[rom]]
+/// path = "boot.bin"
+/// address = 0xC000
+/// boot = true
+///
+/// [[image]]
+/// path = "program.b"
+/// address = 0x0000
+///
+/// [[device]]
+/// kind = "interrupt-controller"
+/// base = 0xFF00
+/// line = 0
+/// ```
+///
+/// Image paths are relative to the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MachineConfig {
+    pub ram_size: usize,
+
+    /// Base address of the interrupt vector table
+    #[serde(default)]
+    pub vector_base: u32,
+
+    /// Address the CPU starts at after reset; by default the image marked
+    /// `boot`, or 0
+    #[serde(default)]
+    pub reset_vector: Option<u32>,
+
+    /// Fill RAM with this byte instead of zeros
+    #[serde(default)]
+    pub poison: Option<u8>,
+
+    /// Stop when the guest reads RAM nothing has written
+    #[serde(default)]
+    pub trap_uninitialized: bool,
+
+    /// Images loaded read-only
+    #[serde(default, rename = "rom")]
+    pub roms: Vec<ImageConfig>,
+
+    /// Images loaded into RAM
+    #[serde(default, rename = "image")]
+    pub images: Vec<ImageConfig>,
+
+    #[serde(default, rename = "device")]
+    pub devices: Vec<DeviceConfig>,
+}
+
+/// A binary file and the address to load it at. An executable (`.rxe`)
+/// says where it loads, so it has no address.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub address: Option<u32>,
+
+    /// Start execution at this image, or at its entry point if it is an
+    /// executable: that becomes the reset vector unless `reset_vector` is
+    /// given
+    #[serde(default)]
+    pub boot: bool,
+}
+
+/// A device to map, selected by `kind`. A device that interrupts does so
+/// on CPU line `line`, or with `source` on that source of the
+/// `interrupt-controller`, which then interrupts on its own line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum DeviceConfig {
+    /// An `InterruptController` with its registers at `base`
+    InterruptController { base: u32, line: u8 },
+
+    /// A `Timer` with its registers at `base`
+    Timer {
+        base: u32,
+        #[serde(default)]
+        line: u8,
+        #[serde(default)]
+        source: Option<u8>,
+    },
+
+    /// An `Rng` with its registers at `base`, seeded with `seed`
+    Rng {
+        base: u32,
+        #[serde(default)]
+        seed: u32,
+    },
+
+    /// A `Uart` with its registers at `base`, on stdin/stdout or, if `port`
+    /// is given, on that TCP port
+    Uart {
+        base: u32,
+        #[serde(default)]
+        line: u8,
+        #[serde(default)]
+        source: Option<u8>,
+        #[serde(default)]
+        port: Option<u16>,
+    },
+
+    /// A `NetDevice` with its registers at `base`, exchanging UDP datagrams
+    /// between the local address `bind` and `peer`
+    Net {
+        base: u32,
+        #[serde(default)]
+        line: u8,
+        #[serde(default)]
+        source: Option<u8>,
+        bind: String,
+        peer: String,
+    },
+
+    /// A `PowerControl` register at `base`
+    Power { base: u32 },
+
+    /// A `Semihost` mailbox at `base` reporting `args` as the program's
+    /// arguments, with file access under `sandbox` (relative to the config
+    /// file) if given
+    Semihost {
+        base: u32,
+        #[serde(default)]
+        sandbox: Option<PathBuf>,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl DeviceConfig {
+    /// The addresses the device's registers cover. Run past the 24-bit
+    /// address space until `check_devices` has passed.
+    fn range(&self) -> Range<u32> {
+        let (base, size) = match *self {
+            DeviceConfig::InterruptController { base, .. } => (base, InterruptController::SIZE),
+            DeviceConfig::Timer { base, .. } => (base, Timer::SIZE),
+            DeviceConfig::Rng { base, .. } => (base, Rng::SIZE),
+            DeviceConfig::Uart { base, .. } => (base, Uart::SIZE),
+            DeviceConfig::Net { base, .. } => (base, NetDevice::SIZE),
+            DeviceConfig::Power { base } => (base, PowerControl::SIZE),
+            DeviceConfig::Semihost { base, .. } => (base, Semihost::SIZE),
+        };
+        base..base.saturating_add(size)
+    }
+
+    /// The CPU line the device interrupts on and the controller source it
+    /// raises instead, if it can interrupt.
+    fn interrupt(&self) -> Option<(u8, Option<u8>)> {
+        match *self {
+            DeviceConfig::InterruptController { line, .. } => Some((line, None)),
+            DeviceConfig::Timer { line, source, .. }
+            | DeviceConfig::Uart { line, source, .. }
+            | DeviceConfig::Net { line, source, .. } => Some((line, source)),
+            _ => None,
+        }
+    }
+}
+
+/// Why a machine could not be built from a config.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A file could not be read.
+    Io(PathBuf, io::Error),
+
+    /// The config is not valid TOML or does not describe a machine.
+    Parse(toml::de::Error),
+
+    /// A device could not listen on its TCP port.
+    Bind(u16, io::Error),
+
+    /// A network device could not open its UDP socket.
+    Socket(String, io::Error),
+
+    /// An image file starts like an executable but is not a valid one.
+    Executable(PathBuf, ExecutableError),
+
+    /// A raw image has no address, or an executable has one.
+    Address(PathBuf),
+
+    /// An image runs past the end of RAM.
+    TooLarge(PathBuf),
+
+    /// A setting is out of range, e.g. a device that runs past the 24-bit
+    /// address space.
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Parse(err) => write!(f, "invalid machine config: {}", err),
+            ConfigError::Bind(port, err) => write!(f, "port {}: {}", port, err),
+            ConfigError::Socket(addr, err) => write!(f, "{}: {}", addr, err),
+            ConfigError::Executable(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Address(path) => write!(
+                f,
+                "{}: a raw image needs an address; an executable loads where it says",
+                path.display()
+            ),
+            ConfigError::TooLarge(path) => write!(f, "{}: image does not fit in memory", path.display()),
+            ConfigError::Invalid(msg) => write!(f, "invalid machine config: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl MachineConfig {
+    /// Parse a config from TOML text.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(ConfigError::Parse)
+    }
+}
+
+impl Machine {
+    /// Build a machine from `config`, reading image files relative to
+    /// `base_dir`, and reset it so PC is at the reset vector.
+    pub fn from_config(config: &MachineConfig, base_dir: &Path) -> Result<Self, ConfigError> {
+        if !(2..=0x1000000).contains(&config.ram_size) {
+            return Err(ConfigError::Invalid(format!(
+                "ram_size 0x{:X} is not between 2 bytes and 16 MiB",
+                config.ram_size
+            )));
+        }
+        let mut machine = Machine::new(config.ram_size);
+        machine.cpu.vector_base = U24::new(config.vector_base);
+        if config.poison.is_some() || config.trap_uninitialized {
+            machine.poison(config.poison.unwrap_or(0), config.trap_uninitialized);
+        }
+
+        let image_starts = config
+            .images
+            .iter()
+            .map(|image| load_image(&mut machine, base_dir, image, false))
+            .collect::<Result<Vec<_>, _>>()?;
+        let rom_starts = config
+            .roms
+            .iter()
+            .map(|rom| load_image(&mut machine, base_dir, rom, true))
+            .collect::<Result<Vec<_>, _>>()?;
+        let boot = config
+            .roms
+            .iter()
+            .zip(rom_starts)
+            .chain(config.images.iter().zip(image_starts))
+            .find(|(image, _)| image.boot)
+            .map(|(_, start)| start);
+        let reset_vector = config.reset_vector.or(boot).unwrap_or(0);
+        machine.cpu.reset_vector = ResetVector::Address(U24::new(reset_vector));
+        check_devices(&config.devices)?;
+        for device in &config.devices {
+            let range = device.range();
+            let source = device.interrupt().and_then(|(_, source)| source);
+            match *device {
+                DeviceConfig::InterruptController { line, .. } => {
+                    machine.map_device(range, InterruptController::new(line));
+                }
+                DeviceConfig::Timer { line, .. } => {
+                    map_device(&mut machine, range, Timer::new(line), source);
+                }
+                DeviceConfig::Rng { seed, .. } => {
+                    machine.map_device(range, Rng::new(seed));
+                }
+                DeviceConfig::Uart { line, port: None, .. } => {
+                    map_device(&mut machine, range, Uart::new(StdioPort::new(), line), source);
+                }
+                DeviceConfig::Uart { line, port: Some(port), .. } => {
+                    let tcp = TcpPort::bind(("0.0.0.0", port)).map_err(|err| ConfigError::Bind(port, err))?;
+                    map_device(&mut machine, range, Uart::new(tcp, line), source);
+                }
+                DeviceConfig::Net { line, ref bind, ref peer, .. } => {
+                    let udp = UdpLink::bind(bind.as_str(), peer.as_str()).map_err(|err| ConfigError::Socket(bind.clone(), err))?;
+                    map_device(&mut machine, range, NetDevice::new(udp, line), source);
+                }
+                DeviceConfig::Power { .. } => {
+                    machine.map_device(range, PowerControl::new());
+                }
+                DeviceConfig::Semihost { ref sandbox, ref args, .. } => {
+                    let sandbox = sandbox.as_ref().map(|dir| base_dir.join(dir));
+                    machine.map_device(range, Semihost::new(args.clone(), sandbox));
+                }
+            }
+        }
+
+        machine.reset();
+        Ok(machine)
+    }
+
+    /// Read a TOML config file and build the machine it describes.
+    pub fn from_config_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        let config = MachineConfig::from_toml(&text)?;
+        Machine::from_config(&config, path.parent().unwrap_or(Path::new(".")))
+    }
+}
+
+/// Map `device`, sending its interrupts to the controller's `source` if
+/// it has one, or else straight to the CPU.
+fn map_device(machine: &mut Machine, range: Range<u32>, device: impl Device + 'static, source: Option<u8>) {
+    match source {
+        Some(source) => machine.map_device_to_source(range, device, source),
+        None => machine.map_device(range, device),
+    }
+}
+
+/// Check that every device is inside the 24-bit address space and clear
+/// of the others, and interrupts on a line and source that exist.
+fn check_devices(devices: &[DeviceConfig]) -> Result<(), ConfigError> {
+    let invalid = |msg: String| Err(ConfigError::Invalid(msg));
+    let has_controller = devices
+        .iter()
+        .any(|device| matches!(device, DeviceConfig::InterruptController { .. }));
+    let mut mapped: Vec<Range<u32>> = Vec::new();
+    for device in devices {
+        let range = device.range();
+        if range.end > 0x1000000 {
+            return invalid(format!("device at 0x{:X} runs past the end of the address space", range.start));
+        }
+        if let Some(other) = mapped.iter().find(|other| range.start < other.end && other.start < range.end) {
+            return invalid(format!("device at 0x{:X} overlaps the one at 0x{:X}", range.start, other.start));
+        }
+        mapped.push(range);
+
+        match device.interrupt() {
+            Some((line, _)) if line > 7 => return invalid(format!("interrupt line {} is not 0-7", line)),
+            Some((_, Some(source))) if source > 7 => {
+                return invalid(format!("interrupt source {} is not 0-7", source));
+            }
+            Some((_, Some(source))) if !has_controller => {
+                return invalid(format!("source {} needs an interrupt-controller", source));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Load `image` into RAM, or read-only if `rom`, and return where it
+/// starts: its address, or its entry point if it is an executable.
+fn load_image(machine: &mut Machine, base_dir: &Path, image: &ImageConfig, rom: bool) -> Result<u32, ConfigError> {
+    let bytes = read_image(base_dir, &image.path)?;
+    let mut load = |addr: U24, bytes: &[u8]| {
+        if rom {
+            machine.load_rom(addr, bytes)
+        } else {
+            machine.load(addr, bytes)
+        }
+    };
+    let start = if !Executable::is_executable(&bytes) {
+        let address = image.address.ok_or_else(|| ConfigError::Address(base_dir.join(&image.path)))?;
+        load(U24::new(address), &bytes);
+        address
+    } else {
+        if image.address.is_some() {
+            return Err(ConfigError::Address(base_dir.join(&image.path)));
+        }
+        let exe = Executable::from_bytes(&bytes).map_err(|err| ConfigError::Executable(base_dir.join(&image.path), err))?;
+        for section in &exe.sections {
+            load(section.address, &section.bytes);
+        }
+        exe.entry.value()
+    };
+    // Whatever doesn't fit in RAM is dropped, with a fault left behind
+    if machine.cpu.bus.take_fault().is_some() {
+        return Err(ConfigError::TooLarge(base_dir.join(&image.path)));
+    }
+    Ok(start)
+}
+
+fn read_image(base_dir: &Path, path: &Path) -> Result<Vec<u8>, ConfigError> {
+    let path = base_dir.join(path);
+    fs::read(&path).map_err(|err| ConfigError::Io(path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(toml: &str) -> Result<Machine, ConfigError> {
+        Machine::from_config(&MachineConfig::from_toml(toml).unwrap(), Path::new("."))
+    }
+
+    #[test]
+    fn overlapping_devices_are_rejected() {
+        let err = build(
+            r#"
+            ram_size = 0x10000
+
+            [[device]]
+            kind = "interrupt-controller"
+            base = 0xFF00
+            line = 0
+
+            [[device]]
+            kind = "timer"
+            base = 0xFF0A
+            line = 1
+            "#,
+        )
+        .err();
+        assert!(matches!(err, Some(ConfigError::Invalid(msg)) if msg.contains("overlaps")));
+
+        assert!(build(
+            r#"
+            ram_size = 0x10000
+
+            [[device]]
+            kind = "interrupt-controller"
+            base = 0xFF00
+            line = 0
+
+            [[device]]
+            kind = "timer"
+            base = 0xFF0B
+            line = 1
+            "#,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn lines_and_sources_past_7_are_rejected() {
+        let err = build(
+            r#"
+            ram_size = 0x10000
+
+            [[device]]
+            kind = "timer"
+            base = 0xFF00
+            line = 9
+            "#,
+        )
+        .err();
+        assert!(matches!(err, Some(ConfigError::Invalid(msg)) if msg.contains("line 9")));
+
+        let err = build(
+            r#"
+            ram_size = 0x10000
+
+            [[device]]
+            kind = "interrupt-controller"
+            base = 0xFF00
+            line = 0
+
+            [[device]]
+            kind = "timer"
+            base = 0xFF10
+            source = 8
+            "#,
+        )
+        .err();
+        assert!(matches!(err, Some(ConfigError::Invalid(msg)) if msg.contains("source 8")));
+    }
+}
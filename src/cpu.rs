@@ -1,25 +1,40 @@
 
+use std::cell::Cell;
+use std::fmt;
+use std::ops::Range;
+
+use crate::bus::{Bus, BusFault, Ram};
 use crate::u24::U24;
+use crate::trace::{TraceBuffer, TraceEntry};
+use crate::watch::{Access, WatchHit, Watchpoint};
 use crate::op::Op;
 use crate::op::OpCode;
+use crate::perf::PerfCounters;
 
-/// Represents the current state of a CPU.
-pub struct Cpu {
+/// Represents the current state of a CPU, attached to a memory bus.
+pub struct Cpu<B: Bus = Ram> {
     /// Program Counter
     pub pc: U24,
 
-    /// Addressable memory (up to 16 MiB) - default to 64KiB
-    pub mem: [u8; 65536],
+    /// Memory bus (up to 16 MiB) - default to 64KiB of RAM
+    pub bus: B,
 
     /// Registers
     pub regs: [u8; 9],
 
+    /// Floating-point registers (`F0`-`F7`)
+    #[cfg(feature = "fpu")]
+    pub fregs: [f32; 8],
+
     /// Flags
     pub flags: u8,
 
     /// Stack Pointer
     pub sp: U24,
 
+    /// Frame Pointer (managed by ENTER/LEAVE)
+    pub fp: U24,
+
     /// True if the CPU is currently executing instructions.
     pub is_running: bool,
 
@@ -28,98 +43,554 @@ pub struct Cpu {
 
     /// Instruction Counter
     pub ic: U24,
+
+    /// Base address of the interrupt vector table
+    pub vector_base: U24,
+
+    /// Vector of the interrupt line currently raised, if any
+    pub irq: Option<u8>,
+
+    /// Pending hardware interrupt lines, one bit per line (see `raise_irq`)
+    pub irq_lines: u8,
+
+    /// Interrupt lines asserted by devices on the bus as of the last tick
+    pub device_lines: u8,
+
+    /// True if the CPU has stopped fetching until an interrupt (WAIT).
+    pub is_waiting: bool,
+
+    /// ISA extensions reported to guest code by CPUID (`FEATURE_*` bits)
+    pub features: u16,
+
+    /// Handler address for unknown opcodes. If `None` (the default) an
+    /// unknown opcode is handled according to `decode_mode`.
+    pub illegal_trap: Option<U24>,
+
+    /// What to do with unknown opcodes when there is no `illegal_trap`
+    pub decode_mode: DecodeMode,
+
+    /// Number of unknown opcodes skipped in `DecodeMode::Permissive`
+    pub skipped_ops: u64,
+
+    /// Where `reset` takes the initial PC from
+    pub reset_vector: ResetVector,
+
+    /// Called before each instruction is fetched (see `set_pre_hook`)
+    pre_hook: Option<PreHook<B>>,
+
+    /// Called after each instruction executes (see `set_post_hook`)
+    post_hook: Option<PostHook<B>>,
+
+    /// Watched address ranges (see `add_watchpoint`)
+    pub(crate) watchpoints: Vec<Watchpoint>,
+
+    /// The access that stopped execution at a watchpoint. Cleared when
+    /// the next instruction starts.
+    pub watch_hit: Option<WatchHit>,
+
+    /// Address of the instruction being executed
+    pub(crate) op_pc: U24,
+
+    /// The first register named by the current instruction that runs past
+    /// the end of `regs`. Reads of it return 0 and writes are dropped.
+    bad_reg: Cell<Option<u8>>,
+
+    /// If set, records the most recently executed instructions, including
+    /// one that failed, so they can be dumped after an error.
+    pub trace: Option<TraceBuffer>,
+
+    /// Addresses where `run`, `run_for` and `run_until` stop before
+    /// executing the instruction. The instruction a run starts at is
+    /// never treated as a breakpoint, so running again resumes.
+    pub breakpoints: Vec<U24>,
+
+    /// Instruction, cycle, memory and branch counts
+    pub perf: PerfCounters,
+
+    /// If set, guest code can read `perf` as registers at this address
+    /// (see `PerfCounters`).
+    pub perf_base: Option<U24>,
+}
+
+/// Callback run before each instruction is fetched, e.g. by a tracer.
+pub type PreHook<B> = Box<dyn FnMut(&mut Cpu<B>)>;
+
+/// Callback run after each instruction executes, with the decoded op.
+pub type PostHook<B> = Box<dyn FnMut(&mut Cpu<B>, &Op)>;
+
+/// Where the CPU starts executing after `Cpu::reset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetVector {
+    /// Start at this address.
+    Address(U24),
+
+    /// Start at the 24-bit little-endian address stored in memory here.
+    Indirect(U24),
+}
+
+/// What a call to `Cpu::step` did.
+#[derive(Debug)]
+pub enum StepResult {
+    /// The instruction was decoded and executed.
+    Executed(Op),
+
+    /// The CPU is in WAIT with no interrupt raised; nothing was done.
+    Waiting,
+
+    /// An unknown opcode was sent to the illegal-instruction trap.
+    Trapped(u16),
+
+    /// An unknown opcode was skipped in `DecodeMode::Permissive`.
+    Skipped(u16),
+
+    /// The instruction was executed and made an access matching a
+    /// watchpoint.
+    Watchpoint(Op, WatchHit),
+}
+
+/// Why `Cpu::run`, `Cpu::run_for` or `Cpu::run_until` stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    /// The instruction budget was used up.
+    Budget,
+
+    /// A HLT instruction was executed.
+    Halted,
+
+    /// `is_running` was cleared by something other than HLT, e.g. a hook.
+    Stopped,
+
+    /// PC reached the breakpoint at this address.
+    Breakpoint(U24),
+
+    /// The address or condition given to `run_until` was reached.
+    Reached,
+
+    /// The CPU is in WAIT with no interrupt raised.
+    Waiting,
+
+    /// A device powered the machine off with this exit code.
+    PowerOff(u8),
+
+    /// An instruction made an access matching a watchpoint.
+    Watchpoint(WatchHit),
+
+    /// Execution failed.
+    Error(CpuError),
+}
+
+/// Result of `Cpu::run_for` and `Cpu::run_until`: how many instructions ran
+/// and why execution stopped.
+#[derive(Debug)]
+pub struct RunOutcome {
+    pub ticks: u64,
+    pub reason: StopReason,
+}
+
+/// How the CPU treats opcodes it cannot decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodeMode {
+    /// Stop the run with `ErrorKind::InvalidOpCode`.
+    #[default]
+    Strict,
+
+    /// Skip the opcode and the operand bytes its encoding implies, as if
+    /// it were a NOP, and count it in `Cpu::skipped_ops`.
+    Permissive,
 }
 
-pub enum CpuError {
+/// What went wrong when execution failed. See `CpuError` for where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
     InvalidOpCode(u16),
     InvalidInstruction,
+    DivideByZero,
+    /// An arithmetic result that has no value in the destination, such
+    /// as converting NaN to an integer.
+    ArithmeticFault,
+    /// An instruction named a register that runs past the end of the
+    /// register file, e.g. a 3-byte access to R7.
+    InvalidRegister(u8),
+    /// The opcode decoded but the CPU has no implementation for it yet.
+    UnimplementedOpcode(u16),
+    /// An instruction wrote to a read-only region set to
+    /// `WriteProtect::Trap`; the write was dropped.
+    WriteProtected(U24),
+    /// An access outside memory. Reads outside memory return 0xFF and
+    /// writes are dropped.
+    MemoryFault { addr: U24 },
+    /// A read of a byte nothing had written, with RAM poisoned to trap
+    /// (see `Machine::poison`).
+    UninitializedRead(U24),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::InvalidOpCode(code) => write!(f, "invalid opcode 0x{:04X}", code),
+            ErrorKind::InvalidInstruction => write!(f, "invalid instruction"),
+            ErrorKind::DivideByZero => write!(f, "divide by zero"),
+            ErrorKind::ArithmeticFault => write!(f, "arithmetic fault"),
+            ErrorKind::InvalidRegister(reg) => write!(f, "invalid register R{}", reg),
+            ErrorKind::UnimplementedOpcode(code) => write!(f, "unimplemented opcode 0x{:04X}", code),
+            ErrorKind::WriteProtected(addr) => write!(f, "write to read-only address 0x{:06X}", addr),
+            ErrorKind::MemoryFault { addr } => write!(f, "memory fault at 0x{:06X}", addr),
+            ErrorKind::UninitializedRead(addr) => write!(f, "read of uninitialized memory at 0x{:06X}", addr),
+        }
+    }
+}
+
+/// An execution failure and the CPU state at the point it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuError {
+    pub kind: ErrorKind,
+
+    /// Address of the instruction that failed.
+    pub pc: U24,
+
+    /// Instruction counter, counting the failed instruction.
+    pub ic: U24,
+
+    /// The raw opcode of the failed instruction.
+    pub ir: u16,
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (PC=0x{:06X}, IR=0x{:04X}, IC={})", self.kind, self.pc, self.ir, self.ic.value())
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+impl Default for Cpu<Ram> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clones the CPU state and the bus. Hooks are closures and cannot be
+/// cloned, so the copy starts without any.
+impl<B: Bus + Clone> Clone for Cpu<B> {
+    fn clone(&self) -> Self {
+        Cpu {
+            pc: self.pc,
+            bus: self.bus.clone(),
+            regs: self.regs,
+            #[cfg(feature = "fpu")]
+            fregs: self.fregs,
+            flags: self.flags,
+            sp: self.sp,
+            fp: self.fp,
+            is_running: self.is_running,
+            ir: self.ir,
+            ic: self.ic,
+            vector_base: self.vector_base,
+            irq: self.irq,
+            irq_lines: self.irq_lines,
+            device_lines: self.device_lines,
+            is_waiting: self.is_waiting,
+            features: self.features,
+            illegal_trap: self.illegal_trap,
+            decode_mode: self.decode_mode,
+            skipped_ops: self.skipped_ops,
+            reset_vector: self.reset_vector,
+            pre_hook: None,
+            post_hook: None,
+            watchpoints: self.watchpoints.clone(),
+            watch_hit: self.watch_hit,
+            op_pc: self.op_pc,
+            bad_reg: self.bad_reg.clone(),
+            trace: self.trace.clone(),
+            breakpoints: self.breakpoints.clone(),
+            perf: self.perf,
+            perf_base: self.perf_base,
+        }
+    }
 }
 
 impl Cpu {
 
     pub const FLAG_ZERO: u8 = 0x01;
     pub const FLAG_CARRY: u8 = 0x02;
+    pub const FLAG_NEGATIVE: u8 = 0x04;
+    pub const FLAG_OVERFLOW: u8 = 0x08;
+    pub const FLAG_INTERRUPT: u8 = 0x10;
+    pub const FLAG_DECIMAL: u8 = 0x20;
+
+    /// Implementation ID reported in the top byte by CPUID.
+    pub const IMPLEMENTATION: u8 = 0x01;
+
+    pub const FEATURE_MUL_DIV: u16 = 0x0001;
+    pub const FEATURE_BIT_OPS: u16 = 0x0002;
+    pub const FEATURE_INTERRUPTS: u16 = 0x0004;
+    pub const FEATURE_BCD: u16 = 0x0008;
+    pub const FEATURE_BLOCK_COPY: u16 = 0x0010;
+    pub const FEATURE_ATOMIC: u16 = 0x0020;
+    pub const FEATURE_MAC: u16 = 0x0040;
+    pub const FEATURE_FPU: u16 = 0x0080;
 
     /// Construct a new CPU with 64kb RAM,
     /// the stack pointer set to the end of RAM,
     /// and registers< PC etc set to 0.
     pub fn new() -> Self {
+        Self::with_memory_size(0x10000)
+    }
+
+    /// Construct a new CPU as `new` does, but with `size` bytes of RAM
+    /// (at most 16 MiB, the 24-bit address space).
+    pub fn with_memory_size(size: usize) -> Self {
+        assert!((2..=0x1000000).contains(&size), "memory must be 2 bytes to 16 MiB");
+        Cpu::with_bus(Ram::new(size))
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    /// Construct a new CPU attached to `bus`, with the stack pointer set
+    /// to the top of the bus's address range.
+    pub fn with_bus(bus: B) -> Self {
+        let stack_top = U24::new(bus.size() as u32 - 2);
         Cpu {
             pc: U24::new(0),
-            mem: [0; 65536],
+            bus,
             regs: [0; 9],
+            #[cfg(feature = "fpu")]
+            fregs: [0.0; 8],
             flags: 0,
-            sp: U24::new(0xFFFE),
+            sp: stack_top,
+            fp: stack_top,
 
             is_running: false,
 
             ir: 0,
             ic: U24::new(0),
+
+            vector_base: U24::new(0),
+            irq: None,
+            irq_lines: 0,
+            device_lines: 0,
+            is_waiting: false,
+
+            features: Cpu::FEATURE_MUL_DIV
+                | Cpu::FEATURE_BIT_OPS
+                | Cpu::FEATURE_INTERRUPTS
+                | Cpu::FEATURE_BCD
+                | Cpu::FEATURE_BLOCK_COPY
+                | Cpu::FEATURE_ATOMIC
+                | Cpu::FEATURE_MAC
+                | if cfg!(feature = "fpu") { Cpu::FEATURE_FPU } else { 0 },
+
+            illegal_trap: None,
+            decode_mode: DecodeMode::Strict,
+            skipped_ops: 0,
+
+            reset_vector: ResetVector::Address(U24::new(0)),
+
+            pre_hook: None,
+            post_hook: None,
+
+            watchpoints: Vec::new(),
+            watch_hit: None,
+            op_pc: U24::new(0),
+            bad_reg: Cell::new(None),
+            trace: None,
+            breakpoints: Vec::new(),
+            perf: PerfCounters::default(),
+            perf_base: None,
+        }
+    }
+
+    /// Call `hook` before every instruction fetch, with PC at the
+    /// instruction about to run. Replaces any previous pre-hook.
+    pub fn set_pre_hook(&mut self, hook: impl FnMut(&mut Self) + 'static) {
+        self.pre_hook = Some(Box::new(hook));
+    }
+
+    /// Call `hook` after every instruction that executes successfully,
+    /// with the decoded op. Not called for trapped or skipped opcodes.
+    /// Replaces any previous post-hook.
+    pub fn set_post_hook(&mut self, hook: impl FnMut(&mut Self, &Op) + 'static) {
+        self.post_hook = Some(Box::new(hook));
+    }
+
+    /// Remove the pre- and post-instruction hooks.
+    pub fn clear_hooks(&mut self) {
+        self.pre_hook = None;
+        self.post_hook = None;
+    }
+
+    /// Return the CPU to its power-on state without touching memory:
+    /// registers and flags are cleared (so interrupts are disabled), SP
+    /// and FP are reinitialised, any WAIT or raised interrupt is dropped
+    /// and PC is loaded from `reset_vector`. Configuration such as
+    /// `vector_base`, `features` and `decode_mode` is kept.
+    pub fn reset(&mut self) {
+        self.regs = [0; 9];
+        #[cfg(feature = "fpu")]
+        {
+            self.fregs = [0.0; 8];
         }
+        self.flags = 0;
+        self.sp = U24::new(self.bus.size() as u32 - 2);
+        self.fp = self.sp;
+        self.is_running = false;
+        self.is_waiting = false;
+        self.irq = None;
+        self.irq_lines = 0;
+        self.device_lines = 0;
+        self.ir = 0;
+        self.ic = U24::new(0);
+        self.skipped_ops = 0;
+        self.perf.reset();
+
+        self.pc = match self.reset_vector {
+            ResetVector::Address(addr) => addr,
+            ResetVector::Indirect(addr) => self.mem_read3(addr),
+        };
+    }
+
+    /// The performance counter register at `addr`, if `perf_base` maps
+    /// one there.
+    fn perf_offset(&self, addr: U24) -> Option<u32> {
+        let offset = addr.value().wrapping_sub(self.perf_base?.value());
+        (offset < PerfCounters::SIZE).then_some(offset)
     }
 
     /// Read a value from memory with the given address.
-    pub fn mem_read(&self, addr: U24) -> u8 {
-        self.mem[addr.value() as usize]
+    pub fn mem_read(&mut self, addr: U24) -> u8 {
+        if let Some(offset) = self.perf_offset(addr) {
+            return self.perf.read(offset);
+        }
+        self.perf.mem_reads += 1;
+        let val = self.bus.read8(addr);
+        if !self.watchpoints.is_empty() {
+            self.check_watch(addr, Access::Read, &[val]);
+        }
+        val
+    }
+
+    /// Read two bytes from memory at the given address.
+    pub fn mem_read2(&mut self, addr: U24) -> u16 {
+        if let Some(offset) = self.perf_offset(addr) {
+            return u16::from_le_bytes([self.perf.read(offset), self.perf.read(offset + 1)]);
+        }
+        self.perf.mem_reads += 1;
+        let val = self.bus.read16(addr);
+        if !self.watchpoints.is_empty() {
+            self.check_watch(addr, Access::Read, &val.to_le_bytes());
+        }
+        val
+    }
+
+    /// Read three bytes from memory at the given address.
+    pub fn mem_read3(&mut self, addr: U24) -> U24 {
+        if let Some(offset) = self.perf_offset(addr) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.perf.read(offset + i));
+            return U24::from_le_bytes([a, b, c]);
+        }
+        self.perf.mem_reads += 1;
+        let val = self.bus.read24(addr);
+        if !self.watchpoints.is_empty() {
+            self.check_watch(addr, Access::Read, &val.to_le_bytes());
+        }
+        val
     }
 
     /// Write a byte to memory at the given address.
     pub fn mem_write(&mut self, addr: U24, val: u8) {
-        self.mem[addr.value() as usize] = val;
+        if let Some(offset) = self.perf_offset(addr) {
+            return self.perf.write(offset, val);
+        }
+        self.perf.mem_writes += 1;
+        self.bus.write8(addr, val);
+        if !self.watchpoints.is_empty() {
+            self.check_watch(addr, Access::Write, &[val]);
+        }
     }
 
     /// Write two bytes to memory at the given address.
     pub fn mem_write2(&mut self, addr: U24, val: u16) {
-        let bytes = val.to_le_bytes();
-        let pos = addr.value() as usize;
-        self.mem[pos..pos+2].copy_from_slice(&bytes);
+        if let Some(offset) = self.perf_offset(addr) {
+            let [low, high] = val.to_le_bytes();
+            self.perf.write(offset, low);
+            return self.perf.write(offset + 1, high);
+        }
+        self.perf.mem_writes += 1;
+        self.bus.write16(addr, val);
+        if !self.watchpoints.is_empty() {
+            self.check_watch(addr, Access::Write, &val.to_le_bytes());
+        }
     }
 
     /// Write three bytes to memory at the given address.
     pub fn mem_write3(&mut self, addr: U24, val: U24) {
-        let bytes = val.to_le_bytes();
-        let pos = addr.value() as usize;
-        self.mem[pos..pos+3].copy_from_slice(&bytes);
+        if let Some(offset) = self.perf_offset(addr) {
+            for (i, byte) in val.to_le_bytes().into_iter().enumerate() {
+                self.perf.write(offset + i as u32, byte);
+            }
+            return;
+        }
+        self.perf.mem_writes += 1;
+        self.bus.write24(addr, val);
+        if !self.watchpoints.is_empty() {
+            self.check_watch(addr, Access::Write, &val.to_le_bytes());
+        }
+    }
+
+    /// The bytes of `regs` a `width`-byte access to `reg` covers, or
+    /// `None` if it runs past the end, in which case the register is
+    /// noted so the instruction fails with `ErrorKind::InvalidRegister`.
+    fn reg_bytes(&self, reg: u8, width: usize) -> Option<Range<usize>> {
+        let pos = reg as usize;
+        if pos + width > self.regs.len() {
+            if self.bad_reg.get().is_none() {
+                self.bad_reg.set(Some(reg));
+            }
+            return None;
+        }
+        Some(pos..pos + width)
     }
 
     /// Read a value from the given register.
     pub fn reg_read(&self, reg: u8) -> u8 {
-        self.regs[reg as usize]
+        self.reg_bytes(reg, 1).map_or(0, |range| self.regs[range.start])
     }
 
     // Read two bytes from register & register+1
     pub fn reg_read2(&self, reg: u8) -> u16 {
-        (self.regs[reg as usize + 1] as u16) << 8
-        | self.regs[reg as usize] as u16
+        self.reg_bytes(reg, 2)
+            .map_or(0, |range| u16::from_le_bytes([self.regs[range.start], self.regs[range.start + 1]]))
     }
 
     /// Read 3 bytes from register & register+1 & register+2
     pub fn reg_read3(&self, reg: u8) -> U24 {
-        let pos = reg as usize;
-
-        let bytes: [u8; 3] = self.regs[pos..pos + 3]
-            .try_into()
-            .expect("out of bounds read");
-
-        U24::from_le_bytes(bytes)
+        self.reg_bytes(reg, 3).map_or(U24::new(0), |range| {
+            let bytes = &self.regs[range];
+            U24::from_le_bytes([bytes[0], bytes[1], bytes[2]])
+        })
     }
 
     /// Write a value to the given register.
     pub fn reg_write(&mut self, reg: u8, val: u8) {
-        self.regs[reg as usize] = val;
+        trace!("R{} <- {:02X}", reg, val);
+        if let Some(range) = self.reg_bytes(reg, 1) {
+            self.regs[range.start] = val;
+        }
     }
 
     /// Write a 16-bit value to register & register+1
     pub fn reg_write2(&mut self, reg: u8, val: u16) {
-        self.regs[reg as usize] = (val & 0xFF) as u8;
-        self.regs[reg as usize + 1] = ((val & 0xFF00) >> 8) as u8;
+        trace!("R{} <- {:04X}", reg, val);
+        if let Some(range) = self.reg_bytes(reg, 2) {
+            self.regs[range].copy_from_slice(&val.to_le_bytes());
+        }
     }
 
     pub fn reg_write3(&mut self, reg: u8, val: U24) {
-        let bytes = val.to_le_bytes();
-        println!("reg_write3: {:?} @ {:?}", bytes, reg);
-        let pos = reg as usize;
-        self.regs[pos..pos+3].copy_from_slice(&bytes);
+        trace!("R{} <- {:06X}", reg, val);
+        if let Some(range) = self.reg_bytes(reg, 3) {
+            self.regs[range].copy_from_slice(&val.to_le_bytes());
+        }
     }
 
     /// Determine whether the given flag is set.
@@ -130,31 +601,118 @@ impl Cpu {
     /// Set or clear the given flag.
     pub fn flag_write(&mut self, flag: u8, val: bool) {
         if val {
-            self.flags = self.flags | flag
+            self.flags |= flag
         }
         else {
-            self.flags = self.flags & !flag
+            self.flags &= !flag
+        }
+    }
+
+    /// OVERFLOW flag for an 8-bit addition. In decimal mode this is the
+    /// carry out of the low nibble instead, which DAA needs to adjust the
+    /// result.
+    fn add_overflow1(&self, lhs: u16, rhs: u16, value: u16) -> bool {
+        if self.flag_read(Cpu::FLAG_DECIMAL) {
+            (lhs & 0x0F) + (rhs & 0x0F) > 0x0F
+        } else {
+            (lhs ^ value) & (rhs ^ value) & 0x80 != 0
         }
     }
 
+    /// Push a byte onto the stack.
+    /// The stack grows downwards and SP points at the next free byte.
+    pub fn push(&mut self, val: u8) {
+        self.mem_write(self.sp, val);
+        self.sp -= 1;
+    }
+
+    /// Pop a byte from the stack.
+    pub fn pop(&mut self) -> u8 {
+        self.sp += 1;
+        self.mem_read(self.sp)
+    }
+
+    /// Push a 24-bit value onto the stack, high byte first, so that it
+    /// is stored little-endian starting at SP+1.
+    pub fn push3(&mut self, val: U24) {
+        let [low, mid, high] = val.to_le_bytes();
+        self.push(high);
+        self.push(mid);
+        self.push(low);
+    }
+
+    /// Pop a 24-bit value pushed with `push3`.
+    pub fn pop3(&mut self) -> U24 {
+        let low = self.pop();
+        let mid = self.pop();
+        let high = self.pop();
+        U24::from_le_bytes([low, mid, high])
+    }
+
+    /// Enter the interrupt handler for `vector`.
+    ///
+    /// Pushes PC and then the flags, leaving the frame
+    /// `(SP+1)` = flags, `(SP+2..SP+4)` = return address, which is
+    /// what RTI expects to unwind. PC is then loaded from the vector
+    /// table entry at `vector_base + vector * 3`. Interrupts are
+    /// disabled on entry; RTI restores the saved I flag.
+    pub fn interrupt(&mut self, vector: u8) {
+        debug!("interrupt {} at {:06X}", vector, self.pc);
+        self.push3(self.pc);
+        self.push(self.flags);
+        self.flag_write(Cpu::FLAG_INTERRUPT, false);
+        self.pc = self.mem_read3(self.vector_base + vector as u32 * 3);
+    }
+
+    /// Raise the interrupt line with the given vector. It is serviced
+    /// on the next tick if the INTERRUPT flag is set, and wakes the CPU
+    /// from WAIT either way.
+    pub fn raise_interrupt(&mut self, vector: u8) {
+        self.irq = Some(vector);
+    }
+
+    /// Assert hardware interrupt line `line` (0-7), which is serviced
+    /// through vector `line`. The line stays pending until the CPU
+    /// services it or the host calls `clear_irq`; like `raise_interrupt`
+    /// it wakes the CPU from WAIT even while interrupts are masked. When
+    /// several lines are pending the lowest-numbered one goes first.
+    pub fn raise_irq(&mut self, line: u8) {
+        self.irq_lines |= 1 << (line & 7);
+    }
+
+    /// Withdraw a pending hardware interrupt line.
+    pub fn clear_irq(&mut self, line: u8) {
+        self.irq_lines &= !(1 << (line & 7));
+    }
+
+    /// True if an interrupt has been raised and not yet serviced.
+    pub fn irq_pending(&self) -> bool {
+        self.irq.is_some() || (self.irq_lines | self.device_lines) != 0
+    }
+
     /// Fetch the opcode at the current memory location (pointed to by PC) and increase the program counter by 2.
     fn fetch(&mut self) {
-        let pos = self.pc.value() as usize;
-        self.ir = u16::from_le_bytes(self.mem[pos..pos + 2].try_into().expect("Out of bounds"));
+        self.ir = self.bus.read16(self.pc);
         self.pc += 2;
     }
 
     /// Decode the current opcode, retrieving required parameters.
-    fn decode(&mut self) -> Result<Op, CpuError> {
+    fn decode(&mut self) -> Result<Op, ErrorKind> {
         let operand_count = ((self.ir & 0xE00) >> 9) as usize;
 
         let op_code = OpCode::try_from(self.ir)
-            .map_err(|_| CpuError::InvalidOpCode(self.ir))?;
+            .map_err(|_| ErrorKind::InvalidOpCode(self.ir))?;
+
+        // Without the FPU its opcodes are unknown, so they can be emulated
+        // through the illegal-instruction trap.
+        if op_code.is_fpu() && !cfg!(feature = "fpu") {
+            return Err(ErrorKind::InvalidOpCode(self.ir));
+        }
 
         let mut op = Op { code: op_code, ..Op::new() };
 
         for i in 0..operand_count {
-            op.operands[i] = self.mem_read(self.pc);
+            op.operands[i] = self.bus.read8(self.pc);
             self.pc += 1;
         }
 
@@ -162,20 +720,12 @@ impl Cpu {
     }
 
     /// Execute the given operation on the CPU.
-    fn execute(&mut self, op: Op) -> Result<(), CpuError> {
+    fn execute(&mut self, op: Op) -> Result<(), ErrorKind> {
         match op.code {
             OpCode::NOP => Ok(()),
 
             OpCode::RTS => {
-                // Pop address from stack
-                self.sp += 2;
-                let addr =
-                    U24::new(self.mem_read(self.sp - 2) as u32) << 16
-                    | U24::new(self.mem_read(self.sp - 1) as u32) << 8
-                    | U24::new(self.mem_read(self.sp) as u32);
-
-                // Jump to address
-                self.pc = addr;
+                self.pc = self.pop3();
                 Ok(())
             }
 
@@ -184,23 +734,98 @@ impl Cpu {
                 Ok(())
             }
 
+            OpCode::PUSHA => {
+                for reg in 0..self.regs.len() as u8 {
+                    self.push(self.reg_read(reg));
+                }
+                Ok(())
+            }
+
+            OpCode::POPA => {
+                for reg in (0..self.regs.len() as u8).rev() {
+                    let value = self.pop();
+                    self.reg_write(reg, value);
+                }
+                Ok(())
+            }
+
+            OpCode::SWI => {
+                self.interrupt(op.read_op(0));
+                Ok(())
+            }
+
+            OpCode::RTI => {
+                self.flags = self.pop();
+                self.pc = self.pop3();
+                Ok(())
+            }
+
+            OpCode::SEI => {
+                self.flag_write(Cpu::FLAG_INTERRUPT, true);
+                Ok(())
+            }
+
+            OpCode::CLI => {
+                self.flag_write(Cpu::FLAG_INTERRUPT, false);
+                Ok(())
+            }
+
+            OpCode::LEAVE => {
+                self.sp = self.fp;
+                self.fp = self.pop3();
+                Ok(())
+            }
+
+            OpCode::SED => {
+                self.flag_write(Cpu::FLAG_DECIMAL, true);
+                Ok(())
+            }
+
+            OpCode::CLD => {
+                self.flag_write(Cpu::FLAG_DECIMAL, false);
+                Ok(())
+            }
+
+            OpCode::WAIT => {
+                self.is_waiting = true;
+                Ok(())
+            }
+
+            OpCode::PUSHF => {
+                self.push(self.flags);
+                Ok(())
+            }
+
+            OpCode::POPF => {
+                self.flags = self.pop();
+                Ok(())
+            }
+
             // ----------------------------------------
             // ADD
             // ----------------------------------------
 
             OpCode::ADD1 => {
-                let value = self.reg_read(op.rd()) as u16 + self.reg_read(op.rs()) as u16;
+                let lhs: u16 = self.reg_read(op.rd()) as u16;
+                let rhs: u16 = self.reg_read(op.rs()) as u16;
+                let value = lhs + rhs;
                 self.reg_write(op.rd(), value as u8);
                 self.flag_write(Cpu::FLAG_ZERO, (value as u8) == 0);
                 self.flag_write(Cpu::FLAG_CARRY, value & 0x100 != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, self.add_overflow1(lhs, rhs, value));
                 Ok(())
             },
 
             OpCode::ADD2 => {
-                let value: u32 = self.reg_read2(op.rd()) as u32 + self.reg_read2(op.rs()) as u32;
+                let lhs: u32 = self.reg_read2(op.rd()) as u32;
+                let rhs: u32 = self.reg_read2(op.rs()) as u32;
+                let value: u32 = lhs + rhs;
                 self.reg_write2(op.rd(), value as u16);
                 self.flag_write(Cpu::FLAG_ZERO, value as u16 == 0);
                 self.flag_write(Cpu::FLAG_CARRY, value & 0x10000 != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ value) & (rhs ^ value) & 0x8000 != 0);
                 Ok(())
             }
 
@@ -211,6 +836,8 @@ impl Cpu {
                 self.reg_write3(op.rd(), U24::new(value));
                 self.flag_write(Cpu::FLAG_ZERO, value & 0xFFFFFF == 0);
                 self.flag_write(Cpu::FLAG_CARRY, value & 0x1000000 != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ value) & (rhs ^ value) & 0x800000 != 0);
                 Ok(())
             }
 
@@ -221,30 +848,166 @@ impl Cpu {
             OpCode::SUB1 => {
                 let rdv: u16 = self.reg_read(op.rd()) as u16;
                 let rsv: u16 = self.reg_read(op.rs()) as u16;
-                let value: u16 = rdv - rsv;
+                let value: u16 = rdv.wrapping_sub(rsv);
                 self.reg_write(op.rd(), value as u8);
                 self.flag_write(Cpu::FLAG_ZERO, (value as u8) == 0);
                 self.flag_write(Cpu::FLAG_CARRY, rdv < rsv);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (rdv ^ rsv) & (rdv ^ value) & 0x80 != 0);
                 Ok(())
             }
 
             OpCode::SUB2 => {
                 let rdv: u32 = self.reg_read2(op.rd()) as u32;
                 let rsv: u32 = self.reg_read2(op.rs()) as u32;
-                let value: u32 = rdv - rsv;
+                let value: u32 = rdv.wrapping_sub(rsv);
                 self.reg_write2(op.rd(), value as u16);
                 self.flag_write(Cpu::FLAG_ZERO, (value as u16) == 0);
                 self.flag_write(Cpu::FLAG_CARRY, rdv < rsv);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (rdv ^ rsv) & (rdv ^ value) & 0x8000 != 0);
                 Ok(())
             }
 
             OpCode::SUB3 => {
                 let rdv: u32 = self.reg_read3(op.rd()).into();
                 let rsv: u32 = self.reg_read3(op.rs()).into();
-                let value: U24 = U24::new(rdv - rsv);
+                let value: U24 = U24::new(rdv.wrapping_sub(rsv));
                 self.reg_write3(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, rdv < rsv);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value.as_u32() & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (rdv ^ rsv) & (rdv ^ value.as_u32()) & 0x800000 != 0);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // MUL
+            // ----------------------------------------
+
+            OpCode::MUL1 => {
+                let value: u16 = self.reg_read(op.rd()) as u16 * self.reg_read(op.rs()) as u16;
+                self.reg_write(op.rd(), value as u8);
+                self.flag_write(Cpu::FLAG_ZERO, (value as u8) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, value > 0xFF);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::MUL2 => {
+                let value: u32 = self.reg_read2(op.rd()) as u32 * self.reg_read2(op.rs()) as u32;
+                self.reg_write2(op.rd(), value as u16);
+                self.flag_write(Cpu::FLAG_ZERO, (value as u16) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, value > 0xFFFF);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::MUL3 => {
+                let lhs: u64 = self.reg_read3(op.rd()).as_u32() as u64;
+                let rhs: u64 = self.reg_read3(op.rs()).as_u32() as u64;
+                let value: u64 = lhs * rhs;
+                self.reg_write3(op.rd(), U24::new(value as u32));
+                self.flag_write(Cpu::FLAG_ZERO, value & 0xFFFFFF == 0);
+                self.flag_write(Cpu::FLAG_CARRY, value > 0xFFFFFF);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // DIV
+            // ----------------------------------------
+
+            OpCode::DIV1 => {
+                let rsv: u8 = self.reg_read(op.rs());
+                if rsv == 0 {
+                    return Err(ErrorKind::DivideByZero);
+                }
+                let value: u8 = self.reg_read(op.rd()) / rsv;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::DIV2 => {
+                let rsv: u16 = self.reg_read2(op.rs());
+                if rsv == 0 {
+                    return Err(ErrorKind::DivideByZero);
+                }
+                let value: u16 = self.reg_read2(op.rd()) / rsv;
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::DIV3 => {
+                let rsv: u32 = self.reg_read3(op.rs()).into();
+                if rsv == 0 {
+                    return Err(ErrorKind::DivideByZero);
+                }
+                let rdv: u32 = self.reg_read3(op.rd()).into();
+                let value: U24 = U24::new(rdv / rsv);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // MOD
+            // ----------------------------------------
+
+            OpCode::MOD1 => {
+                let rsv: u8 = self.reg_read(op.rs());
+                if rsv == 0 {
+                    return Err(ErrorKind::DivideByZero);
+                }
+                let value: u8 = self.reg_read(op.rd()) % rsv;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::MOD2 => {
+                let rsv: u16 = self.reg_read2(op.rs());
+                if rsv == 0 {
+                    return Err(ErrorKind::DivideByZero);
+                }
+                let value: u16 = self.reg_read2(op.rd()) % rsv;
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::MOD3 => {
+                let rsv: u32 = self.reg_read3(op.rs()).into();
+                if rsv == 0 {
+                    return Err(ErrorKind::DivideByZero);
+                }
+                let rdv: u32 = self.reg_read3(op.rd()).into();
+                let value: U24 = U24::new(rdv % rsv);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -257,6 +1020,8 @@ impl Cpu {
                 self.reg_write(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -265,6 +1030,8 @@ impl Cpu {
                 self.reg_write2(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -273,6 +1040,8 @@ impl Cpu {
                 self.reg_write3(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -285,6 +1054,8 @@ impl Cpu {
                 self.reg_write(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -293,6 +1064,8 @@ impl Cpu {
                 self.reg_write2(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -301,6 +1074,8 @@ impl Cpu {
                 self.reg_write3(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -313,6 +1088,8 @@ impl Cpu {
                 self.reg_write(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -321,6 +1098,8 @@ impl Cpu {
                 self.reg_write2(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -329,6 +1108,8 @@ impl Cpu {
                 self.reg_write3(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -341,6 +1122,8 @@ impl Cpu {
                 self.reg_write(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -349,6 +1132,8 @@ impl Cpu {
                 self.reg_write2(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
@@ -357,144 +1142,1263 @@ impl Cpu {
                 self.reg_write3(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
             // ----------------------------------------
-            // LOADI
+            // SHL / SHR by register count
             // ----------------------------------------
 
-            OpCode::LOADI1 => {
+            OpCode::SHLR1 => {
+                let (value, carry) = shift_left(self.reg_read(op.rd()) as u32, self.reg_read(op.rs()) as u32, 8);
+                self.reg_write(op.rd(), value as u8);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHLR2 => {
+                let (value, carry) = shift_left(self.reg_read2(op.rd()) as u32, self.reg_read(op.rs()) as u32, 16);
+                self.reg_write2(op.rd(), value as u16);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHLR3 => {
+                let (value, carry) = shift_left(self.reg_read3(op.rd()).as_u32(), self.reg_read(op.rs()) as u32, 24);
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHRR1 => {
+                let (value, carry) = shift_right(self.reg_read(op.rd()) as u32, self.reg_read(op.rs()) as u32);
+                self.reg_write(op.rd(), value as u8);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHRR2 => {
+                let (value, carry) = shift_right(self.reg_read2(op.rd()) as u32, self.reg_read(op.rs()) as u32);
+                self.reg_write2(op.rd(), value as u16);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHRR3 => {
+                let (value, carry) = shift_right(self.reg_read3(op.rd()).as_u32(), self.reg_read(op.rs()) as u32);
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // CLZ / POPCNT
+            // ----------------------------------------
+
+            OpCode::CLZ1 => {
+                let value = self.reg_read(op.rs()).leading_zeros() as u8;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, false);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::CLZ2 => {
+                let value = self.reg_read2(op.rs()).leading_zeros() as u8;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, false);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::CLZ3 => {
+                let value = (self.reg_read3(op.rs()).as_u32().leading_zeros() - 8) as u8;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, false);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::POPCNT1 => {
+                let value = self.reg_read(op.rs()).count_ones() as u8;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, false);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::POPCNT2 => {
+                let value = self.reg_read2(op.rs()).count_ones() as u8;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, false);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::POPCNT3 => {
+                let value = self.reg_read3(op.rs()).as_u32().count_ones() as u8;
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, false);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // SHLI / SHRI / ROLI / RORI
+            // ----------------------------------------
+
+            OpCode::SHLI1 => {
+                let (value, carry) = shift_left(self.reg_read(op.rd()) as u32, op.read_op(1) as u32, 8);
+                self.reg_write(op.rd(), value as u8);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHLI2 => {
+                let (value, carry) = shift_left(self.reg_read2(op.rd()) as u32, op.read_op(1) as u32, 16);
+                self.reg_write2(op.rd(), value as u16);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHLI3 => {
+                let (value, carry) = shift_left(self.reg_read3(op.rd()).as_u32(), op.read_op(1) as u32, 24);
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHRI1 => {
+                let (value, carry) = shift_right(self.reg_read(op.rd()) as u32, op.read_op(1) as u32);
+                self.reg_write(op.rd(), value as u8);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHRI2 => {
+                let (value, carry) = shift_right(self.reg_read2(op.rd()) as u32, op.read_op(1) as u32);
+                self.reg_write2(op.rd(), value as u16);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SHRI3 => {
+                let (value, carry) = shift_right(self.reg_read3(op.rd()).as_u32(), op.read_op(1) as u32);
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::ROLI1 => {
+                let (value, carry) = rotate_left(self.reg_read(op.rd()) as u32, op.read_op(1) as u32, 8);
+                self.reg_write(op.rd(), value as u8);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::ROLI2 => {
+                let (value, carry) = rotate_left(self.reg_read2(op.rd()) as u32, op.read_op(1) as u32, 16);
+                self.reg_write2(op.rd(), value as u16);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::ROLI3 => {
+                let (value, carry) = rotate_left(self.reg_read3(op.rd()).as_u32(), op.read_op(1) as u32, 24);
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::RORI1 => {
+                let (value, carry) = rotate_right(self.reg_read(op.rd()) as u32, op.read_op(1) as u32, 8);
+                self.reg_write(op.rd(), value as u8);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::RORI2 => {
+                let (value, carry) = rotate_right(self.reg_read2(op.rd()) as u32, op.read_op(1) as u32, 16);
+                self.reg_write2(op.rd(), value as u16);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::RORI3 => {
+                let (value, carry) = rotate_right(self.reg_read3(op.rd()).as_u32(), op.read_op(1) as u32, 24);
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // SEX
+            // ----------------------------------------
+
+            OpCode::SEX2 => {
+                let value: u16 = self.reg_read(op.rd()) as i8 as i16 as u16;
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::SEX3 => {
+                let value: U24 = U24::new(self.reg_read2(op.rd()) as i16 as i32 as u32);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // BSWAP
+            // ----------------------------------------
+
+            OpCode::BSWAP2 => {
+                let value: u16 = self.reg_read2(op.rd()).swap_bytes();
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::BSWAP3 => {
+                let [low, mid, high] = self.reg_read3(op.rd()).to_le_bytes();
+                let value: U24 = U24::from_le_bytes([high, mid, low]);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // DAA
+            // ----------------------------------------
+
+            OpCode::DAA => {
+                // Adjust the binary sum of two packed BCD bytes, using the
+                // nibble carry recorded in OVERFLOW by a decimal-mode add.
+                let mut value: u8 = self.reg_read(op.rd());
+                let carry = self.flag_read(Cpu::FLAG_CARRY) || value > 0x99;
+                if self.flag_read(Cpu::FLAG_OVERFLOW) || value & 0x0F > 0x09 {
+                    value = value.wrapping_add(0x06);
+                }
+                if carry {
+                    value = value.wrapping_add(0x60);
+                }
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, carry);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // CPUID
+            // ----------------------------------------
+
+            OpCode::CPUID => {
+                let [low, high] = self.features.to_le_bytes();
+                self.reg_write3(op.rd(), U24::from_le_bytes([low, high, Cpu::IMPLEMENTATION]));
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // MOVSP / MOVPC
+            // ----------------------------------------
+
+            OpCode::MOVSPR => {
+                self.reg_write3(op.rd(), self.sp);
+                Ok(())
+            }
+
+            OpCode::MOVRSP => {
+                self.sp = self.reg_read3(op.rs());
+                Ok(())
+            }
+
+            OpCode::MOVPC => {
+                self.reg_write3(op.rd(), self.pc);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // XCHG
+            // ----------------------------------------
+
+            OpCode::XCHG1 => {
+                let rdv: u8 = self.reg_read(op.rd());
+                let rsv: u8 = self.reg_read(op.rs());
+                self.reg_write(op.rs(), rdv);
+                self.reg_write(op.rd(), rsv);
+                Ok(())
+            }
+
+            OpCode::XCHG2 => {
+                let rdv: u16 = self.reg_read2(op.rd());
+                let rsv: u16 = self.reg_read2(op.rs());
+                self.reg_write2(op.rs(), rdv);
+                self.reg_write2(op.rd(), rsv);
+                Ok(())
+            }
+
+            OpCode::XCHG3 => {
+                let rdv: U24 = self.reg_read3(op.rd());
+                let rsv: U24 = self.reg_read3(op.rs());
+                self.reg_write3(op.rs(), rdv);
+                self.reg_write3(op.rd(), rsv);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // CMOV
+            // ----------------------------------------
+
+            OpCode::CMOVZ1 => {
+                if self.flag_read(Cpu::FLAG_ZERO) {
+                    self.reg_write(op.rd(), self.reg_read(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVZ2 => {
+                if self.flag_read(Cpu::FLAG_ZERO) {
+                    self.reg_write2(op.rd(), self.reg_read2(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVZ3 => {
+                if self.flag_read(Cpu::FLAG_ZERO) {
+                    self.reg_write3(op.rd(), self.reg_read3(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVNZ1 => {
+                if !self.flag_read(Cpu::FLAG_ZERO) {
+                    self.reg_write(op.rd(), self.reg_read(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVNZ2 => {
+                if !self.flag_read(Cpu::FLAG_ZERO) {
+                    self.reg_write2(op.rd(), self.reg_read2(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVNZ3 => {
+                if !self.flag_read(Cpu::FLAG_ZERO) {
+                    self.reg_write3(op.rd(), self.reg_read3(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVC1 => {
+                if self.flag_read(Cpu::FLAG_CARRY) {
+                    self.reg_write(op.rd(), self.reg_read(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVC2 => {
+                if self.flag_read(Cpu::FLAG_CARRY) {
+                    self.reg_write2(op.rd(), self.reg_read2(op.rs()));
+                }
+                Ok(())
+            }
+
+            OpCode::CMOVC3 => {
+                if self.flag_read(Cpu::FLAG_CARRY) {
+                    self.reg_write3(op.rd(), self.reg_read3(op.rs()));
+                }
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // LOADI
+            // ----------------------------------------
+
+            OpCode::LOADI1 => {
                 let imm = op.read_op(1);
                 self.reg_write(op.rd(), imm);
                 self.flag_write(Cpu::FLAG_ZERO, imm == 0);
                 self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, imm & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            },
+
+            OpCode::LOADI2 => {
+                let imm: u16 = op.read_op2(1);
+                self.reg_write2(op.rd(), imm);
+                self.flag_write(Cpu::FLAG_ZERO, imm == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, imm & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            },
+
+            OpCode::LOADI3 => {                
+                let imm: U24 = op.read_op3(1);
+                self.reg_write3(op.rd(), imm);
+                self.flag_write(Cpu::FLAG_ZERO, imm == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, imm & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            },
+
+            // ----------------------------------------
+            // ADDI
+            // ----------------------------------------
+
+            OpCode::ADDI1 => {
+                let lhs: u16 = self.reg_read(op.rd()) as u16;
+                let rhs: u16 = op.read_op(1) as u16;
+                let value: u16 = lhs + rhs;
+                self.reg_write(op.rd(), (value & 0xFF) as u8);
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0x100) != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x80) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, self.add_overflow1(lhs, rhs, value));
+                Ok(())
+            }
+
+            OpCode::ADDI2 => {
+                let lhs: u32 = self.reg_read2(op.rd()) as u32;
+                let rhs: u32 = op.read_op2(1) as u32;
+                let value: u32 = lhs + rhs;
+                self.reg_write2(op.rd(), (value & 0xFFFF) as u16);
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0x10000) != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x8000) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ value) & (rhs ^ value) & 0x8000 != 0);
+                Ok(())
+            }
+
+            OpCode::ADDI3 => {
+                let lhs: u32 = self.reg_read3(op.rd()).into();
+                let rhs: u32 = op.read_op3(1).as_u32();
+                let value: u32 = lhs + rhs;
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFFFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0x1000000) != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x800000) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ value) & (rhs ^ value) & 0x800000 != 0);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // SUBI
+            // ----------------------------------------
+
+            OpCode::SUBI1 => {
+                let lhs: u8 = self.reg_read(op.rd());
+                let rhs: u8 = op.read_op(1);
+                let value: u8 = lhs.wrapping_sub(rhs);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, lhs < rhs);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ rhs) & (lhs ^ value) & 0x80 != 0);
+                Ok(())
+            }
+
+            OpCode::SUBI2 => {
+                let lhs: u16 = self.reg_read2(op.rd());
+                let rhs: u16 = op.read_op2(1);
+                let value: u16 = lhs.wrapping_sub(rhs);
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, lhs < rhs);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ rhs) & (lhs ^ value) & 0x8000 != 0);
+                Ok(())
+            }
+
+            OpCode::SUBI3 => {
+                let lhs: u32 = self.reg_read3(op.rd()).into();
+                let rhs: u32 = op.read_op3(1).into();
+                let value: u32 = lhs.wrapping_sub(rhs) & 0xFFFFFF;
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, lhs < rhs);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ rhs) & (lhs ^ value) & 0x800000 != 0);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // CMPI
+            // ----------------------------------------
+
+            OpCode::CMPI1 => {
+                let lhs: u8 = self.reg_read(op.rd());
+                let rhs: u8 = op.read_op(1);
+                let value: u8 = lhs.wrapping_sub(rhs);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, lhs < rhs);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ rhs) & (lhs ^ value) & 0x80 != 0);
+                Ok(())
+            }
+
+            OpCode::CMPI2 => {
+                let lhs: u16 = self.reg_read2(op.rd());
+                let rhs: u16 = op.read_op2(1);
+                let value: u16 = lhs.wrapping_sub(rhs);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, lhs < rhs);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ rhs) & (lhs ^ value) & 0x8000 != 0);
+                Ok(())
+            }
+
+            OpCode::CMPI3 => {
+                let lhs: u32 = self.reg_read3(op.rd()).into();
+                let rhs: u32 = op.read_op3(1).into();
+                let value: u32 = lhs.wrapping_sub(rhs) & 0xFFFFFF;
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, lhs < rhs);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (lhs ^ rhs) & (lhs ^ value) & 0x800000 != 0);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // ANDI
+            // ----------------------------------------
+
+            OpCode::ANDI1 => {
+                let value: u8 = self.reg_read(op.rd()) & op.read_op(1);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::ANDI2 => {
+                let value: u16 = self.reg_read2(op.rd()) & op.read_op2(1);
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::ANDI3 => {
+                let value: U24 = self.reg_read3(op.rd()) & op.read_op3(1);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // ORI
+            // ----------------------------------------
+
+            OpCode::ORI1 => {
+                let value: u8 = self.reg_read(op.rd()) | op.read_op(1);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::ORI2 => {
+                let value: u16 = self.reg_read2(op.rd()) | op.read_op2(1);
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::ORI3 => {
+                let value: U24 = self.reg_read3(op.rd()) | op.read_op3(1);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // XORI
+            // ----------------------------------------
+
+            OpCode::XORI1 => {
+                let value: u8 = self.reg_read(op.rd()) ^ op.read_op(1);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::XORI2 => {
+                let value: u16 = self.reg_read2(op.rd()) ^ op.read_op2(1);
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::XORI3 => {
+                let value: U24 = self.reg_read3(op.rd()) ^ op.read_op3(1);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // BSET / BCLR / BTST
+            // ----------------------------------------
+
+            OpCode::BSET1 => {
+                let value: u8 = self.reg_read(op.rd()) | (1 << (op.read_op(1) % 8));
+                self.reg_write(op.rd(), value);
+                Ok(())
+            }
+
+            OpCode::BSET2 => {
+                let value: u16 = self.reg_read2(op.rd()) | (1 << (op.read_op(1) % 16));
+                self.reg_write2(op.rd(), value);
+                Ok(())
+            }
+
+            OpCode::BSET3 => {
+                let value: U24 = self.reg_read3(op.rd()) | (1 << (op.read_op(1) % 24));
+                self.reg_write3(op.rd(), value);
+                Ok(())
+            }
+
+            OpCode::BCLR1 => {
+                let value: u8 = self.reg_read(op.rd()) & !(1 << (op.read_op(1) % 8));
+                self.reg_write(op.rd(), value);
+                Ok(())
+            }
+
+            OpCode::BCLR2 => {
+                let value: u16 = self.reg_read2(op.rd()) & !(1 << (op.read_op(1) % 16));
+                self.reg_write2(op.rd(), value);
+                Ok(())
+            }
+
+            OpCode::BCLR3 => {
+                let value: U24 = self.reg_read3(op.rd()) & !(1 << (op.read_op(1) % 24));
+                self.reg_write3(op.rd(), value);
+                Ok(())
+            }
+
+            OpCode::BTST1 => {
+                let set = self.reg_read(op.rd()) & (1 << (op.read_op(1) % 8)) != 0;
+                self.flag_write(Cpu::FLAG_ZERO, !set);
+                Ok(())
+            }
+
+            OpCode::BTST2 => {
+                let set = self.reg_read2(op.rd()) & (1 << (op.read_op(1) % 16)) != 0;
+                self.flag_write(Cpu::FLAG_ZERO, !set);
+                Ok(())
+            }
+
+            OpCode::BTST3 => {
+                let set = self.reg_read3(op.rd()) & (1 << (op.read_op(1) % 24)) != 0;
+                self.flag_write(Cpu::FLAG_ZERO, !set);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // INC
+            // ----------------------------------------
+
+            OpCode::INC1 => {
+                let value: u16 = self.reg_read(op.rd()) as u16 + 1;
+                self.reg_write(op.rd(), (value & 0xFF) as u8);
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0x100) != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x80) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (value & 0xFF) == 0x80);
+                Ok(())
+            }
+
+            OpCode::INC2 => {
+                let value: u32 = self.reg_read2(op.rd()) as u32 + 1;
+                self.reg_write2(op.rd(), (value & 0xFFFF) as u16);
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0x10000) != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x8000) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (value & 0xFFFF) == 0x8000);
+                Ok(())
+            }
+
+            OpCode::INC3 => {
+                let mut value: u32 = self.reg_read3(op.rd()).into();
+                value += 1;
+                self.reg_write3(op.rd(), U24::new(value));
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFFFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0x1000000) != 0);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x800000) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (value & 0xFFFFFF) == 0x800000);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // DEC
+            // ----------------------------------------
+
+            OpCode::DEC1 => {
+                let value: u16 = (self.reg_read(op.rd()) as u16).wrapping_sub(1);
+                self.reg_write(op.rd(), (value & 0xFF) as u8);
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0xFF) == 0xFF);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x80) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (value & 0xFF) == 0x7F);
+                Ok(())
+            }
+
+            OpCode::DEC2 => {
+                let value: u32 = (self.reg_read2(op.rd()) as u32).wrapping_sub(1);
+                self.reg_write2(op.rd(), (value & 0xFFFF) as u16);
+                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFF) == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0xFFFF) == 0xFFFF);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x8000) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, (value & 0xFFFF) == 0x7FFF);
+                Ok(())
+            }
+
+            OpCode::DEC3 => {
+                let value = self.reg_read3(op.rd()) - 1;
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, (value & 0xFFFFFF) == 0xFFFFFF);
+                self.flag_write(Cpu::FLAG_NEGATIVE, (value & 0x800000) != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, value == 0x7FFFFF);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // JMP
+            // ----------------------------------------
+
+            OpCode::JMP => {
+                self.pc = U24::new(
+                    op.operands[0] as u32 |
+                    (op.operands[1] as u32) << 8 |
+                    (op.operands[2] as u32) << 16);
+                Ok(())
+            },
+
+            OpCode::JZ => {
+                if self.flag_read(Cpu::FLAG_ZERO) {
+                    self.pc = U24::new(
+                    op.operands[0] as u32 |
+                    (op.operands[1] as u32) << 8 |
+                    (op.operands[2] as u32) << 16);
+                }
+                Ok(())
+            },
+
+            OpCode::JLT => {
+                if self.flag_read(Cpu::FLAG_NEGATIVE) != self.flag_read(Cpu::FLAG_OVERFLOW) {
+                    self.pc = op.read_op3(0);
+                }
+                Ok(())
+            },
+
+            OpCode::JGE => {
+                if self.flag_read(Cpu::FLAG_NEGATIVE) == self.flag_read(Cpu::FLAG_OVERFLOW) {
+                    self.pc = op.read_op3(0);
+                }
+                Ok(())
+            },
+
+            OpCode::JGT => {
+                if !self.flag_read(Cpu::FLAG_ZERO)
+                    && self.flag_read(Cpu::FLAG_NEGATIVE) == self.flag_read(Cpu::FLAG_OVERFLOW) {
+                    self.pc = op.read_op3(0);
+                }
+                Ok(())
+            },
+
+            OpCode::JLE => {
+                if self.flag_read(Cpu::FLAG_ZERO)
+                    || self.flag_read(Cpu::FLAG_NEGATIVE) != self.flag_read(Cpu::FLAG_OVERFLOW) {
+                    self.pc = op.read_op3(0);
+                }
+                Ok(())
+            },
+
+            OpCode::JSR => {
+                self.push3(self.pc);
+                self.pc = op.read_op3(0);
+                Ok(())
+            },
+
+            OpCode::JSRR => {
+                self.push3(self.pc);
+                self.pc = self.reg_read3(op.rs());
+                Ok(())
+            },
+
+            OpCode::JTAB => {
+                let entry = op.read_op3(1) + self.reg_read(op.rs()) as u32 * 3;
+                self.pc = self.mem_read3(entry);
+                Ok(())
+            },
+
+            // ----------------------------------------
+            // Relative branches
+            // ----------------------------------------
+
+            OpCode::BRA1 => {
+                self.pc += op.read_op(0) as i8 as u32;
+                Ok(())
+            },
+
+            OpCode::BRA2 => {
+                self.pc += op.read_op2(0) as i16 as u32;
+                Ok(())
+            },
+
+            OpCode::BZ1 => {
+                if self.flag_read(Cpu::FLAG_ZERO) {
+                    self.pc += op.read_op(0) as i8 as u32;
+                }
+                Ok(())
+            },
+
+            OpCode::BZ2 => {
+                if self.flag_read(Cpu::FLAG_ZERO) {
+                    self.pc += op.read_op2(0) as i16 as u32;
+                }
+                Ok(())
+            },
+
+            OpCode::BNZ1 => {
+                if !self.flag_read(Cpu::FLAG_ZERO) {
+                    self.pc += op.read_op(0) as i8 as u32;
+                }
+                Ok(())
+            },
+
+            OpCode::BNZ2 => {
+                if !self.flag_read(Cpu::FLAG_ZERO) {
+                    self.pc += op.read_op2(0) as i16 as u32;
+                }
                 Ok(())
             },
 
-            OpCode::LOADI2 => {
-                let imm: u16 = op.read_op2(1);
-                self.reg_write2(op.rd(), imm);
-                self.flag_write(Cpu::FLAG_ZERO, imm == 0);
-                self.flag_write(Cpu::FLAG_CARRY, false);
+            OpCode::BC1 => {
+                if self.flag_read(Cpu::FLAG_CARRY) {
+                    self.pc += op.read_op(0) as i8 as u32;
+                }
                 Ok(())
             },
 
-            OpCode::LOADI3 => {                
-                let imm: U24 = op.read_op3(1);
-                self.reg_write3(op.rd(), imm);
-                self.flag_write(Cpu::FLAG_ZERO, imm == 0);
-                self.flag_write(Cpu::FLAG_CARRY, false);
+            OpCode::BC2 => {
+                if self.flag_read(Cpu::FLAG_CARRY) {
+                    self.pc += op.read_op2(0) as i16 as u32;
+                }
+                Ok(())
+            },
+
+            OpCode::BNC1 => {
+                if !self.flag_read(Cpu::FLAG_CARRY) {
+                    self.pc += op.read_op(0) as i8 as u32;
+                }
+                Ok(())
+            },
+
+            OpCode::BNC2 => {
+                if !self.flag_read(Cpu::FLAG_CARRY) {
+                    self.pc += op.read_op2(0) as i16 as u32;
+                }
                 Ok(())
             },
 
             // ----------------------------------------
-            // ADDI
+            // LOAD / STORE (register indirect)
             // ----------------------------------------
 
-            OpCode::ADDI1 => {
-                let value: u16 = self.reg_read(op.rd()) as u16 + op.read_op(1) as u16;
-                self.reg_write(op.rd(), (value & 0xFF) as u8);
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0x100) != 0);
+            OpCode::LOADR1 => {
+                let value: u8 = self.mem_read(self.reg_read3(op.rs()));
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
-            OpCode::ADDI2 => {
-                let value: u32 = self.reg_read2(op.rd()) as u32 + op.read_op2(1) as u32;
-                self.reg_write2(op.rd(), (value & 0xFFFF) as u16);
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0x10000) != 0);
+            OpCode::LOADR2 => {
+                let value: u16 = self.mem_read2(self.reg_read3(op.rs()));
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
-            OpCode::ADDI3 => {
-                let mut value: u32 = self.reg_read3(op.rd()).into();
-                value += op.read_op3(1).as_u32();
-                self.reg_write3(op.rd(), U24::new(value));
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFFFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0x1000000) != 0);
+            OpCode::LOADR3 => {
+                let value: U24 = self.mem_read3(self.reg_read3(op.rs()));
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::STORER1 => {
+                self.mem_write(self.reg_read3(op.rd()), self.reg_read(op.rs()));
+                Ok(())
+            }
+
+            OpCode::STORER2 => {
+                self.mem_write2(self.reg_read3(op.rd()), self.reg_read2(op.rs()));
+                Ok(())
+            }
+
+            OpCode::STORER3 => {
+                self.mem_write3(self.reg_read3(op.rd()), self.reg_read3(op.rs()));
                 Ok(())
             }
 
             // ----------------------------------------
-            // INC
+            // TAS
             // ----------------------------------------
 
-            OpCode::INC1 => {
-                let value: u16 = self.reg_read(op.rd()) as u16 + 1;
-                self.reg_write(op.rd(), (value & 0xFF) as u8);
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0x100) != 0);
+            OpCode::TAS => {
+                // The read and write form one indivisible bus operation.
+                let addr = self.reg_read3(op.rs());
+                let value: u8 = self.mem_read(addr);
+                self.mem_write(addr, 0xFF);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
-            OpCode::INC2 => {
-                let value: u32 = self.reg_read2(op.rd()) as u32 + 1;
-                self.reg_write2(op.rd(), (value & 0xFFFF) as u16);
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0x10000) != 0);
+            // ----------------------------------------
+            // LOAD / STORE (indexed)
+            // ----------------------------------------
+
+            OpCode::LOADX1 => {
+                let addr: U24 = self.reg_read3(op.rs()) + op.read_op2(1) as i16 as u32;
+                let value: u8 = self.mem_read(addr);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
-            OpCode::INC3 => {
-                let mut value: u32 = self.reg_read3(op.rd()).into();
-                value += 1;
-                self.reg_write3(op.rd(), U24::new(value));
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFFFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0x1000000) != 0);
+            OpCode::LOADX2 => {
+                let addr: U24 = self.reg_read3(op.rs()) + op.read_op2(1) as i16 as u32;
+                let value: u16 = self.mem_read2(addr);
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::LOADX3 => {
+                let addr: U24 = self.reg_read3(op.rs()) + op.read_op2(1) as i16 as u32;
+                let value: U24 = self.mem_read3(addr);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::STOREX1 => {
+                let addr: U24 = self.reg_read3(op.rd()) + op.read_op2(1) as i16 as u32;
+                self.mem_write(addr, self.reg_read(op.rs()));
+                Ok(())
+            }
+
+            OpCode::STOREX2 => {
+                let addr: U24 = self.reg_read3(op.rd()) + op.read_op2(1) as i16 as u32;
+                self.mem_write2(addr, self.reg_read2(op.rs()));
+                Ok(())
+            }
+
+            OpCode::STOREX3 => {
+                let addr: U24 = self.reg_read3(op.rd()) + op.read_op2(1) as i16 as u32;
+                self.mem_write3(addr, self.reg_read3(op.rs()));
                 Ok(())
             }
 
             // ----------------------------------------
-            // DEC
+            // LOAD (post-increment) / STORE (pre-decrement)
             // ----------------------------------------
 
-            OpCode::DEC1 => {
-                let value: u16 = self.reg_read(op.rd()) as u16 - 1;
-                self.reg_write(op.rd(), (value & 0xFF) as u8);
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0xFF) == 0xFF);
+            OpCode::LOADPI1 => {
+                let addr: U24 = self.reg_read3(op.rs());
+                self.reg_write3(op.rs(), addr + 1);
+                let value: u8 = self.mem_read(addr);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
-            OpCode::DEC2 => {
-                let value: u32 = self.reg_read2(op.rd()) as u32 - 1;
-                self.reg_write2(op.rd(), (value & 0xFFFF) as u16);
-                self.flag_write(Cpu::FLAG_ZERO, (value & 0xFFFF) == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0xFFFF) == 0xFFFF);
+            OpCode::LOADPI2 => {
+                let addr: U24 = self.reg_read3(op.rs());
+                self.reg_write3(op.rs(), addr + 2);
+                let value: u16 = self.mem_read2(addr);
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
             }
 
-            OpCode::DEC3 => {
-                let value = self.reg_read3(op.rd()) - 1;
+            OpCode::LOADPI3 => {
+                let addr: U24 = self.reg_read3(op.rs());
+                self.reg_write3(op.rs(), addr + 3);
+                let value: U24 = self.mem_read3(addr);
                 self.reg_write3(op.rd(), value);
                 self.flag_write(Cpu::FLAG_ZERO, value == 0);
-                self.flag_write(Cpu::FLAG_CARRY, (value & 0xFFFFFF) == 0xFFFFFF);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::STOREPD1 => {
+                let value = self.reg_read(op.rs());
+                let addr: U24 = self.reg_read3(op.rd()) - 1;
+                self.reg_write3(op.rd(), addr);
+                self.mem_write(addr, value);
+                Ok(())
+            }
+
+            OpCode::STOREPD2 => {
+                let value = self.reg_read2(op.rs());
+                let addr: U24 = self.reg_read3(op.rd()) - 2;
+                self.reg_write3(op.rd(), addr);
+                self.mem_write2(addr, value);
+                Ok(())
+            }
+
+            OpCode::STOREPD3 => {
+                let value = self.reg_read3(op.rs());
+                let addr: U24 = self.reg_read3(op.rd()) - 3;
+                self.reg_write3(op.rd(), addr);
+                self.mem_write3(addr, value);
                 Ok(())
             }
 
             // ----------------------------------------
-            // JMP
+            // LOAD / STORE (stack relative)
             // ----------------------------------------
 
-            OpCode::JMP => {
-                self.pc = U24::new(
-                    op.operands[0] as u32 |
-                    (op.operands[1] as u32) << 8 |
-                    (op.operands[2] as u32) << 16);
+            OpCode::LOADS1 => {
+                let value: u8 = self.mem_read(self.sp + op.read_op(1) as u32);
+                self.reg_write(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x80 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
                 Ok(())
-            },
+            }
 
-            OpCode::JZ => {
-                if self.flag_read(Cpu::FLAG_ZERO) {
-                    self.pc = U24::new(
-                    op.operands[0] as u32 |
-                    (op.operands[1] as u32) << 8 |
-                    (op.operands[2] as u32) << 16);
+            OpCode::LOADS2 => {
+                let value: u16 = self.mem_read2(self.sp + op.read_op(1) as u32);
+                self.reg_write2(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x8000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::LOADS3 => {
+                let value: U24 = self.mem_read3(self.sp + op.read_op(1) as u32);
+                self.reg_write3(op.rd(), value);
+                self.flag_write(Cpu::FLAG_ZERO, value == 0);
+                self.flag_write(Cpu::FLAG_CARRY, false);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::STORES1 => {
+                self.mem_write(self.sp + op.read_op(1) as u32, self.reg_read(op.rs()));
+                Ok(())
+            }
+
+            OpCode::STORES2 => {
+                self.mem_write2(self.sp + op.read_op(1) as u32, self.reg_read2(op.rs()));
+                Ok(())
+            }
+
+            OpCode::STORES3 => {
+                self.mem_write3(self.sp + op.read_op(1) as u32, self.reg_read3(op.rs()));
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // ENTER
+            // ----------------------------------------
+
+            OpCode::ENTER => {
+                self.push3(self.fp);
+                self.fp = self.sp;
+                self.sp -= op.read_op2(0) as u32;
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // MAC
+            // ----------------------------------------
+
+            OpCode::MAC => {
+                let rt = op.read_op(1) >> 4;
+                let product: u64 = self.reg_read2(op.rs()) as u64 * self.reg_read2(rt) as u64;
+                let value: u64 = self.reg_read3(op.rd()).as_u32() as u64 + product;
+                self.reg_write3(op.rd(), U24::new(value as u32));
+                self.flag_write(Cpu::FLAG_ZERO, value & 0xFFFFFF == 0);
+                self.flag_write(Cpu::FLAG_CARRY, value > 0xFFFFFF);
+                self.flag_write(Cpu::FLAG_NEGATIVE, value & 0x800000 != 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            // ----------------------------------------
+            // MEMCPY
+            // ----------------------------------------
+
+            OpCode::MEMCPY => {
+                // Copy one byte per execution and re-run the instruction until
+                // the count is exhausted, so all progress lives in registers.
+                let rc = op.read_op(1) >> 4;
+                let count = self.reg_read2(rc);
+                if count != 0 {
+                    let src = self.reg_read3(op.rs());
+                    let dst = self.reg_read3(op.rd());
+                    let byte = self.mem_read(src);
+                    self.mem_write(dst, byte);
+                    self.reg_write3(op.rs(), src + 1);
+                    self.reg_write3(op.rd(), dst + 1);
+                    self.reg_write2(rc, count - 1);
+                    if count > 1 {
+                        self.pc -= 4;
+                    }
                 }
                 Ok(())
-            },
+            }
 
             // ----------------------------------------
             // STORE
@@ -513,36 +2417,474 @@ impl Cpu {
                 Ok(())
             }
 
-            _ => {
-                panic!("OpCode not implemented")
+            OpCode::LEA => {
+                self.reg_write3(op.rd(), op.read_op3(1));
+                Ok(())
             }
 
+            #[cfg(feature = "fpu")]
+            _ if op.code.is_fpu() => self.execute_fpu(op),
+
+            _ => Err(ErrorKind::UnimplementedOpcode(self.ir)),
+
+        }
+    }
+
+    /// Execute a single instruction (or service a pending interrupt
+    /// and run the first instruction of its handler) and report what
+    /// happened. Hosts such as debuggers can call this directly instead
+    /// of `run`; after a HLT `is_running` is false.
+    ///
+    /// Devices on the bus are ticked afterwards, whatever the result. If
+    /// one of them has asked to power off, `is_running` is cleared.
+    pub fn step(&mut self) -> Result<StepResult, CpuError> {
+        let result = self.step_instruction();
+        self.perf.cycles += 1;
+        self.device_lines = self.bus.tick(1);
+        if self.bus.power_off().is_some() {
+            self.is_running = false;
         }
+        result
     }
 
-    /// Execute a single tick (clock cycle) for this
-    /// CPU.
-    fn tick(&mut self) -> Result<(), CpuError> {
+    fn step_instruction(&mut self) -> Result<StepResult, CpuError> {
+        if self.irq_pending() {
+            self.is_waiting = false;
+            if self.flag_read(Cpu::FLAG_INTERRUPT) {
+                let vector = match self.irq.take() {
+                    Some(vector) => vector,
+                    None => {
+                        let line = (self.irq_lines | self.device_lines).trailing_zeros() as u8;
+                        self.irq_lines &= !(1 << line);
+                        line
+                    }
+                };
+                self.interrupt(vector);
+            }
+        }
+
+        if self.is_waiting {
+            return Ok(StepResult::Waiting);
+        }
+
+        // The hook is taken out while it runs so it can borrow the CPU.
+        if let Some(mut hook) = self.pre_hook.take() {
+            hook(self);
+            self.pre_hook.get_or_insert(hook);
+        }
+
+        self.ic += 1;
+        let pc = self.pc;
+        self.op_pc = pc;
+        self.watch_hit = None;
+        self.bad_reg.set(None);
         self.fetch();
-        let op = self.decode()?;
-        self.execute(op)?;
-        Ok(())
+        let op = match self.decode() {
+            Ok(op) => op,
+            Err(ErrorKind::InvalidOpCode(code)) => match (self.illegal_trap, self.decode_mode) {
+                (Some(handler), _) => {
+                    self.trap_illegal(handler);
+                    return Ok(StepResult::Trapped(code));
+                }
+                (None, DecodeMode::Permissive) => {
+                    self.pc += ((code & 0xE00) >> 9) as u32;
+                    self.skipped_ops += 1;
+                    return Ok(StepResult::Skipped(code));
+                }
+                (None, DecodeMode::Strict) => return Err(self.fault(ErrorKind::InvalidOpCode(code), pc)),
+            },
+            Err(kind) => return Err(self.fault(kind, pc)),
+        };
+        trace!("{:06X} {:?} {:02X?}", pc, op.code, op.operands);
+        let next = self.pc;
+        let result = self.execute(op.clone());
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry { pc, op: op.clone(), flags: self.flags });
+        }
+        if let Err(kind) = result {
+            return Err(self.fault(kind, pc));
+        }
+        if let Some(reg) = self.bad_reg.take() {
+            return Err(self.fault(ErrorKind::InvalidRegister(reg), pc));
+        }
+        match self.bus.take_fault() {
+            Some(BusFault::OutOfBounds(addr)) => Err(self.fault(ErrorKind::MemoryFault { addr }, pc)),
+            Some(BusFault::WriteProtected(addr)) => Err(self.fault(ErrorKind::WriteProtected(addr), pc)),
+            Some(BusFault::Uninitialized(addr)) => Err(self.fault(ErrorKind::UninitializedRead(addr), pc)),
+            None => {
+                self.perf.instructions += 1;
+                // MEMCPY re-runs itself by rewinding PC; that isn't a branch
+                if self.pc != next && !matches!(op.code, OpCode::MEMCPY) {
+                    self.perf.branches += 1;
+                }
+                if let Some(mut hook) = self.post_hook.take() {
+                    hook(self, &op);
+                    self.post_hook.get_or_insert(hook);
+                }
+                match self.watch_hit {
+                    Some(hit) => Ok(StepResult::Watchpoint(op, hit)),
+                    None => Ok(StepResult::Executed(op)),
+                }
+            }
+        }
+    }
+
+    /// Attach the current IC and IR to an error from the instruction at `pc`.
+    fn fault(&self, kind: ErrorKind, pc: U24) -> CpuError {
+        let err = CpuError { kind, pc, ic: self.ic, ir: self.ir };
+        debug!("{}", err);
+        err
+    }
+
+    /// Enter the illegal-instruction trap. Pushes PC (the address after the
+    /// opcode, where any operands start) and then IR, leaving
+    /// `(SP+1..SP+2)` = IR and `(SP+3..SP+5)` = PC, and jumps to the trap
+    /// handler. Flags are left for the handler to save if it needs to.
+    fn trap_illegal(&mut self, handler: U24) {
+        let [low, high] = self.ir.to_le_bytes();
+        self.push3(self.pc);
+        self.push(high);
+        self.push(low);
+        self.pc = handler;
     }
 
     pub fn halt(&mut self) {
-        println!("CPU halted!");
+        debug!("CPU halted at {:06X}", self.pc);
         self.is_running = false;
     }
 
-    /// Run the CPU until a HLT instruction is reached
-    /// or an error occurs, starting at the current PC.
-    pub fn run(&mut self) -> Result<(), CpuError> {
+    /// Run the CPU, starting at the current PC, until a HLT instruction
+    /// is reached or something else stops it, and report why.
+    ///
+    /// If the CPU executes WAIT with no interrupt raised and there are no
+    /// devices on the bus, nothing else can raise one while it runs, so
+    /// control returns to the caller with `StopReason::Waiting` rather
+    /// than spinning. Call `raise_interrupt` and then `run` again to
+    /// resume. With devices, the run keeps ticking them until one
+    /// interrupts.
+    pub fn run(&mut self) -> StopReason {
         self.ic = U24::new(0);
+        self.run_with(None, |_| false).reason
+    }
+
+    /// Run at most `budget` instructions, starting at the current PC, and
+    /// report how many ran and why execution stopped. Intended for hosts
+    /// that run the CPU in slices, e.g. once per frame; unlike `run` the
+    /// instruction counter is not reset.
+    pub fn run_for(&mut self, budget: u64) -> RunOutcome {
+        self.run_with(Some(budget), |_| false)
+    }
+
+    /// Run until PC reaches `addr` (checked after each instruction, so at
+    /// least one instruction runs), or until anything else that stops `run`.
+    pub fn run_until(&mut self, addr: U24) -> RunOutcome {
+        self.run_with(None, |cpu| cpu.pc == addr)
+    }
+
+    /// Run until `predicate` returns true for the CPU state after an
+    /// instruction, or until anything else that stops `run`.
+    pub fn run_until_with<F: FnMut(&Self) -> bool>(&mut self, predicate: F) -> RunOutcome {
+        self.run_with(None, predicate)
+    }
+
+    fn run_with<F: FnMut(&Self) -> bool>(&mut self, budget: Option<u64>, mut stop: F) -> RunOutcome {
         self.is_running = true;
-        while self.is_running {
-            self.tick()?;
-            self.ic += 1;
+        let mut ticks = 0;
+        while budget.is_none_or(|budget| ticks < budget) {
+            if self.is_waiting && !self.irq_pending() && !self.bus.has_devices() {
+                return RunOutcome { ticks, reason: StopReason::Waiting };
+            }
+            if ticks > 0 && self.breakpoints.contains(&self.pc) {
+                return RunOutcome { ticks, reason: StopReason::Breakpoint(self.pc) };
+            }
+            let halted = match self.step() {
+                Err(err) => return RunOutcome { ticks, reason: StopReason::Error(err) },
+                Ok(StepResult::Watchpoint(_, hit)) => {
+                    return RunOutcome { ticks: ticks + 1, reason: StopReason::Watchpoint(hit) };
+                }
+                Ok(StepResult::Executed(op)) => matches!(op.code, OpCode::HLT),
+                Ok(_) => false,
+            };
+            ticks += 1;
+            if !self.is_running {
+                let reason = match self.bus.power_off() {
+                    Some(code) => StopReason::PowerOff(code),
+                    None if halted => StopReason::Halted,
+                    None => StopReason::Stopped,
+                };
+                return RunOutcome { ticks, reason };
+            }
+            if stop(self) {
+                return RunOutcome { ticks, reason: StopReason::Reached };
+            }
+        }
+        RunOutcome { ticks, reason: StopReason::Budget }
+    }
+}
+
+/// Shift `value` left by `count` within a `bits`-wide register, returning
+/// the result and the last bit shifted out.
+fn shift_left(value: u32, count: u32, bits: u32) -> (u32, bool) {
+    let wide: u64 = (value as u64) << count.min(32);
+    ((wide & ((1 << bits) - 1)) as u32, wide & (1 << bits) != 0)
+}
+
+/// Logical shift `value` right by `count`, returning the result and the
+/// last bit shifted out.
+fn shift_right(value: u32, count: u32) -> (u32, bool) {
+    let carry = count > 0 && value.checked_shr(count - 1).unwrap_or(0) & 1 != 0;
+    (value.checked_shr(count).unwrap_or(0), carry)
+}
+
+/// Rotate `value` left by `count` within a `bits`-wide register, returning
+/// the result and the last bit rotated round (now bit 0).
+fn rotate_left(value: u32, count: u32, bits: u32) -> (u32, bool) {
+    let count = count % bits;
+    let result = ((value << count) | (value >> (bits - count))) & ((1 << bits) - 1);
+    (result, count != 0 && result & 1 != 0)
+}
+
+/// Rotate `value` right by `count` within a `bits`-wide register, returning
+/// the result and the last bit rotated round (now the top bit).
+fn rotate_right(value: u32, count: u32, bits: u32) -> (u32, bool) {
+    let count = count % bits;
+    let result = ((value >> count) | (value << (bits - count))) & ((1 << bits) - 1);
+    (result, count != 0 && result & (1 << (bits - 1)) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RTI: [u8; 2] = [0x1C, 0x00];
+    const LOADI1_R1_42: [u8; 4] = [0x01, 0x04, 0x10, 0x2A];
+    const HLT: [u8; 2] = [0x04, 0x00];
+
+    /// `code` followed by its operand bytes.
+    fn encode(code: OpCode, operands: &[u8]) -> Vec<u8> {
+        let mut bytes = (code as u16).to_le_bytes().to_vec();
+        bytes.extend(operands);
+        bytes
+    }
+
+    /// A CPU with `program` loaded at 0.
+    fn cpu_with_program(program: &[Vec<u8>]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.bus.load(U24::new(0), &program.concat());
+        cpu
+    }
+
+    /// A CPU with interrupts enabled and the carry flag set, whose vector
+    /// 5 points at `handler`, placed at 0x0100.
+    fn cpu_with_handler(handler: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.vector_base = U24::new(0x0300);
+        cpu.mem_write3(U24::new(0x0300 + 5 * 3), U24::new(0x0100));
+        cpu.bus.load(U24::new(0x0100), handler);
+        cpu.bus.load(U24::new(0x0000), &HLT);
+        cpu.flags = Cpu::FLAG_INTERRUPT | Cpu::FLAG_CARRY;
+        cpu
+    }
+
+    #[test]
+    fn interrupt_entry_pushes_frame_and_masks() {
+        let mut cpu = cpu_with_handler(&RTI);
+        cpu.pc = U24::new(0x0042);
+        let sp = cpu.sp;
+
+        cpu.interrupt(5);
+        assert_eq!(cpu.pc, U24::new(0x0100));
+        assert_eq!(cpu.sp, sp - 4);
+        assert!(!cpu.flag_read(Cpu::FLAG_INTERRUPT));
+        assert_eq!(cpu.mem_read(cpu.sp + 1), Cpu::FLAG_INTERRUPT | Cpu::FLAG_CARRY);
+        assert_eq!(cpu.mem_read3(cpu.sp + 2), U24::new(0x0042));
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, U24::new(0x0042));
+        assert_eq!(cpu.sp, sp);
+        assert_eq!(cpu.flags, Cpu::FLAG_INTERRUPT | Cpu::FLAG_CARRY);
+    }
+
+    #[test]
+    fn raised_irq_runs_handler_and_rti_returns() {
+        let mut handler = LOADI1_R1_42.to_vec();
+        handler.extend(RTI);
+        let mut cpu = cpu_with_handler(&handler);
+        let sp = cpu.sp;
+
+        cpu.raise_irq(5);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(1), 42);
+        assert!(!cpu.flag_read(Cpu::FLAG_INTERRUPT));
+        assert!(!cpu.irq_pending());
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, U24::new(0x0000));
+        assert_eq!(cpu.sp, sp);
+        assert_eq!(cpu.flags, Cpu::FLAG_INTERRUPT | Cpu::FLAG_CARRY);
+
+        assert_eq!(cpu.run(), StopReason::Halted);
+    }
+
+    #[test]
+    fn register_past_the_end_is_an_error() {
+        // ADD.3 R7, R0: R7 has only two bytes behind it
+        let mut cpu = Cpu::new();
+        cpu.bus.load(U24::new(0), &[0x03, 0x02, 0x70]);
+        let err = cpu.step().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidRegister(7));
+        assert_eq!(err.pc, U24::new(0));
+        assert_eq!(cpu.regs, [0; 9]);
+    }
+
+    #[test]
+    fn mul_keeps_the_low_bits_and_sets_carry_on_overflow() {
+        let mut cpu = cpu_with_program(&[
+            encode(OpCode::MUL1, &[0x01]),
+            encode(OpCode::MUL1, &[0x01]),
+            encode(OpCode::MUL2, &[0x02]),
+            encode(OpCode::MUL3, &[0x03]),
+        ]);
+
+        // MUL.1 R0, R1
+        cpu.reg_write(0, 6);
+        cpu.reg_write(1, 7);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 42);
+        assert_eq!(cpu.flags, 0);
+
+        cpu.reg_write(0, 0x10);
+        cpu.reg_write(1, 0x10);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0);
+        assert_eq!(cpu.flags, Cpu::FLAG_ZERO | Cpu::FLAG_CARRY);
+
+        // MUL.2 R0, R2: 0x8001 * 3 = 0x18003
+        cpu.reg_write2(0, 0x8001);
+        cpu.reg_write2(2, 3);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read2(0), 0x8003);
+        assert_eq!(cpu.flags, Cpu::FLAG_CARRY | Cpu::FLAG_NEGATIVE);
+
+        // MUL.3 R0, R3: 0x1000 * 0x1000 = 0x1000000
+        cpu.reg_write3(0, U24::new(0x1000));
+        cpu.reg_write3(3, U24::new(0x1000));
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read3(0), U24::new(0));
+        assert_eq!(cpu.flags, Cpu::FLAG_ZERO | Cpu::FLAG_CARRY);
+    }
+
+    #[test]
+    fn add_and_sub_set_carry_and_overflow() {
+        let mut cpu = cpu_with_program(&[
+            encode(OpCode::ADD1, &[0x01]),
+            encode(OpCode::ADD1, &[0x01]),
+            encode(OpCode::SUB1, &[0x01]),
+            encode(OpCode::SUB1, &[0x01]),
+        ]);
+
+        // ADD.1 R0, R1: 0x7F + 1 overflows into the sign bit
+        cpu.reg_write(0, 0x7F);
+        cpu.reg_write(1, 0x01);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0x80);
+        assert_eq!(cpu.flags, Cpu::FLAG_NEGATIVE | Cpu::FLAG_OVERFLOW);
+
+        // 0xFF + 1 carries out
+        cpu.reg_write(0, 0xFF);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0);
+        assert_eq!(cpu.flags, Cpu::FLAG_ZERO | Cpu::FLAG_CARRY);
+
+        // SUB.1 R0, R1: 0 - 1 borrows
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0xFF);
+        assert_eq!(cpu.flags, Cpu::FLAG_CARRY | Cpu::FLAG_NEGATIVE);
+
+        // 0x80 - 1 overflows out of the sign bit
+        cpu.reg_write(0, 0x80);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0x7F);
+        assert_eq!(cpu.flags, Cpu::FLAG_OVERFLOW);
+    }
+
+    #[test]
+    fn daa_adjusts_a_decimal_add() {
+        let mut cpu = cpu_with_program(&[
+            encode(OpCode::SED, &[]),
+            encode(OpCode::ADD1, &[0x01]),
+            encode(OpCode::DAA, &[0x00]),
+            encode(OpCode::ADD1, &[0x01]),
+            encode(OpCode::DAA, &[0x00]),
+        ]);
+        cpu.step().unwrap();
+
+        // 38 + 29 = 67: the low digits carry into the high ones
+        cpu.reg_write(0, 0x38);
+        cpu.reg_write(1, 0x29);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0x61);
+        assert!(cpu.flag_read(Cpu::FLAG_OVERFLOW));
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0x67);
+        assert!(!cpu.flag_read(Cpu::FLAG_CARRY));
+
+        // 99 + 1 = 100: the result wraps to 00 with a carry
+        cpu.reg_write(0, 0x99);
+        cpu.reg_write(1, 0x01);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0x00);
+        assert_eq!(cpu.flags, Cpu::FLAG_DECIMAL | Cpu::FLAG_ZERO | Cpu::FLAG_CARRY);
+    }
+
+    #[test]
+    fn memcpy_copies_one_byte_per_step_and_reruns_itself() {
+        // MEMCPY R0, R3, R6
+        let mut cpu = cpu_with_program(&[encode(OpCode::MEMCPY, &[0x03, 0x60]), HLT.to_vec()]);
+        cpu.bus.load(U24::new(0x0100), &[1, 2, 3]);
+        cpu.reg_write3(0, U24::new(0x0200));
+        cpu.reg_write3(3, U24::new(0x0100));
+        cpu.reg_write2(6, 3);
+
+        for copied in 1..=3 {
+            cpu.step().unwrap();
+            assert_eq!(cpu.reg_read2(6), 3 - copied);
+            assert_eq!(cpu.reg_read3(0), U24::new(0x0200 + copied as u32));
+            assert_eq!(cpu.reg_read3(3), U24::new(0x0100 + copied as u32));
+            // Back on the MEMCPY until the last byte, then past it
+            assert_eq!(cpu.pc, U24::new(if copied < 3 { 0 } else { 4 }));
         }
-        Ok(())
+        assert_eq!(cpu.mem_read3(U24::new(0x0200)), U24::from_le_bytes([1, 2, 3]));
+        assert_eq!(cpu.run(), StopReason::Halted);
+    }
+
+    #[test]
+    fn cmov_moves_only_when_the_flag_matches() {
+        // CMOVZ.1 R0, R1 / CMOVNZ.1 R0, R1 / CMOVC.1 R0, R1
+        let mut cpu = cpu_with_program(&[
+            encode(OpCode::CMOVZ1, &[0x01]),
+            encode(OpCode::CMOVNZ1, &[0x01]),
+            encode(OpCode::CMOVC1, &[0x01]),
+            encode(OpCode::CMOVZ1, &[0x01]),
+        ]);
+        cpu.reg_write(1, 42);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0);
+
+        cpu.flags = Cpu::FLAG_ZERO;
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 0);
+
+        cpu.flags = Cpu::FLAG_CARRY;
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 42);
+
+        cpu.reg_write(1, 7);
+        cpu.flags = Cpu::FLAG_ZERO;
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg_read(0), 7);
+        assert_eq!(cpu.flags, Cpu::FLAG_ZERO);
     }
-}
\ No newline at end of file
+}
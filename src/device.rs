@@ -0,0 +1,65 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A memory-mapped peripheral. Once mapped into a `MemoryMap`, reads and
+/// writes in its address range are routed to it with the offset from the
+/// start of the range.
+pub trait Device {
+    /// Read the register at `offset`.
+    fn read(&mut self, offset: u32) -> u8;
+
+    /// Write `val` to the register at `offset`.
+    fn write(&mut self, offset: u32, val: u8);
+
+    /// Advance the device by `cycles` CPU cycles. The bus ticks every
+    /// device after each instruction (one cycle per instruction), and
+    /// once per step while the CPU waits.
+    fn tick(&mut self, _cycles: u64) {}
+
+    /// The CPU interrupt line (0-7) the device is asserting, if any. This
+    /// is a level: the CPU keeps seeing the interrupt until the device
+    /// stops asserting it, normally when the handler acknowledges it.
+    fn irq(&self) -> Option<u8> {
+        None
+    }
+
+    /// Take interrupt requests from the devices mapped with
+    /// `MemoryMap::map_device_to_source`, one bit per source that is
+    /// asserting one. Called after every tick in which any is; only an
+    /// interrupt controller does anything with them.
+    fn request(&mut self, _sources: u8) {}
+
+    /// The exit code, if the device has asked for the machine to power
+    /// off. The CPU stops after the step that made the request.
+    fn power_off(&self) -> Option<u8> {
+        None
+    }
+}
+
+/// Lets the host keep a handle to a device after mapping it, e.g. to
+/// raise interrupts on an `InterruptController` or feed input to a UART.
+impl<D: Device> Device for Rc<RefCell<D>> {
+    fn read(&mut self, offset: u32) -> u8 {
+        self.borrow_mut().read(offset)
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        self.borrow_mut().write(offset, val);
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.borrow_mut().tick(cycles);
+    }
+
+    fn irq(&self) -> Option<u8> {
+        self.borrow().irq()
+    }
+
+    fn request(&mut self, sources: u8) {
+        self.borrow_mut().request(sources);
+    }
+
+    fn power_off(&self) -> Option<u8> {
+        self.borrow().power_off()
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::bus::Bus;
+use crate::cpu::Cpu;
+use crate::u24::U24;
+
+/// Differences between two CPUs, from `Cpu::diff`. Each changed value is
+/// given as `(self, other)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuDiff {
+    pub pc: Option<(U24, U24)>,
+
+    /// Changed registers as `(register, self, other)`
+    pub regs: Vec<(u8, u8, u8)>,
+
+    pub flags: Option<(u8, u8)>,
+    pub sp: Option<(U24, U24)>,
+    pub fp: Option<(U24, U24)>,
+
+    /// Runs of differing memory bytes, as offsets into `Bus::dump`
+    /// (addresses, for plain RAM)
+    pub memory: Vec<Range<u32>>,
+}
+
+impl CpuDiff {
+    /// True if nothing differs.
+    pub fn is_empty(&self) -> bool {
+        *self == CpuDiff::default()
+    }
+}
+
+impl fmt::Display for CpuDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((a, b)) = self.pc {
+            writeln!(f, "PC: {:06X} -> {:06X}", a, b)?;
+        }
+        for (reg, a, b) in &self.regs {
+            writeln!(f, "R{}: {:02X} -> {:02X}", reg, a, b)?;
+        }
+        if let Some((a, b)) = self.flags {
+            writeln!(f, "flags: {:02X} -> {:02X}", a, b)?;
+        }
+        if let Some((a, b)) = self.sp {
+            writeln!(f, "SP: {:06X} -> {:06X}", a, b)?;
+        }
+        if let Some((a, b)) = self.fp {
+            writeln!(f, "FP: {:06X} -> {:06X}", a, b)?;
+        }
+        for range in &self.memory {
+            writeln!(f, "memory: {:06X}..{:06X}", range.start, range.end)?;
+        }
+        Ok(())
+    }
+}
+
+fn changed<T: PartialEq + Copy>(a: T, b: T) -> Option<(T, T)> {
+    (a != b).then_some((a, b))
+}
+
+impl<B: Bus> Cpu<B> {
+    /// List the registers, flags and memory that differ between this CPU
+    /// and `other`, e.g. a clone that ran a different path.
+    pub fn diff(&self, other: &Self) -> CpuDiff {
+        let regs = (0..self.regs.len())
+            .filter(|&i| self.regs[i] != other.regs[i])
+            .map(|i| (i as u8, self.regs[i], other.regs[i]))
+            .collect();
+
+        let mut memory: Vec<Range<u32>> = Vec::new();
+        let (a, b) = (self.bus.dump(), other.bus.dump());
+        for i in 0..a.len().max(b.len()) {
+            if a.get(i) == b.get(i) {
+                continue;
+            }
+            let i = i as u32;
+            match memory.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => memory.push(i..i + 1),
+            }
+        }
+
+        CpuDiff {
+            pc: changed(self.pc, other.pc),
+            regs,
+            flags: changed(self.flags, other.flags),
+            sp: changed(self.sp, other.sp),
+            fp: changed(self.fp, other.fp),
+            memory,
+        }
+    }
+}
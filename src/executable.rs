@@ -0,0 +1,286 @@
+use std::fmt;
+
+use crate::cpu::ResetVector;
+use crate::machine::Machine;
+use crate::u24::U24;
+
+/// A program in the rexta executable format (`.rxe`), which unlike a raw
+/// image records where it loads and where it starts. All values are
+/// little-endian:
+///
+/// | Size | Field |
+/// |------|-------|
+/// | 4    | magic: `RXE` and a zero byte |
+/// | 1    | version; see `Executable::VERSION` |
+/// | 3    | entry point |
+/// | 2    | number of sections |
+/// | 2    | number of symbols |
+///
+/// Then each section: its 1-byte kind (see `SectionKind`), 3-byte load
+/// address, 3-byte length and, unless it is `.bss`, its bytes. Then each
+/// symbol: its 3-byte value, 1-byte name length and UTF-8 name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Executable {
+    /// Where execution starts
+    pub entry: U24,
+    pub sections: Vec<Section>,
+    /// Labels, for tools that show addresses by name
+    pub symbols: Vec<Symbol>,
+}
+
+/// Bytes to load at an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub address: U24,
+    /// The contents; all zeros for `.bss`, which the file only records the
+    /// length of
+    pub bytes: Vec<u8>,
+}
+
+/// What a section holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    /// `.text` - code
+    Text = 0,
+    /// `.data` - initialized data
+    Data = 1,
+    /// `.bss` - zero-initialized data
+    Bss = 2,
+}
+
+impl SectionKind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            0 => Some(SectionKind::Text),
+            1 => Some(SectionKind::Data),
+            2 => Some(SectionKind::Bss),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SectionKind::Text => write!(f, ".text"),
+            SectionKind::Data => write!(f, ".data"),
+            SectionKind::Bss => write!(f, ".bss"),
+        }
+    }
+}
+
+/// A named address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: U24,
+}
+
+impl Executable {
+    /// The bytes every executable starts with.
+    pub const MAGIC: [u8; 4] = *b"RXE\0";
+
+    /// The format written by this version of the crate.
+    pub const VERSION: u8 = 2;
+
+    /// Whether `bytes` look like an executable rather than a raw image.
+    pub fn is_executable(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+
+    /// Encode the executable. Fails if there are more than 0xFFFF sections
+    /// or symbols, a section is longer than 0xFFFFFF bytes or a symbol
+    /// name longer than 255 bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ExecutableError> {
+        let sections = u16::try_from(self.sections.len()).map_err(|_| ExecutableError::TooLarge)?;
+        let symbols = u16::try_from(self.symbols.len()).map_err(|_| ExecutableError::TooLarge)?;
+        let mut out = Self::MAGIC.to_vec();
+        out.push(Self::VERSION);
+        out.extend(self.entry.to_le_bytes());
+        out.extend(sections.to_le_bytes());
+        out.extend(symbols.to_le_bytes());
+        for section in &self.sections {
+            if section.bytes.len() > 0xFF_FFFF {
+                return Err(ExecutableError::TooLarge);
+            }
+            out.push(section.kind as u8);
+            out.extend(section.address.to_le_bytes());
+            out.extend(U24::new(section.bytes.len() as u32).to_le_bytes());
+            if section.kind != SectionKind::Bss {
+                out.extend(&section.bytes);
+            }
+        }
+        for symbol in &self.symbols {
+            let len = u8::try_from(symbol.name.len()).map_err(|_| ExecutableError::TooLarge)?;
+            out.extend(symbol.value.to_le_bytes());
+            out.push(len);
+            out.extend(symbol.name.as_bytes());
+        }
+        Ok(out)
+    }
+
+    /// Decode an executable written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ExecutableError> {
+        if !Self::is_executable(bytes) {
+            return Err(ExecutableError::Magic);
+        }
+        let mut reader = Reader { bytes, pos: Self::MAGIC.len() };
+        let version = reader.take(1)?[0];
+        if version != Self::VERSION {
+            return Err(ExecutableError::Version(version));
+        }
+        let entry = reader.u24()?;
+        let sections = u16::from_le_bytes([reader.take(1)?[0], reader.take(1)?[0]]);
+        let symbols = u16::from_le_bytes([reader.take(1)?[0], reader.take(1)?[0]]);
+
+        let mut exe = Executable { entry, sections: Vec::new(), symbols: Vec::new() };
+        for _ in 0..sections {
+            let kind = reader.take(1)?[0];
+            let kind = SectionKind::from_u8(kind).ok_or(ExecutableError::Section(kind))?;
+            let address = reader.u24()?;
+            let len = reader.u24()?.as_u32() as usize;
+            let bytes = match kind {
+                SectionKind::Bss => vec![0; len],
+                _ => reader.take(len)?.to_vec(),
+            };
+            exe.sections.push(Section { kind, address, bytes });
+        }
+        for _ in 0..symbols {
+            let value = reader.u24()?;
+            let len = reader.take(1)?[0] as usize;
+            let name = String::from_utf8(reader.take(len)?.to_vec()).map_err(|_| ExecutableError::Name)?;
+            exe.symbols.push(Symbol { name, value });
+        }
+        Ok(exe)
+    }
+}
+
+/// Reads the fields of an executable in order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ExecutableError> {
+        let bytes = self.bytes.get(self.pos..self.pos + len).ok_or(ExecutableError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u24(&mut self) -> Result<U24, ExecutableError> {
+        let bytes = self.take(3)?;
+        Ok(U24::from_le_bytes([bytes[0], bytes[1], bytes[2]]))
+    }
+}
+
+/// Why an executable could not be read or written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutableError {
+    /// The data does not start with `Executable::MAGIC`.
+    Magic,
+
+    /// The executable was written in a different format version.
+    Version(u8),
+
+    /// The data ends partway through a field.
+    Truncated,
+
+    /// A section has a kind this version doesn't know.
+    Section(u8),
+
+    /// A symbol name is not valid UTF-8.
+    Name,
+
+    /// A count or length does not fit its field.
+    TooLarge,
+}
+
+impl fmt::Display for ExecutableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutableError::Magic => write!(f, "not a rexta executable"),
+            ExecutableError::Version(version) => write!(
+                f,
+                "executable version {} is not supported (expected {})",
+                version,
+                Executable::VERSION
+            ),
+            ExecutableError::Truncated => write!(f, "executable is truncated"),
+            ExecutableError::Section(kind) => write!(f, "executable has a section of unknown kind {}", kind),
+            ExecutableError::Name => write!(f, "executable has a symbol name that is not UTF-8"),
+            ExecutableError::TooLarge => write!(f, "program too large for the executable format"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutableError {}
+
+impl Machine {
+    /// Load every section of `exe`, zero-filling `.bss`, and start at its
+    /// entry point: like `load_boot_rom`, this points the reset vector
+    /// there and resets.
+    pub fn load_executable(&mut self, exe: &Executable) {
+        for section in &exe.sections {
+            self.load(section.address, &section.bytes);
+        }
+        self.cpu.reset_vector = ResetVector::Address(exe.entry);
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Executable {
+        Executable {
+            entry: U24::new(0x1002),
+            sections: vec![
+                Section { kind: SectionKind::Text, address: U24::new(0x1000), bytes: vec![0x04, 0x00, 0x04, 0x00] },
+                Section { kind: SectionKind::Data, address: U24::new(0x2000), bytes: vec![1, 2, 3] },
+                Section { kind: SectionKind::Bss, address: U24::new(0x3000), bytes: vec![0; 0x100] },
+            ],
+            symbols: vec![Symbol { name: "main".to_string(), value: U24::new(0x1002) }],
+        }
+    }
+
+    #[test]
+    fn round_trips_and_bss_has_only_a_length() {
+        let exe = sample();
+        let bytes = exe.to_bytes().unwrap();
+        assert_eq!(Executable::from_bytes(&bytes), Ok(exe));
+
+        // The header, three section headers, the contents of `.text` and
+        // `.data` but not `.bss`, and the symbol
+        assert_eq!(bytes.len(), 12 + 3 * 7 + 4 + 3 + (3 + 1 + 4));
+    }
+
+    #[test]
+    fn bad_data_is_rejected() {
+        let bytes = sample().to_bytes().unwrap();
+        assert_eq!(Executable::from_bytes(b"RXA\0"), Err(ExecutableError::Magic));
+        assert_eq!(Executable::from_bytes(&bytes[..bytes.len() - 1]), Err(ExecutableError::Truncated));
+
+        let mut old = bytes.clone();
+        old[4] = 1;
+        assert_eq!(Executable::from_bytes(&old), Err(ExecutableError::Version(1)));
+
+        // The first section's kind follows the 12-byte header
+        let mut unknown = bytes;
+        unknown[12] = 9;
+        assert_eq!(Executable::from_bytes(&unknown), Err(ExecutableError::Section(9)));
+    }
+
+    #[test]
+    fn load_executable_starts_at_the_entry_point() {
+        let mut machine = Machine::new(0x10000);
+        machine.load(U24::new(0x3010), &[0xAA]);
+        machine.load_executable(&sample());
+        assert_eq!(machine.cpu.reset_vector, ResetVector::Address(U24::new(0x1002)));
+        assert_eq!(machine.cpu.pc, U24::new(0x1002));
+        assert_eq!(machine.cpu.mem_read3(U24::new(0x2000)), U24::from_le_bytes([1, 2, 3]));
+        assert_eq!(machine.cpu.mem_read(U24::new(0x3010)), 0);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::bus::Bus;
+use crate::cpu::{Cpu, ErrorKind};
+use crate::op::{Op, OpCode};
+use crate::u24::U24;
+
+impl<B: Bus> Cpu<B> {
+    /// Read a 32-bit float (little-endian) from memory.
+    fn mem_read_f32(&mut self, addr: U24) -> f32 {
+        let low = self.mem_read2(addr) as u32;
+        let high = self.mem_read2(addr + 2) as u32;
+        f32::from_bits(low | high << 16)
+    }
+
+    /// Write a 32-bit float (little-endian) to memory.
+    fn mem_write_f32(&mut self, addr: crate::u24::U24, val: f32) {
+        let bits = val.to_bits();
+        self.mem_write2(addr, bits as u16);
+        self.mem_write2(addr + 2, (bits >> 16) as u16);
+    }
+
+    /// Set ZERO and NEGATIVE from a floating-point result and clear
+    /// CARRY and OVERFLOW.
+    fn fpu_flags(&mut self, value: f32) {
+        self.flag_write(Cpu::FLAG_ZERO, value == 0.0);
+        self.flag_write(Cpu::FLAG_CARRY, false);
+        self.flag_write(Cpu::FLAG_NEGATIVE, value.is_sign_negative() && value != 0.0);
+        self.flag_write(Cpu::FLAG_OVERFLOW, false);
+    }
+
+    /// Execute an instruction of the floating-point unit.
+    pub(crate) fn execute_fpu(&mut self, op: Op) -> Result<(), ErrorKind> {
+        let fd = op.rd() as usize & 0x07;
+        let fs = op.rs() as usize & 0x07;
+
+        match op.code {
+            OpCode::FLOAD => {
+                self.fregs[fd] = self.mem_read_f32(self.reg_read3(op.rs()));
+                Ok(())
+            }
+
+            OpCode::FSTORE => {
+                self.mem_write_f32(self.reg_read3(op.rd()), self.fregs[fs]);
+                Ok(())
+            }
+
+            OpCode::FITOF => {
+                self.fregs[fd] = self.reg_read2(op.rs()) as i16 as f32;
+                Ok(())
+            }
+
+            OpCode::FFTOI => {
+                // Truncate towards zero, saturating out-of-range values.
+                // NaN has no integer value at all.
+                let value = self.fregs[fs];
+                if value.is_nan() {
+                    return Err(ErrorKind::ArithmeticFault);
+                }
+                let int = value as i16;
+                self.reg_write2(op.rd(), int as u16);
+                self.flag_write(Cpu::FLAG_ZERO, int == 0);
+                self.flag_write(Cpu::FLAG_CARRY, value.trunc() != int as f32);
+                self.flag_write(Cpu::FLAG_NEGATIVE, int < 0);
+                self.flag_write(Cpu::FLAG_OVERFLOW, false);
+                Ok(())
+            }
+
+            OpCode::FADD => {
+                self.fregs[fd] += self.fregs[fs];
+                self.fpu_flags(self.fregs[fd]);
+                Ok(())
+            }
+
+            OpCode::FSUB => {
+                self.fregs[fd] -= self.fregs[fs];
+                self.fpu_flags(self.fregs[fd]);
+                Ok(())
+            }
+
+            OpCode::FMUL => {
+                self.fregs[fd] *= self.fregs[fs];
+                self.fpu_flags(self.fregs[fd]);
+                Ok(())
+            }
+
+            OpCode::FDIV => {
+                self.fregs[fd] /= self.fregs[fs];
+                self.fpu_flags(self.fregs[fd]);
+                Ok(())
+            }
+
+            _ => Err(ErrorKind::InvalidInstruction),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::device::Device;
+
+/// Prioritised interrupt controller. Up to eight sources raise requests,
+/// either devices mapped with `MemoryMap::map_device_to_source` or the host
+/// calling `raise`; the controller asserts a single CPU interrupt line while
+/// any enabled request is pending, and the handler acknowledges requests
+/// through its memory-mapped registers:
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0      | ENABLE: one bit per source | read/write |
+/// | 1      | PENDING: one bit per source; writing 1 bits clears them | read/write |
+/// | 2      | ACK: reading returns the highest-priority enabled pending source and clears its pending bit, or 0xFF if there is none | read |
+/// | 3-10   | PRIORITY for sources 0-7; higher wins, ties go to the lower source | read/write |
+#[derive(Debug, Clone)]
+pub struct InterruptController {
+    /// The CPU interrupt line the controller drives
+    pub line: u8,
+    enable: u8,
+    pending: u8,
+    priority: [u8; 8],
+}
+
+impl InterruptController {
+    pub const ENABLE: u32 = 0;
+    pub const PENDING: u32 = 1;
+    pub const ACK: u32 = 2;
+    pub const PRIORITY: u32 = 3;
+
+    /// Number of bytes of registers to map.
+    pub const SIZE: u32 = 11;
+
+    /// Create a controller driving CPU interrupt line `line`, with every
+    /// source disabled and at priority 0.
+    pub fn new(line: u8) -> Self {
+        InterruptController { line, enable: 0, pending: 0, priority: [0; 8] }
+    }
+
+    /// Request an interrupt from `source` (0-7). The request stays pending
+    /// until it is acknowledged or cleared, even while it is disabled.
+    pub fn raise(&mut self, source: u8) {
+        self.pending |= 1 << (source & 7);
+    }
+
+    /// The highest-priority enabled pending source, if any.
+    pub fn active(&self) -> Option<u8> {
+        let requests = self.enable & self.pending;
+        (0..8u8)
+            .filter(|source| requests & (1 << source) != 0)
+            .max_by_key(|source| (self.priority[*source as usize], 7 - source))
+    }
+}
+
+impl Device for InterruptController {
+    fn read(&mut self, offset: u32) -> u8 {
+        match offset {
+            Self::ENABLE => self.enable,
+            Self::PENDING => self.pending,
+            Self::ACK => match self.active() {
+                Some(source) => {
+                    self.pending &= !(1 << source);
+                    source
+                }
+                None => 0xFF,
+            },
+            _ if (Self::PRIORITY..Self::PRIORITY + 8).contains(&offset) => {
+                self.priority[(offset - Self::PRIORITY) as usize]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        match offset {
+            Self::ENABLE => self.enable = val,
+            Self::PENDING => self.pending &= !val,
+            _ if (Self::PRIORITY..Self::PRIORITY + 8).contains(&offset) => {
+                self.priority[(offset - Self::PRIORITY) as usize] = val;
+            }
+            _ => {}
+        }
+    }
+
+    fn request(&mut self, sources: u8) {
+        self.pending |= sources;
+    }
+
+    fn irq(&self) -> Option<u8> {
+        self.active().map(|_| self.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{Bus, MemoryMap};
+    use crate::u24::U24;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A device holding its interrupt request at a fixed level.
+    struct Level(bool);
+
+    impl Device for Level {
+        fn read(&mut self, _offset: u32) -> u8 {
+            0
+        }
+
+        fn write(&mut self, _offset: u32, _val: u8) {}
+
+        fn irq(&self) -> Option<u8> {
+            // The line is ignored when the request goes to a controller
+            self.0.then_some(7)
+        }
+    }
+
+    #[test]
+    fn ack_takes_the_highest_priority_enabled_source() {
+        let mut ic = InterruptController::new(2);
+        ic.write(InterruptController::ENABLE, 0b0010_0110);
+        ic.write(InterruptController::PRIORITY + 1, 1);
+        ic.write(InterruptController::PRIORITY + 2, 5);
+        ic.write(InterruptController::PRIORITY + 5, 5);
+        for source in [1, 2, 5, 6] {
+            ic.raise(source);
+        }
+        assert_eq!(ic.irq(), Some(2));
+
+        // 2 and 5 tie, so the lower goes first; 6 is not enabled
+        assert_eq!(ic.read(InterruptController::ACK), 2);
+        assert_eq!(ic.read(InterruptController::ACK), 5);
+        assert_eq!(ic.read(InterruptController::ACK), 1);
+        assert_eq!(ic.read(InterruptController::ACK), 0xFF);
+        assert_eq!(ic.irq(), None);
+        assert_eq!(ic.read(InterruptController::PENDING), 0b0100_0000);
+
+        ic.write(InterruptController::PENDING, 0b0100_0000);
+        assert_eq!(ic.read(InterruptController::PENDING), 0);
+    }
+
+    #[test]
+    fn devices_mapped_to_a_source_raise_it() {
+        let ic = Rc::new(RefCell::new(InterruptController::new(4)));
+        let mut map = MemoryMap::new(0x100);
+        map.map_device(0x80..0x80 + InterruptController::SIZE, ic.clone());
+        map.map_device_to_source(0xA0..0xA1, Level(true), 3);
+        map.map_device_to_source(0xA1..0xA2, Level(false), 5);
+
+        // Pending but disabled, so the CPU line stays low
+        assert_eq!(map.tick(1), 0);
+        assert_eq!(map.read8(U24::new(0x80 + InterruptController::PENDING)), 0b0000_1000);
+
+        map.write8(U24::new(0x80 + InterruptController::ENABLE), 0xFF);
+        assert_eq!(map.tick(1), 1 << 4);
+        assert_eq!(map.read8(U24::new(0x80 + InterruptController::ACK)), 3);
+
+        // The device still asserts its level, so the request comes back
+        assert_eq!(ic.borrow().active(), None);
+        assert_eq!(map.tick(1), 1 << 4);
+        assert_eq!(ic.borrow().active(), Some(3));
+    }
+}
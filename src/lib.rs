@@ -1,3 +1,32 @@
+#[macro_use]
+mod logging;
+
+pub mod bus;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod cpu;
+pub mod device;
+pub mod diff;
+pub mod executable;
+#[cfg(feature = "fpu")]
+mod fpu;
+pub mod interrupt;
+pub mod machine;
+pub mod monitor;
+pub mod net;
 pub mod op;
-pub mod u24;
\ No newline at end of file
+pub mod perf;
+pub mod power;
+pub mod rng;
+pub mod semihost;
+pub mod smp;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+pub mod sound;
+pub mod steps;
+pub mod timer;
+pub mod trace;
+pub mod u24;
+pub mod uart;
+pub mod video;
+pub mod watch;
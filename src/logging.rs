@@ -0,0 +1,17 @@
+//! Logging macros that forward to the `log` crate when the `log` feature
+//! is enabled and compile to nothing otherwise, so the CPU never pays for
+//! logging it was not built with.
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::trace!($($arg)*);
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+    };
+}
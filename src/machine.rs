@@ -0,0 +1,78 @@
+use std::ops::Range;
+
+use crate::bus::{Bus, MemoryMap, WriteProtect};
+use crate::cpu::{Cpu, ResetVector, RunOutcome, StopReason};
+use crate::device::Device;
+use crate::u24::U24;
+
+/// A complete system: a CPU attached to a memory map of RAM, ROM and
+/// devices. Hosts such as rexta-sim build a `Machine`, load a program and
+/// run it, rather than wiring a `Cpu` and bus together themselves.
+pub struct Machine {
+    pub cpu: Cpu<MemoryMap>,
+}
+
+impl Machine {
+    /// Create a machine with `ram_size` bytes of RAM (2 bytes to 16 MiB)
+    /// and nothing mapped.
+    pub fn new(ram_size: usize) -> Self {
+        assert!((2..=0x1000000).contains(&ram_size), "memory must be 2 bytes to 16 MiB");
+        Machine { cpu: Cpu::with_bus(MemoryMap::new(ram_size)) }
+    }
+
+    /// Map a device over `range`; see `MemoryMap::map_device`.
+    pub fn map_device(&mut self, range: Range<u32>, device: impl Device + 'static) {
+        self.cpu.bus.map_device(range, device);
+    }
+
+    /// Map a device whose interrupts go to an interrupt controller source;
+    /// see `MemoryMap::map_device_to_source`.
+    pub fn map_device_to_source(&mut self, range: Range<u32>, device: impl Device + 'static, source: u8) {
+        self.cpu.bus.map_device_to_source(range, device, source);
+    }
+
+    /// Fill RAM with `pattern` instead of zeros, and with `trap` stop with
+    /// `ErrorKind::UninitializedRead` when the guest reads a byte nothing
+    /// has written, to catch uses of uninitialized memory. Call this
+    /// before loading the program, which counts as writing it.
+    pub fn poison(&mut self, pattern: u8, trap: bool) {
+        self.cpu.bus.poison(pattern, trap);
+    }
+
+    /// Copy `bytes` into memory at `addr`.
+    pub fn load(&mut self, addr: U24, bytes: &[u8]) {
+        self.cpu.bus.load(addr, bytes);
+    }
+
+    /// Copy `bytes` into memory at `addr` and make that range read-only,
+    /// ignoring writes as a real ROM would.
+    pub fn load_rom(&mut self, addr: U24, bytes: &[u8]) {
+        self.cpu.bus.load(addr, bytes);
+        let start = addr.value();
+        self.cpu.bus.protect(start..start + bytes.len() as u32, WriteProtect::Ignore);
+    }
+
+    /// Install a boot ROM: load `bytes` read-only at `addr`, point the
+    /// reset vector at its first byte and reset, so the next run starts in
+    /// the ROM rather than at address 0.
+    pub fn load_boot_rom(&mut self, addr: U24, bytes: &[u8]) {
+        self.load_rom(addr, bytes);
+        self.cpu.reset_vector = ResetVector::Address(addr);
+        self.reset();
+    }
+
+    /// Reset the CPU; memory and devices are left as they are.
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    /// Run until the CPU stops; see `Cpu::run`.
+    pub fn run(&mut self) -> StopReason {
+        self.cpu.run()
+    }
+
+    /// Run at most `budget` instructions; see `Cpu::run_for`.
+    pub fn run_for(&mut self, budget: u64) -> RunOutcome {
+        self.cpu.run_for(budget)
+    }
+}
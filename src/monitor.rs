@@ -0,0 +1,74 @@
+//! The monitor ROM: a tiny runtime user programs can call into for
+//! console and file I/O. The source is `src/monitor.rxa`; `IMAGE` is its
+//! assembled form. The routines reach the host through a `Semihost`
+//! mailbox, which `Machine::load_monitor` maps alongside the ROM.
+
+use std::path::PathBuf;
+
+use crate::machine::Machine;
+use crate::semihost::Semihost;
+use crate::u24::U24;
+
+/// Address the monitor ROM is loaded at.
+pub const BASE: u32 = 0xF000;
+
+/// Address of the semihosting mailbox the monitor uses.
+pub const SEMIHOST_BASE: u32 = 0xE000;
+
+/// Print the NUL-terminated string at R3.
+pub const PRINT_STRING: u32 = BASE;
+
+/// Read a line into the buffer at R3, holding at most R0 characters plus a
+/// NUL. Returns the length in R2 and the mailbox status in R1 (1 at end of
+/// input).
+pub const READ_LINE: u32 = BASE + 4;
+
+/// Load the file named by the NUL-terminated string at R3 into memory at
+/// R6. Returns the status in R2 (0 on success) and the end of the data in
+/// R6.
+pub const LOAD_FILE: u32 = BASE + 8;
+
+/// The assembled monitor.
+pub const IMAGE: [u8; 371] = [
+    0x00, 0x04, 0x08, 0x00, 0x00, 0x04, 0x54, 0x00, 0x00, 0x04, 0xB1, 0x00,
+    0x03, 0x08, 0x60, 0x00, 0xE1, 0x00, 0x02, 0x06, 0x00, 0x00, 0x00, 0x71,
+    0x02, 0x23, 0x6D, 0x04, 0x20, 0xFF, 0x04, 0x02, 0x29, 0x6D, 0x02, 0x62,
+    0x53, 0x08, 0x60, 0x01, 0x00, 0x00, 0x4E, 0x06, 0x00, 0x01, 0x00, 0x5A,
+    0x06, 0x00, 0x00, 0x04, 0x08, 0x02, 0xE0, 0x0A, 0x08, 0x00, 0x02, 0xE0,
+    0x00, 0x01, 0x04, 0x20, 0x01, 0x09, 0x08, 0x02, 0x00, 0xE0, 0x00, 0x00,
+    0x02, 0xC2, 0x0A, 0x08, 0x00, 0x02, 0xE0, 0x00, 0x01, 0x04, 0x20, 0x01,
+    0x09, 0x08, 0x02, 0x00, 0xE0, 0x00, 0x08, 0x00, 0x01, 0x04, 0x20, 0x02,
+    0x09, 0x08, 0x02, 0x00, 0xE0, 0x00, 0x03, 0x08, 0x60, 0x02, 0xE0, 0x00,
+    0x6A, 0x02, 0x66, 0x01, 0x04, 0x80, 0x00, 0x53, 0x08, 0x60, 0x00, 0xE1,
+    0x00, 0x6D, 0x02, 0x68, 0x03, 0x08, 0x60, 0x00, 0xE1, 0x00, 0x01, 0x04,
+    0x20, 0x00, 0x6D, 0x04, 0x00, 0xFF, 0x04, 0x02, 0x1E, 0x71, 0x02, 0x16,
+    0x6D, 0x04, 0x10, 0xFF, 0x04, 0x02, 0x14, 0x6D, 0x02, 0x31, 0x53, 0x08,
+    0x30, 0x01, 0x00, 0x00, 0x49, 0x04, 0x20, 0x01, 0x61, 0x04, 0x00, 0x01,
+    0x00, 0x02, 0xDB, 0x01, 0x04, 0x10, 0x00, 0x6D, 0x02, 0x31, 0x03, 0x08,
+    0x60, 0x01, 0xE0, 0x00, 0x69, 0x02, 0x16, 0x08, 0x00, 0x0C, 0x00, 0x03,
+    0x08, 0x60, 0x00, 0xE1, 0x00, 0x02, 0x06, 0x00, 0x00, 0x00, 0x71, 0x02,
+    0x23, 0x6D, 0x04, 0x20, 0xFF, 0x04, 0x02, 0x11, 0x6D, 0x02, 0x62, 0x53,
+    0x08, 0x60, 0x01, 0x00, 0x00, 0x4E, 0x06, 0x00, 0x01, 0x00, 0x00, 0x02,
+    0xE5, 0x0A, 0x08, 0x00, 0x02, 0xE0, 0x00, 0x01, 0x04, 0x20, 0x03, 0x09,
+    0x08, 0x02, 0x00, 0xE0, 0x00, 0x03, 0x08, 0x30, 0x01, 0xE0, 0x00, 0x69,
+    0x02, 0x23, 0x08, 0x02, 0x64, 0xE5, 0x04, 0x60, 0x03, 0xE5, 0x04, 0x70,
+    0x02, 0xE5, 0x04, 0x80, 0x01, 0x02, 0x06, 0x00, 0x00, 0x04, 0x0A, 0x08,
+    0x00, 0x02, 0xE0, 0x00, 0x01, 0x04, 0x20, 0x05, 0x09, 0x08, 0x02, 0x00,
+    0xE0, 0x00, 0x03, 0x08, 0x30, 0x01, 0xE0, 0x00, 0x69, 0x02, 0x23, 0x08,
+    0x02, 0x37, 0x03, 0x08, 0x30, 0x02, 0xE0, 0x00, 0x6A, 0x02, 0x03, 0x04,
+    0x02, 0x1D, 0x03, 0x08, 0x30, 0x00, 0xE1, 0x00, 0x71, 0x02, 0x23, 0x6D,
+    0x02, 0x62, 0x53, 0x08, 0x60, 0x01, 0x00, 0x00, 0x66, 0x06, 0x00, 0x01,
+    0x00, 0x08, 0x02, 0xEC, 0x00, 0x02, 0xB6, 0x01, 0x04, 0x20, 0x07, 0x09,
+    0x08, 0x02, 0x00, 0xE0, 0x00, 0x01, 0x04, 0x20, 0x00, 0x7B, 0x02, 0x30,
+    0x53, 0x08, 0x30, 0x09, 0x00, 0x00, 0x7F, 0x02, 0x03, 0x08, 0x00,
+];
+
+impl Machine {
+    /// Load the monitor ROM at `monitor::BASE` and map a semihosting
+    /// mailbox for it at `monitor::SEMIHOST_BASE`, reporting `args` as the
+    /// program's arguments and allowing file access under `sandbox`.
+    pub fn load_monitor(&mut self, args: Vec<String>, sandbox: Option<PathBuf>) {
+        self.map_device(SEMIHOST_BASE..SEMIHOST_BASE + Semihost::SIZE, Semihost::new(args, sandbox));
+        self.load_rom(U24::new(BASE), &IMAGE);
+    }
+}
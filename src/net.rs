@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind};
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::device::Device;
+
+/// The host side of a network link: something that carries whole packets.
+pub trait PacketLink {
+    /// Send a packet from the guest.
+    fn send(&mut self, packet: &[u8]);
+
+    /// Return the next packet that has arrived, without blocking.
+    fn receive(&mut self) -> Option<Vec<u8>>;
+}
+
+/// Packet interface device. Received packets queue in a ring of
+/// `RX_SLOTS` entries; the packet at the head is visible in the RX
+/// buffer until the guest drops it. The guest sends by filling the TX
+/// buffer, setting TX_LEN and issuing the SEND command.
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0      | STATUS: bit 0 a packet is waiting in the RX buffer | read |
+/// | 1      | CONTROL: bit 0 interrupt while a packet is waiting | read/write |
+/// | 2-3    | RX_LEN: length of the waiting packet, 0 if none (16-bit, little-endian) | read |
+/// | 4-5    | TX_LEN: length of the packet to send (16-bit, little-endian) | read/write |
+/// | 6      | COMMAND: 1 sends the TX buffer, 2 drops the waiting packet | write |
+/// | 0x0100-0x06FF | RX buffer | read |
+/// | 0x0700-0x0CFF | TX buffer | read/write |
+pub struct NetDevice {
+    /// The CPU interrupt line the device asserts
+    pub line: u8,
+    link: Box<dyn PacketLink>,
+    rx: VecDeque<Vec<u8>>,
+    tx: Vec<u8>,
+    tx_len: u16,
+    control: u8,
+    cycles: u64,
+}
+
+impl NetDevice {
+    pub const STATUS: u32 = 0;
+    pub const CONTROL: u32 = 1;
+    pub const RX_LEN: u32 = 2;
+    pub const TX_LEN: u32 = 4;
+    pub const COMMAND: u32 = 6;
+    pub const RX_BUFFER: u32 = 0x0100;
+    pub const TX_BUFFER: u32 = 0x0700;
+
+    /// Largest packet the buffers hold; longer packets are truncated.
+    pub const MTU: usize = 0x600;
+
+    /// Number of received packets held before new ones are dropped.
+    pub const RX_SLOTS: usize = 16;
+
+    /// Number of bytes of registers and buffers to map.
+    pub const SIZE: u32 = Self::TX_BUFFER + Self::MTU as u32;
+
+    pub const COMMAND_SEND: u8 = 1;
+    pub const COMMAND_DROP: u8 = 2;
+
+    /// How many cycles pass between checks for received packets
+    const POLL_INTERVAL: u64 = 256;
+
+    /// Create a device on `link` that asserts CPU interrupt line `line`.
+    pub fn new(link: impl PacketLink + 'static, line: u8) -> Self {
+        NetDevice {
+            line,
+            link: Box::new(link),
+            rx: VecDeque::new(),
+            tx: vec![0; Self::MTU],
+            tx_len: 0,
+            control: 0,
+            cycles: 0,
+        }
+    }
+
+    fn poll(&mut self) {
+        while self.rx.len() < Self::RX_SLOTS {
+            match self.link.receive() {
+                Some(mut packet) => {
+                    packet.truncate(Self::MTU);
+                    self.rx.push_back(packet);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn rx_len(&self) -> u16 {
+        self.rx.front().map_or(0, |packet| packet.len() as u16)
+    }
+}
+
+impl Device for NetDevice {
+    fn read(&mut self, offset: u32) -> u8 {
+        match offset {
+            Self::STATUS => {
+                if self.rx.is_empty() {
+                    self.poll();
+                }
+                !self.rx.is_empty() as u8
+            }
+            Self::CONTROL => self.control,
+            2 => self.rx_len() as u8,
+            3 => (self.rx_len() >> 8) as u8,
+            4 => self.tx_len as u8,
+            5 => (self.tx_len >> 8) as u8,
+            Self::RX_BUFFER..Self::TX_BUFFER => {
+                let i = (offset - Self::RX_BUFFER) as usize;
+                self.rx.front().and_then(|packet| packet.get(i)).copied().unwrap_or(0)
+            }
+            Self::TX_BUFFER.. => self.tx.get((offset - Self::TX_BUFFER) as usize).copied().unwrap_or(0xFF),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        match offset {
+            Self::CONTROL => self.control = val & 0x01,
+            4 => self.tx_len = (self.tx_len & 0xFF00) | val as u16,
+            5 => self.tx_len = (self.tx_len & 0x00FF) | (val as u16) << 8,
+            Self::COMMAND => match val {
+                Self::COMMAND_SEND => {
+                    let len = (self.tx_len as usize).min(Self::MTU);
+                    self.link.send(&self.tx[..len]);
+                }
+                Self::COMMAND_DROP => {
+                    self.rx.pop_front();
+                }
+                _ => {}
+            },
+            Self::TX_BUFFER.. => {
+                if let Some(byte) = self.tx.get_mut((offset - Self::TX_BUFFER) as usize) {
+                    *byte = val;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.cycles += cycles;
+        if self.cycles >= Self::POLL_INTERVAL {
+            self.cycles = 0;
+            self.poll();
+        }
+    }
+
+    fn irq(&self) -> Option<u8> {
+        (self.control & 0x01 != 0 && !self.rx.is_empty()).then_some(self.line)
+    }
+}
+
+/// In-process link, for connecting machines in the same program.
+pub struct ChannelLink {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl ChannelLink {
+    /// Create two linked ends: packets sent on one arrive at the other.
+    pub fn pair() -> (Self, Self) {
+        let (a_sender, b_receiver) = mpsc::channel();
+        let (b_sender, a_receiver) = mpsc::channel();
+        (
+            ChannelLink { sender: a_sender, receiver: a_receiver },
+            ChannelLink { sender: b_sender, receiver: b_receiver },
+        )
+    }
+}
+
+impl PacketLink for ChannelLink {
+    fn send(&mut self, packet: &[u8]) {
+        // The other end may have gone; like a cable, that loses the packet
+        let _ = self.sender.send(packet.to_vec());
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Link over UDP: each packet is one datagram to or from the peer.
+pub struct UdpLink {
+    socket: UdpSocket,
+}
+
+impl UdpLink {
+    /// Bind to `local` and send to `peer`, e.g. `"127.0.0.1:4000"` and
+    /// `"127.0.0.1:4001"`. Only datagrams from the peer are received.
+    pub fn bind(local: impl ToSocketAddrs, peer: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpLink { socket })
+    }
+}
+
+impl PacketLink for UdpLink {
+    fn send(&mut self, packet: &[u8]) {
+        // UDP is unreliable anyway; a failed send is a lost packet
+        let _ = self.socket.send(packet);
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let mut buf = vec![0; NetDevice::MTU];
+        loop {
+            match self.socket.recv(&mut buf) {
+                Ok(n) => {
+                    buf.truncate(n);
+                    return Some(buf);
+                }
+                // A refusal reports an earlier send to a peer that wasn't
+                // listening yet
+                Err(err) if matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::ConnectionRefused) => {}
+                Err(_) => return None,
+            }
+        }
+    }
+}
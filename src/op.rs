@@ -1,6 +1,7 @@
 use crate::u24::U24;
 
 /// Represents an operation being performed by the CPU.
+#[derive(Debug, Clone)]
 pub struct Op {
     pub code: OpCode,
 
@@ -8,6 +9,12 @@ pub struct Op {
     pub operands: [u8; 4],
 }
 
+impl Default for Op {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Op {
     /// Create a new op set to no-op with no parameters.
     pub fn new() -> Self {
@@ -58,6 +65,23 @@ pub enum OpCode {
 
     HLT = 0x0004,
     RTS = 0x0008,
+    PUSHA = 0x000C,
+    POPA = 0x0010,
+    PUSHF = 0x0014,
+    POPF = 0x0018,
+    RTI = 0x001C,
+    SEI = 0x0020,
+    CLI = 0x0024,
+    WAIT = 0x0028,
+    LEAVE = 0x002C,
+    SED = 0x0030,
+    CLD = 0x0034,
+    BRA1 = 0x0200,
+    BZ1 = 0x0204,
+    BNZ1 = 0x0208,
+    BC1 = 0x020C,
+    BNC1 = 0x0210,
+    SWI = 0x0214,
     ADD1 = 0x0201,
     SUB1 = 0x0205,
     AND1 = 0x0209,
@@ -76,6 +100,16 @@ pub enum OpCode {
     TST1 = 0x023D,
     PUSH1 = 0x0241,
     POP1 = 0x0245,
+    MUL1 = 0x0249,
+    DIV1 = 0x024D,
+    MOD1 = 0x0251,
+    SHLR1 = 0x0255,
+    SHRR1 = 0x0259,
+    XCHG1 = 0x0265,
+    LOADR1 = 0x0269,
+    STORER1 = 0x026D,
+    LOADPI1 = 0x0271,
+    STOREPD1 = 0x0275,
     ADD2 = 0x0202,
     SUB2 = 0x0206,
     AND2 = 0x020A,
@@ -94,6 +128,18 @@ pub enum OpCode {
     TST2 = 0x023E,
     PUSH2 = 0x0242,
     POP2 = 0x0246,
+    MUL2 = 0x024A,
+    DIV2 = 0x024E,
+    MOD2 = 0x0252,
+    SHLR2 = 0x0256,
+    SHRR2 = 0x025A,
+    XCHG2 = 0x0266,
+    LOADR2 = 0x026A,
+    STORER2 = 0x026E,
+    LOADPI2 = 0x0272,
+    STOREPD2 = 0x0276,
+    SEX2 = 0x025E,
+    BSWAP2 = 0x0262,
     ADD3 = 0x0203,
     SUB3 = 0x0207,
     AND3 = 0x020B,
@@ -112,16 +158,102 @@ pub enum OpCode {
     TST3 = 0x023F,
     PUSH3 = 0x0243,
     POP3 = 0x0247,
+    MUL3 = 0x024B,
+    DIV3 = 0x024F,
+    MOD3 = 0x0253,
+    SHLR3 = 0x0257,
+    SHRR3 = 0x025B,
+    XCHG3 = 0x0267,
+    LOADR3 = 0x026B,
+    STORER3 = 0x026F,
+    LOADPI3 = 0x0273,
+    STOREPD3 = 0x0277,
+    MOVSPR = 0x027B,
+    MOVRSP = 0x027F,
+    MOVPC = 0x0283,
+    CLZ1 = 0x0285,
+    CLZ2 = 0x0286,
+    CLZ3 = 0x0287,
+    POPCNT1 = 0x0289,
+    POPCNT2 = 0x028A,
+    POPCNT3 = 0x028B,
+    CMOVZ1 = 0x028D,
+    CMOVZ2 = 0x028E,
+    CMOVZ3 = 0x028F,
+    CMOVNZ1 = 0x0291,
+    CMOVNZ2 = 0x0292,
+    CMOVNZ3 = 0x0293,
+    CMOVC1 = 0x0295,
+    CMOVC2 = 0x0296,
+    CMOVC3 = 0x0297,
+    JSRR = 0x0298,
+    TAS = 0x029C,
+    DAA = 0x02A0,
+    CPUID = 0x02A4,
+    FLOAD = 0x02A8,
+    FSTORE = 0x02AC,
+    FITOF = 0x02B0,
+    FFTOI = 0x02B4,
+    FADD = 0x02B8,
+    FSUB = 0x02BC,
+    FMUL = 0x02C0,
+    FDIV = 0x02C4,
+    SEX3 = 0x025F,
+    BSWAP3 = 0x0263,
+    BRA2 = 0x0400,
+    BZ2 = 0x0404,
+    BNZ2 = 0x0408,
+    BC2 = 0x040C,
+    BNC2 = 0x0410,
+    MEMCPY = 0x0414,
+    ENTER = 0x0418,
+    MAC = 0x041C,
     LOADI1 = 0x0401,
     ADDI1 = 0x0449,
+    CMPI1 = 0x0455,
+    SUBI1 = 0x0461,
+    ANDI1 = 0x046D,
+    ORI1 = 0x0479,
+    XORI1 = 0x0485,
+    LOADX1 = 0x06E5,
+    STOREX1 = 0x06F1,
+    SHLI1 = 0x0491,
+    SHRI1 = 0x049D,
+    ROLI1 = 0x04A9,
+    RORI1 = 0x04B5,
+    BSET1 = 0x04C1,
+    BCLR1 = 0x04CD,
+    BTST1 = 0x04D9,
+    LOADS1 = 0x04E5,
+    STORES1 = 0x04F1,
     JMP = 0x0600,
     JZ = 0x0604,
     JNZ = 0x0608,
     JC = 0x060C,
     JNC = 0x0610,
     JSR = 0x0614,
+    JLT = 0x0618,
+    JGE = 0x061C,
+    JGT = 0x0620,
+    JLE = 0x0624,
     LOADI2 = 0x0602,
     ADDI2 = 0x064E,
+    CMPI2 = 0x065A,
+    SUBI2 = 0x0666,
+    ANDI2 = 0x0672,
+    ORI2 = 0x067E,
+    XORI2 = 0x068A,
+    LOADX2 = 0x06EA,
+    STOREX2 = 0x06F6,
+    SHLI2 = 0x0496,
+    SHRI2 = 0x04A2,
+    ROLI2 = 0x04AE,
+    RORI2 = 0x04BA,
+    BSET2 = 0x04C6,
+    BCLR2 = 0x04D2,
+    BTST2 = 0x04DE,
+    LOADS2 = 0x04EA,
+    STORES2 = 0x04F6,
     JMPA = 0x0603,
     JZA = 0x0607,
     JNZA = 0x060B,
@@ -136,6 +268,24 @@ pub enum OpCode {
     LOAD3 = 0x0807,
     STORE3 = 0x080B,
     ADDI3 = 0x0853,
+    CMPI3 = 0x085F,
+    SUBI3 = 0x086B,
+    ANDI3 = 0x0877,
+    ORI3 = 0x0883,
+    XORI3 = 0x088F,
+    LEA = 0x0893,
+    JTAB = 0x0894,
+    LOADX3 = 0x06EF,
+    STOREX3 = 0x06FB,
+    SHLI3 = 0x049B,
+    SHRI3 = 0x04A7,
+    ROLI3 = 0x04B3,
+    RORI3 = 0x04BF,
+    BSET3 = 0x04CB,
+    BCLR3 = 0x04D7,
+    BTST3 = 0x04E3,
+    LOADS3 = 0x04EF,
+    STORES3 = 0x04FB,
 }
 
 impl TryFrom<u16> for OpCode {
@@ -145,6 +295,23 @@ impl TryFrom<u16> for OpCode {
         match value {
             0x0004 => Ok(OpCode::HLT),
             0x0008 => Ok(OpCode::RTS),
+            0x000C => Ok(OpCode::PUSHA),
+            0x0010 => Ok(OpCode::POPA),
+            0x0014 => Ok(OpCode::PUSHF),
+            0x0018 => Ok(OpCode::POPF),
+            0x001C => Ok(OpCode::RTI),
+            0x0020 => Ok(OpCode::SEI),
+            0x0024 => Ok(OpCode::CLI),
+            0x0028 => Ok(OpCode::WAIT),
+            0x002C => Ok(OpCode::LEAVE),
+            0x0030 => Ok(OpCode::SED),
+            0x0034 => Ok(OpCode::CLD),
+            0x0200 => Ok(OpCode::BRA1),
+            0x0204 => Ok(OpCode::BZ1),
+            0x0208 => Ok(OpCode::BNZ1),
+            0x020C => Ok(OpCode::BC1),
+            0x0210 => Ok(OpCode::BNC1),
+            0x0214 => Ok(OpCode::SWI),
             0x0201 => Ok(OpCode::ADD1),
             0x0205 => Ok(OpCode::SUB1),
             0x0209 => Ok(OpCode::AND1),
@@ -163,6 +330,16 @@ impl TryFrom<u16> for OpCode {
             0x023D => Ok(OpCode::TST1),
             0x0241 => Ok(OpCode::PUSH1),
             0x0245 => Ok(OpCode::POP1),
+            0x0249 => Ok(OpCode::MUL1),
+            0x024D => Ok(OpCode::DIV1),
+            0x0251 => Ok(OpCode::MOD1),
+            0x0255 => Ok(OpCode::SHLR1),
+            0x0259 => Ok(OpCode::SHRR1),
+            0x0265 => Ok(OpCode::XCHG1),
+            0x0269 => Ok(OpCode::LOADR1),
+            0x026D => Ok(OpCode::STORER1),
+            0x0271 => Ok(OpCode::LOADPI1),
+            0x0275 => Ok(OpCode::STOREPD1),
             0x0202 => Ok(OpCode::ADD2),
             0x0206 => Ok(OpCode::SUB2),
             0x020A => Ok(OpCode::AND2),
@@ -181,6 +358,18 @@ impl TryFrom<u16> for OpCode {
             0x023E => Ok(OpCode::TST2),
             0x0242 => Ok(OpCode::PUSH2),
             0x0246 => Ok(OpCode::POP2),
+            0x024A => Ok(OpCode::MUL2),
+            0x024E => Ok(OpCode::DIV2),
+            0x0252 => Ok(OpCode::MOD2),
+            0x0256 => Ok(OpCode::SHLR2),
+            0x025A => Ok(OpCode::SHRR2),
+            0x0266 => Ok(OpCode::XCHG2),
+            0x026A => Ok(OpCode::LOADR2),
+            0x026E => Ok(OpCode::STORER2),
+            0x0272 => Ok(OpCode::LOADPI2),
+            0x0276 => Ok(OpCode::STOREPD2),
+            0x025E => Ok(OpCode::SEX2),
+            0x0262 => Ok(OpCode::BSWAP2),
             0x0203 => Ok(OpCode::ADD3),
             0x0207 => Ok(OpCode::SUB3),
             0x020B => Ok(OpCode::AND3),
@@ -199,16 +388,102 @@ impl TryFrom<u16> for OpCode {
             0x023F => Ok(OpCode::TST3),
             0x0243 => Ok(OpCode::PUSH3),
             0x0247 => Ok(OpCode::POP3),
+            0x024B => Ok(OpCode::MUL3),
+            0x024F => Ok(OpCode::DIV3),
+            0x0253 => Ok(OpCode::MOD3),
+            0x0257 => Ok(OpCode::SHLR3),
+            0x025B => Ok(OpCode::SHRR3),
+            0x0267 => Ok(OpCode::XCHG3),
+            0x026B => Ok(OpCode::LOADR3),
+            0x026F => Ok(OpCode::STORER3),
+            0x0273 => Ok(OpCode::LOADPI3),
+            0x0277 => Ok(OpCode::STOREPD3),
+            0x027B => Ok(OpCode::MOVSPR),
+            0x027F => Ok(OpCode::MOVRSP),
+            0x0283 => Ok(OpCode::MOVPC),
+            0x0285 => Ok(OpCode::CLZ1),
+            0x0286 => Ok(OpCode::CLZ2),
+            0x0287 => Ok(OpCode::CLZ3),
+            0x0289 => Ok(OpCode::POPCNT1),
+            0x028A => Ok(OpCode::POPCNT2),
+            0x028B => Ok(OpCode::POPCNT3),
+            0x028D => Ok(OpCode::CMOVZ1),
+            0x028E => Ok(OpCode::CMOVZ2),
+            0x028F => Ok(OpCode::CMOVZ3),
+            0x0291 => Ok(OpCode::CMOVNZ1),
+            0x0292 => Ok(OpCode::CMOVNZ2),
+            0x0293 => Ok(OpCode::CMOVNZ3),
+            0x0295 => Ok(OpCode::CMOVC1),
+            0x0296 => Ok(OpCode::CMOVC2),
+            0x0297 => Ok(OpCode::CMOVC3),
+            0x0298 => Ok(OpCode::JSRR),
+            0x029C => Ok(OpCode::TAS),
+            0x02A0 => Ok(OpCode::DAA),
+            0x02A4 => Ok(OpCode::CPUID),
+            0x02A8 => Ok(OpCode::FLOAD),
+            0x02AC => Ok(OpCode::FSTORE),
+            0x02B0 => Ok(OpCode::FITOF),
+            0x02B4 => Ok(OpCode::FFTOI),
+            0x02B8 => Ok(OpCode::FADD),
+            0x02BC => Ok(OpCode::FSUB),
+            0x02C0 => Ok(OpCode::FMUL),
+            0x02C4 => Ok(OpCode::FDIV),
+            0x025F => Ok(OpCode::SEX3),
+            0x0263 => Ok(OpCode::BSWAP3),
+            0x0400 => Ok(OpCode::BRA2),
+            0x0404 => Ok(OpCode::BZ2),
+            0x0408 => Ok(OpCode::BNZ2),
+            0x040C => Ok(OpCode::BC2),
+            0x0410 => Ok(OpCode::BNC2),
+            0x0414 => Ok(OpCode::MEMCPY),
+            0x0418 => Ok(OpCode::ENTER),
+            0x041C => Ok(OpCode::MAC),
             0x0401 => Ok(OpCode::LOADI1),
             0x0449 => Ok(OpCode::ADDI1),
+            0x0455 => Ok(OpCode::CMPI1),
+            0x0461 => Ok(OpCode::SUBI1),
+            0x046D => Ok(OpCode::ANDI1),
+            0x0479 => Ok(OpCode::ORI1),
+            0x0485 => Ok(OpCode::XORI1),
+            0x06E5 => Ok(OpCode::LOADX1),
+            0x06F1 => Ok(OpCode::STOREX1),
+            0x0491 => Ok(OpCode::SHLI1),
+            0x049D => Ok(OpCode::SHRI1),
+            0x04A9 => Ok(OpCode::ROLI1),
+            0x04B5 => Ok(OpCode::RORI1),
+            0x04C1 => Ok(OpCode::BSET1),
+            0x04CD => Ok(OpCode::BCLR1),
+            0x04D9 => Ok(OpCode::BTST1),
+            0x04E5 => Ok(OpCode::LOADS1),
+            0x04F1 => Ok(OpCode::STORES1),
             0x0600 => Ok(OpCode::JMP),
             0x0604 => Ok(OpCode::JZ),
             0x0608 => Ok(OpCode::JNZ),
             0x060C => Ok(OpCode::JC),
             0x0610 => Ok(OpCode::JNC),
             0x0614 => Ok(OpCode::JSR),
+            0x0618 => Ok(OpCode::JLT),
+            0x061C => Ok(OpCode::JGE),
+            0x0620 => Ok(OpCode::JGT),
+            0x0624 => Ok(OpCode::JLE),
             0x0602 => Ok(OpCode::LOADI2),
             0x064E => Ok(OpCode::ADDI2),
+            0x065A => Ok(OpCode::CMPI2),
+            0x0666 => Ok(OpCode::SUBI2),
+            0x0672 => Ok(OpCode::ANDI2),
+            0x067E => Ok(OpCode::ORI2),
+            0x068A => Ok(OpCode::XORI2),
+            0x06EA => Ok(OpCode::LOADX2),
+            0x06F6 => Ok(OpCode::STOREX2),
+            0x0496 => Ok(OpCode::SHLI2),
+            0x04A2 => Ok(OpCode::SHRI2),
+            0x04AE => Ok(OpCode::ROLI2),
+            0x04BA => Ok(OpCode::RORI2),
+            0x04C6 => Ok(OpCode::BSET2),
+            0x04D2 => Ok(OpCode::BCLR2),
+            0x04DE => Ok(OpCode::BTST2),
+            0x04EA => Ok(OpCode::LOADS2),
+            0x04F6 => Ok(OpCode::STORES2),
             0x0603 => Ok(OpCode::JMPA),
             0x0607 => Ok(OpCode::JZA),
             0x060B => Ok(OpCode::JNZA),
@@ -223,7 +498,42 @@ impl TryFrom<u16> for OpCode {
             0x0807 => Ok(OpCode::LOAD3),
             0x080B => Ok(OpCode::STORE3),
             0x0853 => Ok(OpCode::ADDI3),
+            0x085F => Ok(OpCode::CMPI3),
+            0x086B => Ok(OpCode::SUBI3),
+            0x0877 => Ok(OpCode::ANDI3),
+            0x0883 => Ok(OpCode::ORI3),
+            0x088F => Ok(OpCode::XORI3),
+            0x0893 => Ok(OpCode::LEA),
+            0x0894 => Ok(OpCode::JTAB),
+            0x06EF => Ok(OpCode::LOADX3),
+            0x06FB => Ok(OpCode::STOREX3),
+            0x049B => Ok(OpCode::SHLI3),
+            0x04A7 => Ok(OpCode::SHRI3),
+            0x04B3 => Ok(OpCode::ROLI3),
+            0x04BF => Ok(OpCode::RORI3),
+            0x04CB => Ok(OpCode::BSET3),
+            0x04D7 => Ok(OpCode::BCLR3),
+            0x04E3 => Ok(OpCode::BTST3),
+            0x04EF => Ok(OpCode::LOADS3),
+            0x04FB => Ok(OpCode::STORES3),
             _ => Err(()),
         }
     }
 }
+
+impl OpCode {
+    /// True for the instructions of the optional floating-point unit.
+    pub fn is_fpu(&self) -> bool {
+        matches!(
+            self,
+            OpCode::FLOAD
+                | OpCode::FSTORE
+                | OpCode::FITOF
+                | OpCode::FFTOI
+                | OpCode::FADD
+                | OpCode::FSUB
+                | OpCode::FMUL
+                | OpCode::FDIV
+        )
+    }
+}
@@ -0,0 +1,60 @@
+/// Event counts kept by the CPU. Hosts read them from `Cpu::perf`; if
+/// `Cpu::perf_base` is set, guest code can also read them as registers:
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0-2    | INSTRUCTIONS retired | read |
+/// | 3-5    | CYCLES, one per step including steps spent waiting | read |
+/// | 6-8    | MEM_READS: data reads, not instruction fetches | read |
+/// | 9-11   | MEM_WRITES: data writes, including pushes | read |
+/// | 12-14  | BRANCHES taken: instructions that changed the flow of control | read |
+/// | 15     | RESET: writing any value clears every counter | write |
+///
+/// Each register is the low 24 bits of its counter, little-endian. Read a
+/// register with a single `LOAD.3` so its bytes are consistent. Accesses
+/// to the registers are not themselves counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfCounters {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub mem_reads: u64,
+    pub mem_writes: u64,
+    pub branches: u64,
+}
+
+impl PerfCounters {
+    pub const INSTRUCTIONS: u32 = 0;
+    pub const CYCLES: u32 = 3;
+    pub const MEM_READS: u32 = 6;
+    pub const MEM_WRITES: u32 = 9;
+    pub const BRANCHES: u32 = 12;
+    pub const RESET: u32 = 15;
+
+    /// Number of bytes of registers mapped at `Cpu::perf_base`.
+    pub const SIZE: u32 = 16;
+
+    /// Clear every counter.
+    pub fn reset(&mut self) {
+        *self = PerfCounters::default();
+    }
+
+    /// Read the register byte at `offset`.
+    pub(crate) fn read(&self, offset: u32) -> u8 {
+        let counter = match offset / 3 {
+            0 => self.instructions,
+            1 => self.cycles,
+            2 => self.mem_reads,
+            3 => self.mem_writes,
+            4 => self.branches,
+            _ => return 0xFF,
+        };
+        (counter >> (offset % 3 * 8)) as u8
+    }
+
+    /// Write the register byte at `offset`.
+    pub(crate) fn write(&mut self, offset: u32, _val: u8) {
+        if offset == Self::RESET {
+            self.reset();
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::device::Device;
+
+/// Power control register. Writing EXIT powers the machine off: the CPU
+/// stops with `StopReason::PowerOff` carrying the value written, which
+/// rexta-sim passes on as its exit status. This lets a guest test
+/// program report pass or fail to a script.
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0      | EXIT: exit code; writing it powers off | write |
+#[derive(Debug, Clone, Default)]
+pub struct PowerControl {
+    exit_code: Option<u8>,
+}
+
+impl PowerControl {
+    pub const EXIT: u32 = 0;
+
+    /// Number of bytes of registers to map.
+    pub const SIZE: u32 = 1;
+
+    pub fn new() -> Self {
+        PowerControl { exit_code: None }
+    }
+
+    /// The exit code written by the guest, if it has powered off.
+    pub fn exit_code(&self) -> Option<u8> {
+        self.exit_code
+    }
+
+    /// Power back on, so the machine can run again.
+    pub fn reset(&mut self) {
+        self.exit_code = None;
+    }
+}
+
+impl Device for PowerControl {
+    fn read(&mut self, _offset: u32) -> u8 {
+        0xFF
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        if offset == Self::EXIT {
+            self.exit_code = Some(val);
+        }
+    }
+
+    fn power_off(&self) -> Option<u8> {
+        self.exit_code
+    }
+}
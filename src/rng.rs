@@ -0,0 +1,68 @@
+use crate::device::Device;
+
+/// Seedable pseudorandom number generator (xorshift32). The same seed
+/// always yields the same sequence, so runs stay reproducible.
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0      | DATA: the next random byte | read |
+/// | 1-4    | SEED (32-bit, little-endian); writing any byte reseeds | write |
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u32,
+    seed: u32,
+}
+
+impl Rng {
+    pub const DATA: u32 = 0;
+    pub const SEED: u32 = 1;
+
+    /// Number of bytes of registers to map.
+    pub const SIZE: u32 = 5;
+
+    /// Create a generator seeded with `seed`.
+    pub fn new(seed: u32) -> Self {
+        let mut rng = Rng { state: 0, seed: 0 };
+        rng.reseed(seed);
+        rng
+    }
+
+    /// Restart the sequence from `seed`.
+    pub fn reseed(&mut self, seed: u32) {
+        self.seed = seed;
+        // Scramble the seed so small seeds don't start with small numbers
+        let mut z = seed.wrapping_add(0x9E37_79B9);
+        z = (z ^ (z >> 16)).wrapping_mul(0x85EB_CA6B);
+        z = (z ^ (z >> 13)).wrapping_mul(0xC2B2_AE35);
+        z ^= z >> 16;
+        // xorshift never leaves an all-zero state
+        self.state = if z == 0 { 0x2545_F491 } else { z };
+    }
+
+    /// The next 32 bits of the sequence.
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+}
+
+impl Device for Rng {
+    fn read(&mut self, offset: u32) -> u8 {
+        match offset {
+            Self::DATA => self.next_u32() as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        if (Self::SEED..Self::SEED + 4).contains(&offset) {
+            let shift = (offset - Self::SEED) * 8;
+            let seed = (self.seed & !(0xFF << shift)) | (val as u32) << shift;
+            self.reseed(seed);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::device::Device;
+
+/// Mailbox for host services ("semihosting"). The guest puts a request's
+/// data in BUFFER with its length in LENGTH, then writes a command to
+/// COMMAND. The command runs straight away; STATUS, LENGTH, HANDLE and
+/// BUFFER then hold the result.
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0      | COMMAND: the service to run | write |
+/// | 1      | STATUS: result of the last command, 0 on success | read |
+/// | 2-3    | LENGTH: bytes of data in BUFFER (16-bit, little-endian) | read/write |
+/// | 4      | HANDLE: file handle for file commands | read/write |
+/// | 5      | ARG: argument index for `ARGV` | read/write |
+/// | 0x0100-0x04FF | BUFFER | read/write |
+///
+/// | Command | Effect |
+/// |---------|--------|
+/// | 1 `WRITE` | print BUFFER to stdout |
+/// | 2 `READ_LINE` | read a line from stdin into BUFFER, without the newline |
+/// | 3 `OPEN_READ` | open the file named in BUFFER; HANDLE is set |
+/// | 4 `OPEN_WRITE` | create or truncate the file named in BUFFER; HANDLE is set |
+/// | 5 `READ` | read up to LENGTH bytes from HANDLE into BUFFER; 0 at end of file |
+/// | 6 `WRITE_FILE` | write BUFFER to HANDLE |
+/// | 7 `CLOSE` | close HANDLE |
+/// | 8 `ARGC` | LENGTH is set to the number of program arguments |
+/// | 9 `ARGV` | copy argument ARG into BUFFER |
+///
+/// File names are relative paths inside the sandbox directory; with no
+/// sandbox, every file command is denied.
+pub struct Semihost {
+    args: Vec<String>,
+    sandbox: Option<PathBuf>,
+    files: Vec<Option<File>>,
+    status: u8,
+    length: u16,
+    handle: u8,
+    arg: u8,
+    buffer: Vec<u8>,
+}
+
+impl Semihost {
+    pub const COMMAND: u32 = 0;
+    pub const STATUS: u32 = 1;
+    pub const LENGTH: u32 = 2;
+    pub const HANDLE: u32 = 4;
+    pub const ARG: u32 = 5;
+    pub const BUFFER: u32 = 0x0100;
+
+    /// Size of the data buffer in bytes.
+    pub const BUFFER_SIZE: usize = 0x400;
+
+    /// Number of bytes of registers and buffer to map.
+    pub const SIZE: u32 = Self::BUFFER + Self::BUFFER_SIZE as u32;
+
+    pub const WRITE: u8 = 1;
+    pub const READ_LINE: u8 = 2;
+    pub const OPEN_READ: u8 = 3;
+    pub const OPEN_WRITE: u8 = 4;
+    pub const READ: u8 = 5;
+    pub const WRITE_FILE: u8 = 6;
+    pub const CLOSE: u8 = 7;
+    pub const ARGC: u8 = 8;
+    pub const ARGV: u8 = 9;
+
+    pub const STATUS_OK: u8 = 0;
+    /// Stdin is at end of file
+    pub const STATUS_EOF: u8 = 1;
+    /// The host reported an I/O error
+    pub const STATUS_IO_ERROR: u8 = 2;
+    /// The file name is outside the sandbox, or there is no sandbox
+    pub const STATUS_DENIED: u8 = 3;
+    /// HANDLE or ARG does not name an open file or an argument
+    pub const STATUS_BAD_HANDLE: u8 = 4;
+    /// COMMAND was not a known command
+    pub const STATUS_BAD_COMMAND: u8 = 5;
+
+    /// Create a mailbox that reports `args` as the program's arguments
+    /// and allows file access under `sandbox`, if given.
+    pub fn new(args: Vec<String>, sandbox: Option<PathBuf>) -> Self {
+        Semihost {
+            args,
+            sandbox,
+            files: Vec::new(),
+            status: Self::STATUS_OK,
+            length: 0,
+            handle: 0,
+            arg: 0,
+            buffer: vec![0; Self::BUFFER_SIZE],
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.buffer[..(self.length as usize).min(Self::BUFFER_SIZE)]
+    }
+
+    /// Copy `bytes` into the buffer, truncating if needed, and set LENGTH.
+    fn set_data(&mut self, bytes: &[u8]) {
+        let len = bytes.len().min(Self::BUFFER_SIZE);
+        self.buffer[..len].copy_from_slice(&bytes[..len]);
+        self.length = len as u16;
+    }
+
+    /// The path in the sandbox named by the buffer, if it stays inside.
+    fn sandbox_path(&self) -> Option<PathBuf> {
+        let sandbox = self.sandbox.as_ref()?;
+        let name = std::str::from_utf8(self.data()).ok()?;
+        let name = Path::new(name);
+        let inside = name.components().all(|part| matches!(part, Component::Normal(_) | Component::CurDir));
+        (inside && !name.as_os_str().is_empty()).then(|| sandbox.join(name))
+    }
+
+    fn open(&mut self, create: bool) -> u8 {
+        let Some(path) = self.sandbox_path() else { return Self::STATUS_DENIED };
+        let file = if create { File::create(path) } else { File::open(path) };
+        let Ok(file) = file else { return Self::STATUS_IO_ERROR };
+        let slot = match self.files.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None if self.files.len() < 0x100 => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+            None => return Self::STATUS_IO_ERROR,
+        };
+        self.files[slot] = Some(file);
+        self.handle = slot as u8;
+        Self::STATUS_OK
+    }
+
+    fn file(&mut self) -> Option<&mut File> {
+        self.files.get_mut(self.handle as usize)?.as_mut()
+    }
+
+    fn run(&mut self, command: u8) -> u8 {
+        match command {
+            Self::WRITE => {
+                let mut stdout = io::stdout();
+                match stdout.write_all(self.data()).and_then(|_| stdout.flush()) {
+                    Ok(()) => Self::STATUS_OK,
+                    Err(_) => Self::STATUS_IO_ERROR,
+                }
+            }
+            Self::READ_LINE => {
+                let mut line = String::new();
+                match io::stdin().lock().read_line(&mut line) {
+                    Ok(0) => {
+                        self.length = 0;
+                        Self::STATUS_EOF
+                    }
+                    Ok(_) => {
+                        let line = line.trim_end_matches(['\n', '\r']);
+                        self.set_data(line.as_bytes());
+                        Self::STATUS_OK
+                    }
+                    Err(_) => Self::STATUS_IO_ERROR,
+                }
+            }
+            Self::OPEN_READ => self.open(false),
+            Self::OPEN_WRITE => self.open(true),
+            Self::READ => {
+                let len = (self.length as usize).min(Self::BUFFER_SIZE);
+                let Some(file) = self.files.get_mut(self.handle as usize).and_then(Option::as_mut) else {
+                    return Self::STATUS_BAD_HANDLE;
+                };
+                match file.read(&mut self.buffer[..len]) {
+                    Ok(n) => {
+                        self.length = n as u16;
+                        Self::STATUS_OK
+                    }
+                    Err(_) => Self::STATUS_IO_ERROR,
+                }
+            }
+            Self::WRITE_FILE => {
+                let len = (self.length as usize).min(Self::BUFFER_SIZE);
+                let Some(file) = self.files.get_mut(self.handle as usize).and_then(Option::as_mut) else {
+                    return Self::STATUS_BAD_HANDLE;
+                };
+                match file.write_all(&self.buffer[..len]) {
+                    Ok(()) => Self::STATUS_OK,
+                    Err(_) => Self::STATUS_IO_ERROR,
+                }
+            }
+            Self::CLOSE => match self.file() {
+                Some(_) => {
+                    self.files[self.handle as usize] = None;
+                    Self::STATUS_OK
+                }
+                None => Self::STATUS_BAD_HANDLE,
+            },
+            Self::ARGC => {
+                self.length = self.args.len() as u16;
+                Self::STATUS_OK
+            }
+            Self::ARGV => match self.args.get(self.arg as usize) {
+                Some(arg) => {
+                    let arg = arg.clone();
+                    self.set_data(arg.as_bytes());
+                    Self::STATUS_OK
+                }
+                None => Self::STATUS_BAD_HANDLE,
+            },
+            _ => Self::STATUS_BAD_COMMAND,
+        }
+    }
+}
+
+impl Device for Semihost {
+    fn read(&mut self, offset: u32) -> u8 {
+        match offset {
+            Self::STATUS => self.status,
+            2 => self.length as u8,
+            3 => (self.length >> 8) as u8,
+            Self::HANDLE => self.handle,
+            Self::ARG => self.arg,
+            Self::BUFFER.. => self.buffer.get((offset - Self::BUFFER) as usize).copied().unwrap_or(0xFF),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        match offset {
+            Self::COMMAND => self.status = self.run(val),
+            2 => self.length = (self.length & 0xFF00) | val as u16,
+            3 => self.length = (self.length & 0x00FF) | (val as u16) << 8,
+            Self::HANDLE => self.handle = val,
+            Self::ARG => self.arg = val,
+            Self::BUFFER.. => {
+                if let Some(byte) = self.buffer.get_mut((offset - Self::BUFFER) as usize) {
+                    *byte = val;
+                }
+            }
+            _ => {}
+        }
+    }
+}
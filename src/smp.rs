@@ -0,0 +1,303 @@
+use std::cell::{Cell, RefCell};
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::bus::{Bus, BusFault, MemoryMap, WriteProtect};
+use crate::cpu::{Cpu, StepResult, StopReason};
+use crate::device::Device;
+use crate::u24::U24;
+
+/// State shared by all the cores of an `SmpMachine`.
+struct Shared {
+    map: RefCell<MemoryMap>,
+    control: Range<u32>,
+    cores: u8,
+    /// Interrupt lines asserted by devices in the last round
+    device_lines: Cell<u8>,
+    /// Pending inter-processor interrupts, one bit per core
+    ipi: Cell<u32>,
+}
+
+/// One core's view of an `SmpMachine`'s memory map. All cores see the same
+/// RAM and devices, except for the core control registers, which answer
+/// for the core making the access:
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0      | CORE_ID: the number of this core, from 0 | read |
+/// | 1      | CORE_COUNT: the number of cores | read |
+/// | 2      | IPI_SEND: writing a core number interrupts that core | write |
+/// | 3      | IPI_ACK: writing any value clears this core's interrupt | write |
+///
+/// Device interrupts go to core 0; the inter-processor interrupt arrives
+/// on `SmpMachine::IPI_LINE`.
+pub struct CoreBus {
+    shared: Rc<Shared>,
+    id: u8,
+}
+
+impl CoreBus {
+    pub const CORE_ID: u32 = 0;
+    pub const CORE_COUNT: u32 = 1;
+    pub const IPI_SEND: u32 = 2;
+    pub const IPI_ACK: u32 = 3;
+
+    /// Number of bytes of control registers to map.
+    pub const SIZE: u32 = 4;
+
+    /// The control register at `addr`, if it is one.
+    fn control_offset(&self, addr: U24) -> Option<u32> {
+        let control = &self.shared.control;
+        control.contains(&addr.value()).then(|| addr.value() - control.start)
+    }
+}
+
+impl Bus for CoreBus {
+    fn size(&self) -> usize {
+        self.shared.map.borrow().size()
+    }
+
+    fn read8(&mut self, addr: U24) -> u8 {
+        match self.control_offset(addr) {
+            Some(Self::CORE_ID) => self.id,
+            Some(Self::CORE_COUNT) => self.shared.cores,
+            Some(_) => 0xFF,
+            None => self.shared.map.borrow_mut().read8(addr),
+        }
+    }
+
+    fn write8(&mut self, addr: U24, val: u8) {
+        match self.control_offset(addr) {
+            Some(Self::IPI_SEND) if val < self.shared.cores => {
+                self.shared.ipi.set(self.shared.ipi.get() | 1 << val);
+            }
+            Some(Self::IPI_ACK) => self.shared.ipi.set(self.shared.ipi.get() & !(1 << self.id)),
+            Some(_) => {}
+            None => self.shared.map.borrow_mut().write8(addr, val),
+        }
+    }
+
+    fn take_fault(&mut self) -> Option<BusFault> {
+        self.shared.map.borrow_mut().take_fault()
+    }
+
+    /// Devices are ticked once per round by the machine, not by each core,
+    /// so this only reports the lines this core sees.
+    fn tick(&mut self, _cycles: u64) -> u8 {
+        let mut lines = if self.id == 0 { self.shared.device_lines.get() } else { 0 };
+        if self.shared.ipi.get() & 1 << self.id != 0 {
+            lines |= 1 << SmpMachine::IPI_LINE;
+        }
+        lines
+    }
+
+    fn has_devices(&self) -> bool {
+        true
+    }
+
+    fn power_off(&self) -> Option<u8> {
+        self.shared.map.borrow().power_off()
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.shared.map.borrow().dump()
+    }
+
+    fn restore(&mut self, data: &[u8]) -> bool {
+        self.shared.map.borrow_mut().restore(data)
+    }
+
+    fn load(&mut self, addr: U24, bytes: &[u8]) {
+        self.shared.map.borrow_mut().load(addr, bytes);
+    }
+}
+
+/// Why an `SmpMachine` run stopped, and the core it concerns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreStop {
+    pub core: usize,
+    pub reason: StopReason,
+}
+
+/// A machine with several CPU cores sharing one memory map. The cores run
+/// interleaved, one instruction each per round, and devices are ticked
+/// once per round. Every core starts at the reset vector, so a program
+/// reads CORE_ID from the control registers (see `CoreBus`) to decide
+/// what each core does.
+pub struct SmpMachine {
+    pub cores: Vec<Cpu<CoreBus>>,
+    shared: Rc<Shared>,
+}
+
+impl SmpMachine {
+    /// The interrupt line inter-processor interrupts arrive on.
+    pub const IPI_LINE: u8 = 7;
+
+    /// Bytes of stack each core gets; core `n`'s stack starts `n` stacks
+    /// below the top of memory.
+    pub const STACK_SIZE: u32 = 0x1000;
+
+    /// Create a machine with `cores` cores (at most 32), `ram_size` bytes
+    /// of RAM and the core control registers at `control_base`, which must
+    /// leave them inside the 24-bit address space.
+    pub fn new(ram_size: usize, cores: usize, control_base: u32) -> Self {
+        assert!((1..=32).contains(&cores), "an SmpMachine has 1 to 32 cores");
+        let control_end = control_base
+            .checked_add(CoreBus::SIZE)
+            .filter(|end| *end <= 0x1000000)
+            .expect("core control registers run past the end of the address space");
+        let shared = Rc::new(Shared {
+            map: RefCell::new(MemoryMap::new(ram_size)),
+            control: control_base..control_end,
+            cores: cores as u8,
+            device_lines: Cell::new(0),
+            ipi: Cell::new(0),
+        });
+        let cores = (0..cores)
+            .map(|id| Cpu::with_bus(CoreBus { shared: shared.clone(), id: id as u8 }))
+            .collect();
+        let mut machine = SmpMachine { cores, shared };
+        machine.reset();
+        machine
+    }
+
+    /// Map a device over `range`; see `MemoryMap::map_device`.
+    pub fn map_device(&mut self, range: Range<u32>, device: impl Device + 'static) {
+        self.shared.map.borrow_mut().map_device(range, device);
+    }
+
+    /// Copy `bytes` into memory at `addr`.
+    pub fn load(&mut self, addr: U24, bytes: &[u8]) {
+        self.shared.map.borrow_mut().load(addr, bytes);
+    }
+
+    /// Copy `bytes` into memory at `addr` and make that range read-only,
+    /// ignoring writes as a real ROM would.
+    pub fn load_rom(&mut self, addr: U24, bytes: &[u8]) {
+        let mut map = self.shared.map.borrow_mut();
+        map.load(addr, bytes);
+        let start = addr.value();
+        map.protect(start..start + bytes.len() as u32, WriteProtect::Ignore);
+    }
+
+    /// Reset every core and give each its own stack. Pending
+    /// inter-processor interrupts are dropped; memory and devices are left
+    /// as they are.
+    pub fn reset(&mut self) {
+        self.shared.ipi.set(0);
+        self.shared.device_lines.set(0);
+        for (id, core) in self.cores.iter_mut().enumerate() {
+            core.reset();
+            core.sp -= id as u32 * Self::STACK_SIZE;
+            core.fp = core.sp;
+        }
+    }
+
+    /// Run until every core has halted, or something stops the machine:
+    /// an error or watchpoint on one core, a power-off, or all cores
+    /// waiting with nothing to wake them.
+    pub fn run(&mut self) -> CoreStop {
+        self.run_with(None)
+    }
+
+    /// Run at most `rounds` rounds, stopping with `StopReason::Budget`
+    /// if the machine is still going.
+    pub fn run_for(&mut self, rounds: u64) -> CoreStop {
+        self.run_with(Some(rounds))
+    }
+
+    fn run_with(&mut self, rounds: Option<u64>) -> CoreStop {
+        for core in &mut self.cores {
+            core.is_running = true;
+        }
+        let mut last = 0;
+        let mut round = 0;
+        while rounds.is_none_or(|rounds| round < rounds) {
+            let mut running = false;
+            let mut idle = true;
+            for (id, core) in self.cores.iter_mut().enumerate() {
+                if !core.is_running {
+                    continue;
+                }
+                running = true;
+                last = id;
+                match core.step() {
+                    Err(err) => return CoreStop { core: id, reason: StopReason::Error(err) },
+                    Ok(StepResult::Watchpoint(_, hit)) => {
+                        return CoreStop { core: id, reason: StopReason::Watchpoint(hit) };
+                    }
+                    Ok(_) => {}
+                }
+                if let Some(code) = core.bus.power_off() {
+                    return CoreStop { core: id, reason: StopReason::PowerOff(code) };
+                }
+                if !core.is_waiting || core.irq_pending() {
+                    idle = false;
+                }
+            }
+            if !running {
+                return CoreStop { core: last, reason: StopReason::Halted };
+            }
+
+            let mut map = self.shared.map.borrow_mut();
+            self.shared.device_lines.set(map.tick(1));
+            if idle && self.shared.ipi.get() == 0 && !map.has_devices() {
+                return CoreStop { core: last, reason: StopReason::Waiting };
+            }
+            round += 1;
+        }
+        CoreStop { core: last, reason: StopReason::Budget }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTROL: u32 = 0xFF00;
+
+    fn control(reg: u32) -> U24 {
+        U24::new(CONTROL + reg)
+    }
+
+    #[test]
+    fn control_registers_answer_for_the_core_reading_them() {
+        let mut machine = SmpMachine::new(0x10000, 3, CONTROL);
+        for (id, core) in machine.cores.iter_mut().enumerate() {
+            assert_eq!(core.bus.read8(control(CoreBus::CORE_ID)), id as u8);
+            assert_eq!(core.bus.read8(control(CoreBus::CORE_COUNT)), 3);
+        }
+    }
+
+    #[test]
+    fn ipi_interrupts_and_is_acknowledged_by_the_target_only() {
+        let mut machine = SmpMachine::new(0x10000, 3, CONTROL);
+        let ipi = 1 << SmpMachine::IPI_LINE;
+        machine.cores[0].bus.write8(control(CoreBus::IPI_SEND), 2);
+        let lines: Vec<u8> = machine.cores.iter_mut().map(|core| core.bus.tick(1)).collect();
+        assert_eq!(lines, [0, 0, ipi]);
+
+        // Another core's ACK leaves it pending
+        machine.cores[1].bus.write8(control(CoreBus::IPI_ACK), 0);
+        assert_eq!(machine.cores[2].bus.tick(1), ipi);
+
+        machine.cores[2].bus.write8(control(CoreBus::IPI_ACK), 0);
+        assert_eq!(machine.cores[2].bus.tick(1), 0);
+    }
+
+    #[test]
+    fn each_core_has_its_own_stack() {
+        let machine = SmpMachine::new(0x10000, 4, CONTROL);
+        let top = machine.cores[0].sp;
+        for (id, core) in machine.cores.iter().enumerate() {
+            assert_eq!(core.sp, top - id as u32 * SmpMachine::STACK_SIZE);
+            assert_eq!(core.fp, core.sp);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "past the end of the address space")]
+    fn control_registers_must_fit_the_address_space() {
+        SmpMachine::new(0x10000, 2, u32::MAX - 1);
+    }
+}
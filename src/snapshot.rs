@@ -0,0 +1,174 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bus::Bus;
+use crate::cpu::Cpu;
+use crate::u24::U24;
+
+/// Saved CPU and memory state, taken with `Cpu::snapshot`. Serialize it
+/// with any serde format to make save states.
+///
+/// Host configuration (features, decode mode, traps, reset vector, hooks,
+/// watchpoints and tracing) is not part of the snapshot, and neither is
+/// device state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Format version; see `Snapshot::VERSION`
+    pub version: u32,
+
+    pub pc: U24,
+    pub regs: [u8; 9],
+    #[cfg(feature = "fpu")]
+    #[serde(default)]
+    pub fregs: [f32; 8],
+    pub flags: u8,
+    pub sp: U24,
+    pub fp: U24,
+    pub ir: u16,
+    pub ic: U24,
+    pub is_running: bool,
+    pub is_waiting: bool,
+    pub vector_base: U24,
+    pub irq: Option<u8>,
+    pub irq_lines: u8,
+    /// Lines devices were asserting as of the last tick, so an interrupt
+    /// a device raised is still taken after a restore
+    pub device_lines: u8,
+    pub skipped_ops: u64,
+
+    /// Memory contents as saved by `Bus::dump`
+    pub memory: Vec<u8>,
+}
+
+impl Snapshot {
+    /// The format written by this version of the crate. Bump it whenever
+    /// the fields change.
+    pub const VERSION: u32 = 2;
+}
+
+/// Why `Cpu::restore` rejected a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The snapshot was written in a different format version.
+    Version(u32),
+
+    /// The saved memory does not fit the CPU's bus.
+    MemoryLayout,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Version(version) => write!(
+                f,
+                "snapshot version {} is not supported (expected {})",
+                version,
+                Snapshot::VERSION
+            ),
+            SnapshotError::MemoryLayout => write!(f, "snapshot memory does not match the bus"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl<B: Bus> Cpu<B> {
+    /// Save the CPU registers and the memory behind the bus.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: Snapshot::VERSION,
+            pc: self.pc,
+            regs: self.regs,
+            #[cfg(feature = "fpu")]
+            fregs: self.fregs,
+            flags: self.flags,
+            sp: self.sp,
+            fp: self.fp,
+            ir: self.ir,
+            ic: self.ic,
+            is_running: self.is_running,
+            is_waiting: self.is_waiting,
+            vector_base: self.vector_base,
+            irq: self.irq,
+            irq_lines: self.irq_lines,
+            device_lines: self.device_lines,
+            skipped_ops: self.skipped_ops,
+            memory: self.bus.dump(),
+        }
+    }
+
+    /// Load state saved with `snapshot`. On error nothing is changed.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if snapshot.version != Snapshot::VERSION {
+            return Err(SnapshotError::Version(snapshot.version));
+        }
+        if !self.bus.restore(&snapshot.memory) {
+            return Err(SnapshotError::MemoryLayout);
+        }
+        self.pc = snapshot.pc;
+        self.regs = snapshot.regs;
+        #[cfg(feature = "fpu")]
+        {
+            self.fregs = snapshot.fregs;
+        }
+        self.flags = snapshot.flags;
+        self.sp = snapshot.sp;
+        self.fp = snapshot.fp;
+        self.ir = snapshot.ir;
+        self.ic = snapshot.ic;
+        self.is_running = snapshot.is_running;
+        self.is_waiting = snapshot.is_waiting;
+        self.vector_base = snapshot.vector_base;
+        self.irq = snapshot.irq;
+        self.irq_lines = snapshot.irq_lines;
+        self.device_lines = snapshot.device_lines;
+        self.skipped_ops = snapshot.skipped_ops;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_undoes_changes_since_the_snapshot() {
+        let mut cpu = Cpu::new();
+        cpu.pc = U24::new(0x0123);
+        cpu.regs = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        cpu.flags = Cpu::FLAG_CARRY;
+        cpu.device_lines = 0b100;
+        cpu.mem_write(U24::new(0x4000), 0xAB);
+        let snapshot = cpu.snapshot();
+
+        cpu.pc = U24::new(0);
+        cpu.regs = [0; 9];
+        cpu.flags = 0;
+        cpu.device_lines = 0;
+        cpu.mem_write(U24::new(0x4000), 0);
+        cpu.sp -= 3;
+
+        cpu.restore(&snapshot).unwrap();
+        assert_eq!(cpu.snapshot(), snapshot);
+        assert_eq!(cpu.mem_read(U24::new(0x4000)), 0xAB);
+        assert!(cpu.irq_pending());
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut cpu = Cpu::new();
+        let mut snapshot = cpu.snapshot();
+        snapshot.version = Snapshot::VERSION + 1;
+        snapshot.pc = U24::new(0x0123);
+        assert_eq!(cpu.restore(&snapshot), Err(SnapshotError::Version(Snapshot::VERSION + 1)));
+        assert_eq!(cpu.pc, U24::new(0));
+    }
+
+    #[test]
+    fn memory_of_another_size_is_rejected() {
+        let snapshot = Cpu::with_memory_size(0x100).snapshot();
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.restore(&snapshot), Err(SnapshotError::MemoryLayout));
+    }
+}
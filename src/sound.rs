@@ -0,0 +1,106 @@
+use crate::device::Device;
+
+/// A tone requested by the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tone {
+    /// Frequency in Hz
+    pub frequency: u16,
+    /// Duration in milliseconds
+    pub duration_ms: u16,
+}
+
+/// Called with each tone the guest plays.
+pub type ToneOutput = Box<dyn FnMut(Tone)>;
+
+/// Simple tone generator. The guest sets FREQUENCY and DURATION, then
+/// writes to PLAY. Every tone is recorded in `tones`, and also passed to
+/// the output set with `set_output`, e.g. `audio_output()` with the
+/// `audio` feature.
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0-1    | FREQUENCY in Hz (16-bit, little-endian) | read/write |
+/// | 2-3    | DURATION in ms (16-bit, little-endian) | read/write |
+/// | 4      | PLAY: writing any value plays the tone | write |
+pub struct Beeper {
+    frequency: u16,
+    duration_ms: u16,
+    /// Tones played so far, oldest first
+    pub tones: Vec<Tone>,
+    output: Option<ToneOutput>,
+}
+
+impl Beeper {
+    pub const FREQUENCY: u32 = 0;
+    pub const DURATION: u32 = 2;
+    pub const PLAY: u32 = 4;
+
+    /// Number of bytes of registers to map.
+    pub const SIZE: u32 = 5;
+
+    /// Create a silent beeper with no output.
+    pub fn new() -> Self {
+        Beeper { frequency: 0, duration_ms: 0, tones: Vec::new(), output: None }
+    }
+
+    /// Pass every tone played from now on to `output`.
+    pub fn set_output(&mut self, output: impl FnMut(Tone) + 'static) {
+        self.output = Some(Box::new(output));
+    }
+}
+
+impl Default for Beeper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for Beeper {
+    fn read(&mut self, offset: u32) -> u8 {
+        match offset {
+            0 => self.frequency as u8,
+            1 => (self.frequency >> 8) as u8,
+            2 => self.duration_ms as u8,
+            3 => (self.duration_ms >> 8) as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        match offset {
+            0 => self.frequency = (self.frequency & 0xFF00) | val as u16,
+            1 => self.frequency = (self.frequency & 0x00FF) | (val as u16) << 8,
+            2 => self.duration_ms = (self.duration_ms & 0xFF00) | val as u16,
+            3 => self.duration_ms = (self.duration_ms & 0x00FF) | (val as u16) << 8,
+            Self::PLAY => {
+                let tone = Tone { frequency: self.frequency, duration_ms: self.duration_ms };
+                self.tones.push(tone);
+                if let Some(output) = &mut self.output {
+                    output(tone);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Play tones on the host's default audio device. Each call blocks until
+/// the tone has finished, so the guest runs no faster than its music.
+/// Returns `None` if there is no audio device.
+#[cfg(feature = "audio")]
+pub fn audio_output() -> Option<impl FnMut(Tone)> {
+    use std::time::Duration;
+    use rodio::{OutputStream, Sink, Source, source::SineWave};
+
+    let (stream, handle) = OutputStream::try_default().ok()?;
+    let sink = Sink::try_new(&handle).ok()?;
+    Some(move |tone: Tone| {
+        // Keep the stream alive as long as the output
+        let _ = &stream;
+        let wave = SineWave::new(tone.frequency as f32)
+            .take_duration(Duration::from_millis(tone.duration_ms as u64))
+            .amplify(0.2);
+        sink.append(wave);
+        sink.sleep_until_end();
+    })
+}
@@ -0,0 +1,50 @@
+use crate::bus::Bus;
+use crate::cpu::{Cpu, CpuError, StepResult};
+use crate::u24::U24;
+
+/// One step yielded by `Cpu::steps`.
+#[derive(Debug)]
+pub struct ExecutedOp {
+    /// Address of the instruction
+    pub pc: U24,
+
+    /// What the step did; `StepResult::Waiting` only while devices on the
+    /// bus are being ticked during a WAIT
+    pub result: StepResult,
+}
+
+/// Iterator returned by `Cpu::steps`.
+pub struct Steps<'a, B: Bus> {
+    cpu: &'a mut Cpu<B>,
+    done: bool,
+}
+
+impl<B: Bus> Iterator for Steps<'_, B> {
+    type Item = Result<ExecutedOp, CpuError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idle = self.cpu.is_waiting && !self.cpu.irq_pending() && !self.cpu.bus.has_devices();
+        if self.done || !self.cpu.is_running || idle {
+            return None;
+        }
+        match self.cpu.step() {
+            Ok(result) => Some(Ok(ExecutedOp { pc: self.cpu.op_pc, result })),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    /// Execute instructions one per iteration, starting at the current PC.
+    /// The iterator ends after HLT, after an error (which it yields), or
+    /// when the CPU waits with no interrupt raised and no devices that
+    /// could raise one. Breakpoints are not
+    /// checked; use adapters such as `take_while` instead.
+    pub fn steps(&mut self) -> Steps<'_, B> {
+        self.is_running = true;
+        Steps { cpu: self, done: false }
+    }
+}
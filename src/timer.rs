@@ -0,0 +1,93 @@
+use crate::device::Device;
+
+/// Programmable down-counting timer. While enabled, COUNTER drops by one
+/// every CPU cycle; when it reaches zero the timer sets EXPIRED and, if
+/// interrupts are enabled, asserts its CPU interrupt line until the
+/// handler clears EXPIRED. In periodic mode COUNTER is then reloaded from
+/// RELOAD, otherwise the timer stops.
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0-1    | RELOAD (16-bit, little-endian) | read/write |
+/// | 2-3    | COUNTER (16-bit, little-endian) | read/write |
+/// | 4      | CONTROL: bit 0 enable, bit 1 periodic, bit 2 interrupt enable | read/write |
+/// | 5      | STATUS: bit 0 expired; writing 1 clears it | read/write |
+#[derive(Debug, Clone)]
+pub struct Timer {
+    /// The CPU interrupt line the timer asserts
+    pub line: u8,
+    reload: u16,
+    counter: u16,
+    control: u8,
+    expired: bool,
+}
+
+impl Timer {
+    pub const RELOAD: u32 = 0;
+    pub const COUNTER: u32 = 2;
+    pub const CONTROL: u32 = 4;
+    pub const STATUS: u32 = 5;
+
+    /// Number of bytes of registers to map.
+    pub const SIZE: u32 = 6;
+
+    pub const CONTROL_ENABLE: u8 = 0x01;
+    pub const CONTROL_PERIODIC: u8 = 0x02;
+    pub const CONTROL_IRQ: u8 = 0x04;
+
+    /// Create a stopped timer that asserts CPU interrupt line `line`.
+    pub fn new(line: u8) -> Self {
+        Timer { line, reload: 0, counter: 0, control: 0, expired: false }
+    }
+
+    fn expire(&mut self) {
+        self.expired = true;
+        if self.control & Self::CONTROL_PERIODIC != 0 && self.reload != 0 {
+            self.counter = self.reload;
+        } else {
+            self.counter = 0;
+            self.control &= !Self::CONTROL_ENABLE;
+        }
+    }
+}
+
+impl Device for Timer {
+    fn read(&mut self, offset: u32) -> u8 {
+        match offset {
+            0 => self.reload as u8,
+            1 => (self.reload >> 8) as u8,
+            2 => self.counter as u8,
+            3 => (self.counter >> 8) as u8,
+            Self::CONTROL => self.control,
+            Self::STATUS => self.expired as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        match offset {
+            0 => self.reload = (self.reload & 0xFF00) | val as u16,
+            1 => self.reload = (self.reload & 0x00FF) | (val as u16) << 8,
+            2 => self.counter = (self.counter & 0xFF00) | val as u16,
+            3 => self.counter = (self.counter & 0x00FF) | (val as u16) << 8,
+            Self::CONTROL => self.control = val & 0x07,
+            Self::STATUS if val & 1 != 0 => self.expired = false,
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        let mut remaining = cycles;
+        while self.control & Self::CONTROL_ENABLE != 0 && remaining >= self.counter as u64 {
+            remaining -= self.counter as u64;
+            self.expire();
+        }
+        if self.control & Self::CONTROL_ENABLE != 0 {
+            self.counter -= remaining as u16;
+        }
+    }
+
+    fn irq(&self) -> Option<u8> {
+        (self.expired && self.control & Self::CONTROL_IRQ != 0).then_some(self.line)
+    }
+}
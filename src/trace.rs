@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::op::Op;
+use crate::u24::U24;
+
+/// One executed instruction, as recorded in a `TraceBuffer`.
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    /// Address of the instruction
+    pub pc: U24,
+
+    /// The decoded instruction
+    pub op: Op,
+
+    /// Flags after the instruction executed
+    pub flags: u8,
+}
+
+/// Ring buffer holding the last `capacity` executed instructions.
+#[derive(Debug, Clone)]
+pub struct TraceBuffer {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+}
+
+impl TraceBuffer {
+    /// Create an empty buffer that keeps at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        TraceBuffer { entries: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Record an entry, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// The recorded entries, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl fmt::Display for TraceBuffer {
+    /// One line per instruction, oldest first: PC, opcode, the operand
+    /// bytes it was encoded with and the flags afterwards.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let count = ((entry.op.code.clone() as u16 & 0xE00) >> 9) as usize;
+            write!(f, "{:06X}  {:<8}", entry.pc, format!("{:?}", entry.op.code))?;
+            for byte in &entry.op.operands[..count] {
+                write!(f, " {:02X}", byte)?;
+            }
+            writeln!(f, "{:width$}  flags={:02X}", "", entry.flags, width = 3 * (4 - count))?;
+        }
+        Ok(())
+    }
+}
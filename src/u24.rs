@@ -3,6 +3,7 @@ use std::str::FromStr;
 use std::num::ParseIntError;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct U24(u32);
 
 impl U24 {
@@ -71,7 +72,7 @@ impl Add for U24 {
 impl Add<u32> for U24 {
     type Output = U24;
     fn add(self, rhs: u32) -> U24 {
-        U24::new(self.0.wrapping_add(rhs & Self::MASK))
+        U24::new(self.0.wrapping_add(rhs))
     }
 }
 
@@ -85,31 +86,31 @@ impl Sub for U24 {
 impl Sub<u32> for U24 {
     type Output = U24;
     fn sub(self, rhs: u32) -> U24 {
-        U24::new(self.0.wrapping_sub(rhs & Self::MASK))
+        U24::new(self.0.wrapping_sub(rhs))
     }
 }
 
 impl AddAssign for U24 {
     fn add_assign(&mut self, rhs: U24) {
-        self.0 = (self.0.wrapping_add(rhs.0)) & Self::MASK;
+        *self = *self + rhs;
     }
 }
 
 impl SubAssign for U24 {
     fn sub_assign(&mut self, rhs: U24) {
-        self.0 = (self.0.wrapping_sub(rhs.0)) & Self::MASK;
+        *self = *self - rhs;
     }
 }
 
 impl AddAssign<u32> for U24 {
     fn add_assign(&mut self, rhs: u32) {
-        self.0 = (self.0.wrapping_add(rhs & Self::MASK)) & Self::MASK;
+        *self = *self + rhs;
     }
 }
 
 impl SubAssign<u32> for U24 {
     fn sub_assign(&mut self, rhs: u32) {
-        self.0 = (self.0.wrapping_sub(rhs & Self::MASK)) & Self::MASK;
+        *self = *self - rhs;
     }
 }
 
@@ -235,14 +236,79 @@ impl PartialOrd for U24 {
     }
 }
 
+/// Parse a decimal number, or hex, binary or octal with a `0x`, `0b` or
+/// `0o` prefix. Digits may be separated with `_`, as in `0b1010_0001`.
+/// There is no sign, before or after the prefix.
+pub fn parse_number(s: &str) -> Result<u32, ParseIntError> {
+    let (radix, digits) = match s.get(..2).map(str::to_ascii_lowercase).as_deref() {
+        Some("0x") => (16, &s[2..]),
+        Some("0b") => (2, &s[2..]),
+        Some("0o") => (8, &s[2..]),
+        _ => (10, s),
+    };
+    if digits.starts_with(['+', '-']) {
+        // `from_str_radix` would take the sign; on its own it is an
+        // invalid digit
+        return u32::from_str_radix(&digits[..1], radix);
+    }
+    u32::from_str_radix(&digits.replace('_', ""), radix)
+}
+
+/// Why a string is not a `U24`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseU24Error {
+    /// The string is not a number; see `parse_number`.
+    Invalid(ParseIntError),
+
+    /// The number is above 0xFFFFFF.
+    TooLarge(u32),
+}
+
+impl std::fmt::Display for ParseU24Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseU24Error::Invalid(err) => write!(f, "{}", err),
+            ParseU24Error::TooLarge(value) => write!(f, "0x{:X} does not fit in 24 bits", value),
+        }
+    }
+}
+
+impl std::error::Error for ParseU24Error {}
+
+impl From<ParseIntError> for ParseU24Error {
+    fn from(err: ParseIntError) -> Self {
+        ParseU24Error::Invalid(err)
+    }
+}
+
 impl FromStr for U24 {
-    type Err = ParseIntError;
+    type Err = ParseU24Error;
 
+    /// Parse a number as `parse_number` does. Unlike `U24::new`, a value
+    /// above 0xFFFFFF is an error rather than being masked.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let radix = if s.starts_with("0x") { 16 } else { 10 };
-        // Parse as u32 first
-        let v = u32::from_str_radix(s, radix)?;
-        // Mask to 24 bits
+        let v = parse_number(s)?;
+        if v > 0xFF_FFFF {
+            return Err(ParseU24Error::TooLarge(v));
+        }
         Ok(U24::new(v))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_number_rejects_a_sign_after_the_prefix() {
+        assert_eq!(parse_number("0x1_F"), Ok(0x1F));
+        assert!(parse_number("0x+5").is_err());
+        assert!(parse_number("0b+1").is_err());
+        assert!(parse_number("0o-7").is_err());
+    }
+
+    #[test]
+    fn from_str_rejects_values_above_24_bits() {
+        assert_eq!("0xFFFFFF".parse::<U24>(), Ok(U24::new(0xFFFFFF)));
+        assert_eq!("0x1000000".parse::<U24>(), Err(ParseU24Error::TooLarge(0x1000000)));
+    }
+}
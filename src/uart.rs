@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::device::Device;
+
+/// The host side of a serial line.
+pub trait SerialPort {
+    /// Return every byte that has arrived since the last call, without
+    /// blocking.
+    fn receive(&mut self, buf: &mut VecDeque<u8>);
+
+    /// Send a byte from the guest.
+    fn send(&mut self, byte: u8);
+}
+
+/// Serial port device. Received bytes are buffered until the guest reads
+/// DATA; writing DATA transmits a byte straight away.
+///
+/// | Offset | Register | Access |
+/// |--------|----------|--------|
+/// | 0      | DATA: next received byte (0 if none) / byte to send | read/write |
+/// | 1      | STATUS: bit 0 received data ready, bit 1 ready to send | read |
+/// | 2      | CONTROL: bit 0 interrupt while received data is ready | read/write |
+pub struct Uart {
+    /// The CPU interrupt line the UART asserts
+    pub line: u8,
+    port: Box<dyn SerialPort>,
+    rx: VecDeque<u8>,
+    control: u8,
+    cycles: u64,
+}
+
+impl Uart {
+    pub const DATA: u32 = 0;
+    pub const STATUS: u32 = 1;
+    pub const CONTROL: u32 = 2;
+
+    /// Number of bytes of registers to map.
+    pub const SIZE: u32 = 3;
+
+    pub const STATUS_RX_READY: u8 = 0x01;
+    pub const STATUS_TX_READY: u8 = 0x02;
+    pub const CONTROL_RX_IRQ: u8 = 0x01;
+
+    /// How many cycles pass between checks for received data
+    const POLL_INTERVAL: u64 = 256;
+
+    /// Create a UART connected to `port` that asserts CPU interrupt line
+    /// `line`.
+    pub fn new(port: impl SerialPort + 'static, line: u8) -> Self {
+        Uart { line, port: Box::new(port), rx: VecDeque::new(), control: 0, cycles: 0 }
+    }
+}
+
+impl Device for Uart {
+    fn read(&mut self, offset: u32) -> u8 {
+        match offset {
+            Self::DATA => self.rx.pop_front().unwrap_or(0),
+            Self::STATUS => {
+                if self.rx.is_empty() {
+                    self.port.receive(&mut self.rx);
+                }
+                let ready = if self.rx.is_empty() { 0 } else { Self::STATUS_RX_READY };
+                ready | Self::STATUS_TX_READY
+            }
+            Self::CONTROL => self.control,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        match offset {
+            Self::DATA => self.port.send(val),
+            Self::CONTROL => self.control = val & Self::CONTROL_RX_IRQ,
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.cycles += cycles;
+        if self.cycles >= Self::POLL_INTERVAL {
+            self.cycles = 0;
+            self.port.receive(&mut self.rx);
+        }
+    }
+
+    fn irq(&self) -> Option<u8> {
+        (self.control & Self::CONTROL_RX_IRQ != 0 && !self.rx.is_empty()).then_some(self.line)
+    }
+}
+
+/// Serial line on the host's stdin and stdout. Stdin is read on a
+/// background thread so the guest never blocks on it.
+pub struct StdioPort {
+    input: Receiver<u8>,
+}
+
+impl StdioPort {
+    pub fn new() -> Self {
+        let (sender, input) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdin = io::stdin();
+            let mut chunk = [0; 256];
+            while let Ok(n @ 1..) = stdin.read(&mut chunk) {
+                if chunk[..n].iter().any(|&byte| sender.send(byte).is_err()) {
+                    break;
+                }
+            }
+        });
+        StdioPort { input }
+    }
+}
+
+impl Default for StdioPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialPort for StdioPort {
+    fn receive(&mut self, buf: &mut VecDeque<u8>) {
+        buf.extend(self.input.try_iter());
+    }
+
+    fn send(&mut self, byte: u8) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&[byte]);
+        let _ = stdout.flush();
+    }
+}
+
+/// Serial line on a TCP port, e.g. for `telnet` or `nc`. One client is
+/// served at a time; when it disconnects the port accepts the next one.
+/// Bytes sent while nobody is connected are dropped.
+pub struct TcpPort {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+}
+
+impl TcpPort {
+    /// Listen on `addr`, e.g. `"127.0.0.1:2323"`.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpPort { listener, client: None })
+    }
+
+    /// The address the port is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    fn accept(&mut self) {
+        if self.client.is_none()
+            && let Ok((stream, _)) = self.listener.accept()
+            && stream.set_nonblocking(true).is_ok()
+        {
+            self.client = Some(stream);
+        }
+    }
+}
+
+impl SerialPort for TcpPort {
+    fn receive(&mut self, buf: &mut VecDeque<u8>) {
+        self.accept();
+        let Some(client) = &mut self.client else { return };
+        let mut chunk = [0; 256];
+        loop {
+            match client.read(&mut chunk) {
+                Ok(0) => {
+                    self.client = None;
+                    return;
+                }
+                Ok(n) => buf.extend(&chunk[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.client = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, byte: u8) {
+        self.accept();
+        if let Some(client) = &mut self.client {
+            match client.write(&[byte]) {
+                Ok(_) => {}
+                // The client isn't keeping up; drop the byte
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => self.client = None,
+            }
+        }
+    }
+}
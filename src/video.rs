@@ -0,0 +1,96 @@
+use std::fmt;
+
+use crate::device::Device;
+
+/// 80x25 text-mode display. Each cell is two bytes of video memory: the
+/// character code followed by an attribute byte, row by row from the top
+/// left. The attribute uses the CGA layout: bits 0-3 are the foreground
+/// colour, bits 4-6 the background colour (bit 7 is ignored).
+///
+/// `Display` renders the characters as plain text; `render_ansi` adds the
+/// colours as ANSI escape sequences for a terminal.
+#[derive(Debug, Clone)]
+pub struct TextVideo {
+    cells: Vec<u8>,
+}
+
+impl TextVideo {
+    pub const COLUMNS: usize = 80;
+    pub const ROWS: usize = 25;
+
+    /// Number of bytes of video memory to map.
+    pub const SIZE: u32 = (Self::COLUMNS * Self::ROWS * 2) as u32;
+
+    /// Attribute of a cleared cell: light grey on black.
+    pub const DEFAULT_ATTRIBUTE: u8 = 0x07;
+
+    /// Create a cleared screen.
+    pub fn new() -> Self {
+        let mut cells = Vec::with_capacity(Self::SIZE as usize);
+        for _ in 0..Self::COLUMNS * Self::ROWS {
+            cells.extend_from_slice(&[b' ', Self::DEFAULT_ATTRIBUTE]);
+        }
+        TextVideo { cells }
+    }
+
+    /// Character and attribute of the cell at (`col`, `row`).
+    pub fn cell(&self, col: usize, row: usize) -> (u8, u8) {
+        let i = (row * Self::COLUMNS + col) * 2;
+        (self.cells[i], self.cells[i + 1])
+    }
+
+    /// Render the screen with its colours, one line per row.
+    pub fn render_ansi(&self) -> String {
+        // CGA colour order to ANSI colour order
+        const ANSI: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+        let mut out = String::new();
+        for row in 0..Self::ROWS {
+            let mut current = None;
+            for col in 0..Self::COLUMNS {
+                let (ch, attr) = self.cell(col, row);
+                if current != Some(attr) {
+                    let fg = ANSI[(attr & 0x07) as usize] + if attr & 0x08 != 0 { 90 } else { 30 };
+                    let bg = ANSI[((attr >> 4) & 0x07) as usize] + 40;
+                    out.push_str(&format!("\x1b[{};{}m", fg, bg));
+                    current = Some(attr);
+                }
+                out.push(printable(ch));
+            }
+            out.push_str("\x1b[0m\n");
+        }
+        out
+    }
+}
+
+impl Default for TextVideo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn printable(ch: u8) -> char {
+    if ch.is_ascii_graphic() { ch as char } else { ' ' }
+}
+
+impl Device for TextVideo {
+    fn read(&mut self, offset: u32) -> u8 {
+        self.cells.get(offset as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        if let Some(byte) = self.cells.get_mut(offset as usize) {
+            *byte = val;
+        }
+    }
+}
+
+impl fmt::Display for TextVideo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..Self::ROWS {
+            let line: String = (0..Self::COLUMNS).map(|col| printable(self.cell(col, row).0)).collect();
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use crate::bus::Bus;
+use crate::cpu::Cpu;
+use crate::u24::U24;
+
+/// Which accesses a watchpoint triggers on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// A memory access made by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A watched address range.
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub range: Range<u32>,
+    pub kind: WatchKind,
+}
+
+/// The access that triggered a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    /// Address of the instruction that made the access
+    pub pc: U24,
+
+    /// The watched address that was accessed
+    pub addr: U24,
+
+    pub access: Access,
+
+    /// The byte read, or written
+    pub value: u8,
+}
+
+impl WatchKind {
+    fn matches(self, access: Access) -> bool {
+        matches!(
+            (self, access),
+            (WatchKind::ReadWrite, _) | (WatchKind::Read, Access::Read) | (WatchKind::Write, Access::Write)
+        )
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    /// Stop execution after any instruction that accesses `range` in a
+    /// way matching `kind`. Instruction fetches are not watched, only the
+    /// data accesses an instruction makes.
+    pub fn add_watchpoint(&mut self, range: Range<u32>, kind: WatchKind) {
+        self.watchpoints.push(Watchpoint { range, kind });
+    }
+
+    /// Remove all watchpoints.
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Record the first watched byte in an access of `bytes` at `addr`.
+    pub(crate) fn check_watch(&mut self, addr: U24, access: Access, bytes: &[u8]) {
+        if self.watch_hit.is_some() {
+            return;
+        }
+        for (i, value) in bytes.iter().enumerate() {
+            let addr = addr + i as u32;
+            let watched = self
+                .watchpoints
+                .iter()
+                .any(|w| w.kind.matches(access) && w.range.contains(&addr.value()));
+            if watched {
+                self.watch_hit = Some(WatchHit { pc: self.op_pc, addr, access, value: *value });
+                return;
+            }
+        }
+    }
+}